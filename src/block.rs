@@ -1,12 +1,22 @@
+use crate::errors::RustyCoinError;
+use crate::hasher::{Hasher, Sha256dHasher};
 use crate::transaction::Transaction;
-use crate::types::HashValue;
+use crate::types::{difficulty_ratio, nbits_to_target, HashValue};
+use log::{debug, info};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::fmt::Display;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::Instant;
+
+/// number of nonces between two progress logs of the mining loop
+const MINING_PROGRESS_INTERVAL: u64 = 1 << 20;
 
 /// The `Block` struct represents a block in the blockchain.
 ///
-/// tips: consider use log crate to print log
+/// mining and verification emit logs through the `log` crate, install any logger to see them
 /// # Fields
 ///
 /// * `version` - A floating point number representing the version of the block.
@@ -29,96 +39,338 @@ pub struct Block {
     pub(crate) difficulty: u32, // difficulty target for the proof of work, adjusted every 1024 blocks
     pub(crate) nonce: i64,      // random number
     pub(crate) data: Vec<Transaction>, // transactions
+    #[serde(skip)]
+    pub(crate) merkle_cache: MerkleCache, // layers of the merkle tree of `data`, computed on first use
+}
+
+/// the layers of the merkle tree of a block, from the transaction hashes up to the root,
+/// computed once by `Block::calc_merkle_root` or `Block::merkle_proof`.
+///
+/// the cache is never serialized and doesn't take part in comparisons,
+/// it must be cleared whenever the transactions change, see `Block::data_mut`
+#[derive(Debug, Clone, Default)]
+pub(crate) struct MerkleCache(OnceLock<Vec<Vec<HashValue>>>);
+
+impl PartialEq for MerkleCache {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+/// The header of a block: every field of the block except its hash and its transactions.
+///
+/// the hash of a block is the hash of its header, so light clients can sync the headers first
+/// and check the proof of work without downloading the transactions.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct BlockHeader {
+    pub(crate) version: String,        // version of the block
+    pub(crate) index: usize,           // block height
+    pub(crate) timestamp: u64, // time elapsed since the Unix Epoch (January 1, 1970) in seconds
+    pub(crate) prev_hash: HashValue, // previous block hash
+    pub(crate) merkle_root: HashValue, // merkle root of all the transactions
+    pub(crate) difficulty: u32, // difficulty target for the proof of work
+    pub(crate) nonce: i64,     // random number
+}
+
+impl BlockHeader {
+    /// the bytes of the header, the input of its hash and of the proof of work
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(self.version.as_bytes());
+        bytes.extend_from_slice(&self.index.to_be_bytes());
+        bytes.extend_from_slice(&self.timestamp.to_be_bytes());
+        bytes.extend_from_slice(&*self.prev_hash);
+        bytes.extend_from_slice(&*self.merkle_root);
+        bytes.extend_from_slice(&self.difficulty.to_be_bytes());
+        bytes.extend_from_slice(&self.nonce.to_be_bytes());
+        bytes
+    }
+
+    /// calculate the hash value of the header
+    pub fn sha256(&self) -> HashValue {
+        HashValue::new(Sha256::digest(self.to_bytes()).into())
+    }
 }
 
 impl Block {
-    /// calculate the target difficulty by the nBits in `difficulty`
-    ///
-    /// $ target\ threshold = b_2b_3b_4 \times 2^{8(b_1 - 3)} $
-    #[allow(clippy::identity_op)]
+    /// assemble a block from a received header and its transactions,
+    /// fails if the merkle root of `txs` does not match the one committed in the header
+    pub fn from_header_and_txs(
+        header: BlockHeader,
+        txs: Vec<Transaction>,
+    ) -> Result<Block, RustyCoinError> {
+        let block = Block {
+            hash: Sha256dHasher.digest(&header.to_bytes()),
+            version: header.version,
+            index: header.index,
+            timestamp: header.timestamp,
+            prev_hash: header.prev_hash,
+            merkle_root: header.merkle_root,
+            difficulty: header.difficulty,
+            nonce: header.nonce,
+            data: txs,
+            merkle_cache: MerkleCache::default(),
+        };
+        if block.calc_merkle_root() != block.merkle_root {
+            return Err(RustyCoinError::MerkleRootMismatch);
+        }
+        Ok(block)
+    }
+
+    /// get the header of the block
+    pub fn header(&self) -> BlockHeader {
+        BlockHeader {
+            version: self.version.clone(),
+            index: self.index,
+            timestamp: self.timestamp,
+            prev_hash: self.prev_hash,
+            merkle_root: self.merkle_root,
+            difficulty: self.difficulty,
+            nonce: self.nonce,
+        }
+    }
+
+    /// calculate the target difficulty by the nBits in `difficulty`, see `nbits_to_target`
     pub fn target_threshold(&self) -> HashValue {
-        let n_bit_bytes: [u8; 4] = self.difficulty.to_be_bytes();
-        let mut target = [0u8; 32];
-        let exp: isize = n_bit_bytes[0] as isize - 3;
-
-        let mut i = 0usize;
-        while i < 32 {
-            if i == (32 - exp - 2 - 1) as usize {
-                target[i] = n_bit_bytes[1];
-            } else if i == (32 - exp - 1 - 1) as usize {
-                target[i] = n_bit_bytes[2];
-            } else if i == (32 - exp - 0 - 1) as usize {
-                target[i] = n_bit_bytes[3];
-            } else {
-                target[i] = 0x00;
-            }
-            i += 1;
+        nbits_to_target(self.difficulty)
+    }
+
+    /// the difficulty as a human-readable number, how many times harder the block is than
+    /// difficulty 1, see `difficulty_ratio`
+    pub fn difficulty_human(&self) -> f64 {
+        difficulty_ratio(self.difficulty)
+    }
+
+    /// calculate the proof-of-work of the block, the expected number of hashes to mine it:
+    ///
+    /// $ work = 2^{256} / (target + 1) $
+    ///
+    /// - the work is saturated at `u128::MAX`
+    /// - a block with a zero target can never be mined (e.g. the genesis block), so it carries no work
+    pub fn work(&self) -> u128 {
+        let target = self.target_threshold();
+        let target_hi = u128::from_be_bytes(target[..16].try_into().unwrap());
+        let target_lo = u128::from_be_bytes(target[16..].try_into().unwrap());
+        if target_hi == 0 && target_lo == 0 {
+            return 0;
         }
 
-        HashValue::new(target)
+        // 2^256 / (target + 1) == !target / (target + 1) + 1, so the dividend fits in 256 bits
+        let (divisor_lo, carry) = target_lo.overflowing_add(1);
+        let (divisor_hi, overflow) = target_hi.overflowing_add(carry as u128);
+        if overflow {
+            return 1; // the target is 2^256 - 1
+        }
+        let (quotient_hi, quotient_lo) =
+            div_u256((!target_hi, !target_lo), (divisor_hi, divisor_lo));
+        if quotient_hi != 0 {
+            return u128::MAX;
+        }
+        quotient_lo.saturating_add(1)
     }
 
-    /// calculate the merkle root of all the transactions
+    /// calculate the merkle root of all the transactions,
+    /// the tree is built once and cached until the transactions change.
+    ///
+    /// the root of no transaction is all zeros, every block but the genesis block
+    /// has at least the coinbase transaction, so verification rejects it for them
     pub fn calc_merkle_root(&self) -> HashValue {
-        // return 0x00...000 directly if the data is empty
-        if self.data.is_empty() {
-            return HashValue::new([0; 32]);
+        match self.merkle_layers().last() {
+            Some(root_layer) if !root_layer.is_empty() => root_layer[0],
+            _ => HashValue::new([0; 32]), // 0x00...000 if the data is empty
         }
+    }
 
-        //calculate all the transactions' hash value
-        let mut hashes = self
+    /// generate a merkle proof that the transaction is included in this block:
+    /// the sibling hashes from the leaf up to the root, each with a flag
+    /// telling if the sibling is on the left (`true`) or on the right (`false`).
+    ///
+    /// a node promoted to the next level without a sibling adds nothing to the proof.
+    ///
+    /// return `None` if the transaction is not in this block
+    pub fn merkle_proof(&self, tx_id: HashValue) -> Option<Vec<(HashValue, bool)>> {
+        let mut position = self
             .data
             .iter()
-            .map(|transaction| transaction.sha256())
-            .collect::<Vec<HashValue>>();
-
-        // construct a merkle tree
-        while hashes.len() > 1 {
-            hashes = hashes
-                .chunks(2)
-                .map(|chunk| match *chunk {
-                    [hash] => hash,
-                    [hash1, hash2] => {
-                        let mut hasher = Sha256::new();
-                        hasher.update(hash1);
-                        hasher.update(hash2);
-                        let result = hasher.finalize().into();
-                        HashValue::new(result)
-                    }
-                    _ => unreachable!(), // panic immediately if none of the previous pattern get matched
-                })
-                .collect::<Vec<HashValue>>();
+            .position(|tx| tx.get_transaction_id() == tx_id)?;
+
+        let mut proof = vec![];
+        for hashes in self
+            .merkle_layers()
+            .iter()
+            .take_while(|hashes| hashes.len() > 1)
+        {
+            let sibling = position ^ 1;
+            if sibling < hashes.len() {
+                proof.push((hashes[sibling], sibling < position));
+            }
+            position /= 2;
         }
 
-        hashes[0]
+        Some(proof)
+    }
+
+    /// get the transactions for modification, clearing the cached merkle tree
+    pub(crate) fn data_mut(&mut self) -> &mut Vec<Transaction> {
+        self.merkle_cache = MerkleCache::default();
+        &mut self.data
+    }
+
+    /// the layers of the merkle tree of the transactions, from the cache if it has been built:
+    /// the hashes of the transactions first, the root last
+    fn merkle_layers(&self) -> &[Vec<HashValue>] {
+        self.merkle_cache.0.get_or_init(|| {
+            //calculate all the transactions' hash value
+            let mut layers = vec![self
+                .data
+                .iter()
+                .map(|transaction| transaction.sha256())
+                .collect::<Vec<HashValue>>()];
+            while layers.last().unwrap().len() > 1 {
+                layers.push(merkle_level(layers.last().unwrap()));
+            }
+            layers
+        })
     }
     /// POW algorithm,
     /// find the valid hash value by the proof of work
     pub fn update_hash_and_nonce(&mut self) {
-        let mut nonce = self.nonce;
+        self.update_hash_and_nonce_with(&Sha256dHasher)
+    }
+
+    /// POW algorithm with a given hash algorithm, see `update_hash_and_nonce`
+    pub fn update_hash_and_nonce_with<H: Hasher + ?Sized>(&mut self, hasher: &H) {
+        self.mine_while(hasher, || true);
+    }
+
+    /// POW algorithm with a deadline, so the miner can abandon stale work,
+    /// return `false` if the deadline passes before a valid hash is found.
+    ///
+    /// the hash of an unmined block is left as it is, while the nonce keeps the progress,
+    /// so calling it again resumes the search
+    pub fn mine_until(&mut self, deadline: Instant) -> bool {
+        self.mine_while(&Sha256dHasher, || Instant::now() < deadline)
+    }
+
+    /// POW algorithm that stops once `cancel` is set, e.g. by the node when a new block arrives,
+    /// return `false` if it is cancelled before a valid hash is found, see `mine_until`
+    pub fn mine_until_cancelled(&mut self, cancel: &AtomicBool) -> bool {
+        self.mine_while(&Sha256dHasher, || !cancel.load(Ordering::Relaxed))
+    }
+
+    /// try the nonces one by one while `keep_mining` is true,
+    /// return whether a valid hash is found
+    fn mine_while<H: Hasher + ?Sized>(
+        &mut self,
+        hasher: &H,
+        keep_mining: impl Fn() -> bool,
+    ) -> bool {
         let target_threshold = self.target_threshold();
-        let mut valid_hash = self.sha256().sha256();
+        let mut valid_hash = self.pow_hash(hasher);
+        let mut tried_nonces = 1u64;
 
-        while valid_hash > target_threshold {
-            nonce += 1;
-            self.nonce = nonce;
-            valid_hash = self.sha256().sha256();
+        while !valid_hash.meets_target(&target_threshold) {
+            if !keep_mining() {
+                debug!(
+                    "stopped mining block {} after {} nonces",
+                    self.index, tried_nonces
+                );
+                return false;
+            }
+            self.advance_nonce(1, 0);
+            valid_hash = self.pow_hash(hasher);
+            tried_nonces += 1;
+            if tried_nonces.is_multiple_of(MINING_PROGRESS_INTERVAL) {
+                debug!(
+                    "mining block {}: tried {} nonces, current nonce {}",
+                    self.index, tried_nonces, self.nonce
+                );
+            }
         }
         self.hash = valid_hash;
+        info!(
+            "mined block {} after {} nonces: {}",
+            self.index, tried_nonces, self.hash
+        );
+        true
+    }
+
+    /// move the nonce forward by `step`.
+    ///
+    /// the nonce never wraps around: once the nonce space is exhausted,
+    /// the timestamp is bumped by one second to get a fresh header, and the nonce restarts from `restart`
+    fn advance_nonce(&mut self, step: i64, restart: i64) {
+        match self.nonce.checked_add(step) {
+            Some(nonce) => self.nonce = nonce,
+            None => {
+                self.timestamp += 1;
+                self.nonce = restart;
+            }
+        }
     }
 
-    /// calculate the hash value of the block
+    /// POW algorithm on multiple threads,
+    /// the nonce space is partitioned across `threads` workers:
+    /// worker `i` starts at `nonce + i` and strides by `threads`.
+    ///
+    /// all the workers stop as soon as one of them finds a valid hash
+    pub fn mine_parallel(&mut self, threads: usize) {
+        self.mine_parallel_with(threads, &Sha256dHasher)
+    }
+
+    /// POW algorithm on multiple threads with a given hash algorithm, see `mine_parallel`
+    pub fn mine_parallel_with<H: Hasher + Sync + ?Sized>(&mut self, threads: usize, hasher: &H) {
+        let threads = threads.max(1);
+        let target_threshold = self.target_threshold();
+        let stop = AtomicBool::new(false);
+
+        let winner = thread::scope(|scope| {
+            let workers = (0..threads)
+                .map(|offset| {
+                    let mut block = self.clone();
+                    let stop = &stop;
+                    scope.spawn(move || {
+                        block.advance_nonce(offset as i64, offset as i64);
+                        while !stop.load(Ordering::Relaxed) {
+                            let hash = block.pow_hash(hasher);
+                            if hash.meets_target(&target_threshold) {
+                                stop.store(true, Ordering::Relaxed);
+                                return Some((block.timestamp, block.nonce, hash));
+                            }
+                            block.advance_nonce(threads as i64, offset as i64);
+                        }
+                        None
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            workers
+                .into_iter()
+                .filter_map(|worker| worker.join().unwrap())
+                .next()
+        });
+
+        // a worker only stops without a result after another worker found one
+        let (timestamp, nonce, hash) = winner.unwrap();
+        self.timestamp = timestamp;
+        self.nonce = nonce;
+        self.hash = hash;
+    }
+
+    /// calculate the hash value of the block, which is the hash value of its header
     pub fn sha256(&self) -> HashValue {
-        let mut hasher = Sha256::new();
-        hasher.update(self.version.as_bytes());
-        hasher.update(self.index.to_be_bytes());
-        hasher.update(self.timestamp.to_be_bytes());
-        hasher.update(self.prev_hash);
-        hasher.update(self.merkle_root);
-        hasher.update(self.difficulty.to_be_bytes());
-        hasher.update(self.nonce.to_be_bytes());
-        let result = hasher.finalize().into();
-        HashValue::new(result)
+        self.header().sha256()
+    }
+
+    /// calculate the proof-of-work hash of the block by `hasher` over its header,
+    /// by default the double SHA256 of the header
+    pub fn pow_hash<H: Hasher + ?Sized>(&self, hasher: &H) -> HashValue {
+        hasher.digest(&self.header().to_bytes())
+    }
+
+    /// the size of the block in bytes: the length of its serialized transactions
+    pub fn size(&self) -> usize {
+        bincode::serialized_size(&self.data).unwrap() as usize
     }
 
     /// # Arguments
@@ -131,6 +383,93 @@ impl Block {
     }
 }
 
+/// calculate the merkle root of a list of hashes,
+/// an odd node at the end of a level is promoted to the next level as it is.
+///
+/// return 0x00...000 directly if the list is empty
+pub fn merkle_root(hashes: Vec<HashValue>) -> HashValue {
+    if hashes.is_empty() {
+        return HashValue::new([0; 32]);
+    }
+
+    let mut hashes = hashes;
+    // construct a merkle tree
+    while hashes.len() > 1 {
+        hashes = merkle_level(&hashes);
+    }
+
+    hashes[0]
+}
+
+/// calculate the next level of a merkle tree
+fn merkle_level(hashes: &[HashValue]) -> Vec<HashValue> {
+    hashes
+        .chunks(2)
+        .map(|chunk| match *chunk {
+            [hash] => hash,
+            [hash1, hash2] => hash_pair(hash1, hash2),
+            _ => unreachable!(), // panic immediately if none of the previous pattern get matched
+        })
+        .collect::<Vec<HashValue>>()
+}
+
+fn hash_pair(left: HashValue, right: HashValue) -> HashValue {
+    let mut hasher = Sha256::new();
+    hasher.update(left);
+    hasher.update(right);
+    let result = hasher.finalize().into();
+    HashValue::new(result)
+}
+
+/// divide two 256-bit unsigned integers, each given as its (high, low) 128-bit halves,
+/// by binary long division. the divisor must not be zero.
+fn div_u256(dividend: (u128, u128), divisor: (u128, u128)) -> (u128, u128) {
+    let mut quotient = (0u128, 0u128);
+    let mut remainder = (0u128, 0u128);
+    for bit in (0..256).rev() {
+        let dividend_bit = if bit >= 128 {
+            (dividend.0 >> (bit - 128)) & 1
+        } else {
+            (dividend.1 >> bit) & 1
+        };
+        // shift the next bit of the dividend into the remainder, it may overflow 256 bits
+        let overflow = remainder.0 >> 127 == 1;
+        remainder = (
+            (remainder.0 << 1) | (remainder.1 >> 127),
+            (remainder.1 << 1) | dividend_bit,
+        );
+
+        if overflow || remainder >= divisor {
+            let (lo, borrow) = remainder.1.overflowing_sub(divisor.1);
+            let hi = remainder
+                .0
+                .wrapping_sub(divisor.0)
+                .wrapping_sub(borrow as u128);
+            remainder = (hi, lo);
+            if bit >= 128 {
+                quotient.0 |= 1 << (bit - 128);
+            } else {
+                quotient.1 |= 1 << bit;
+            }
+        }
+    }
+    quotient
+}
+
+/// verify a merkle proof generated by `Block::merkle_proof`,
+/// check if hashing the transaction ID up along the proof leads to the merkle root
+pub fn verify_merkle_proof(tx_id: HashValue, proof: &[(HashValue, bool)], root: HashValue) -> bool {
+    let calculated_root = proof.iter().fold(tx_id, |hash, (sibling, is_left)| {
+        if *is_left {
+            hash_pair(*sibling, hash)
+        } else {
+            hash_pair(hash, *sibling)
+        }
+    });
+
+    calculated_root == root
+}
+
 impl Display for Block {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "Block[{}]:", self.index)?;
@@ -139,7 +478,12 @@ impl Display for Block {
         writeln!(f, "\t-prev_hash: {}", self.prev_hash)?;
         writeln!(f, "\t-hash: {}", self.hash)?;
         writeln!(f, "\t-merkle_root: {}", self.merkle_root)?;
-        writeln!(f, "\t-difficulty: {}", self.difficulty)?;
+        writeln!(
+            f,
+            "\t-difficulty: {:#010X} ({})",
+            self.difficulty,
+            self.difficulty_human()
+        )?;
         writeln!(f, "\t-nonce: {}", self.nonce)?;
         writeln!(f, "\t-data: [")?;
         for tx in self.data.iter() {
@@ -154,10 +498,14 @@ impl Display for Block {
 
 #[cfg(test)]
 mod tests {
-    use crate::block::Block;
+    use crate::block::{merkle_root, verify_merkle_proof, Block, MerkleCache};
+    use crate::errors::RustyCoinError;
     use crate::transaction::Transaction;
     use crate::types::HashValue;
     use rust_decimal_macros::dec;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+    use std::time::{Duration, Instant};
 
     #[test]
     fn test_target_threshold() {
@@ -171,6 +519,7 @@ mod tests {
             merkle_root: HashValue::new([0; 32]),
             difficulty: 0x20123456_u32,
             nonce: 0,
+            merkle_cache: MerkleCache::default(),
         };
         let target_threshold = block.target_threshold();
 
@@ -179,6 +528,86 @@ mod tests {
             "0x1234560000000000000000000000000000000000000000000000000000000000"
         );
     }
+    #[test]
+    fn test_work() {
+        let work_of = |difficulty: u32| {
+            Block {
+                version: "0.1v test".to_string(),
+                index: 0,
+                data: Vec::new(),
+                timestamp: 0u64,
+                prev_hash: HashValue::new([0; 32]),
+                hash: HashValue::new([0; 32]),
+                merkle_root: HashValue::new([0; 32]),
+                difficulty,
+                nonce: 0,
+                merkle_cache: MerkleCache::default(),
+            }
+            .work()
+        };
+
+        assert_eq!(work_of(0x00000000), 0); // zero target
+        assert_eq!(work_of(0x23FFFFFF), 1); // maximum target
+        assert_eq!(work_of(0x207FFFFF), 2);
+        assert_eq!(work_of(0x2000FFFF), 256);
+        // 2^256 / (0xFFFF * 2^208 + 1), the difficulty 1 target of bitcoin
+        assert_eq!(work_of(0x1D00FFFF), 0x0100010001);
+        // 2^256 / (1 + 1) doesn't fit in 128 bits
+        assert_eq!(work_of(0x03000001), u128::MAX);
+        // a smaller nBits can mean more work
+        assert!(work_of(0x1D00FFFF) > work_of(0x1E123456));
+        assert!(work_of(0x1C7FFFFF) > work_of(0x1D00FFFF));
+    }
+
+    #[test]
+    fn test_target_threshold_boundaries() {
+        let target_of = |difficulty: u32| {
+            Block {
+                version: "0.1v test".to_string(),
+                index: 0,
+                data: Vec::new(),
+                timestamp: 0u64,
+                prev_hash: HashValue::new([0; 32]),
+                hash: HashValue::new([0; 32]),
+                merkle_root: HashValue::new([0; 32]),
+                difficulty,
+                nonce: 0,
+                merkle_cache: MerkleCache::default(),
+            }
+            .target_threshold()
+            .to_string()
+        };
+
+        assert_eq!(
+            target_of(0x00123456),
+            "0x0000000000000000000000000000000000000000000000000000000000000000"
+        );
+        assert_eq!(
+            target_of(0x02123456),
+            "0x0000000000000000000000000000000000000000000000000000000000001234"
+        );
+        assert_eq!(
+            target_of(0x03123456),
+            "0x0000000000000000000000000000000000000000000000000000000000123456"
+        );
+        assert_eq!(
+            target_of(0x1D00FFFF),
+            "0x00000000ffff0000000000000000000000000000000000000000000000000000"
+        );
+        assert_eq!(
+            target_of(0x20FFFFFF),
+            "0xffffff0000000000000000000000000000000000000000000000000000000000"
+        );
+        // leading zero bytes of the mantissa may overflow
+        assert_eq!(
+            target_of(0x21001234),
+            "0x1234000000000000000000000000000000000000000000000000000000000000"
+        );
+        assert_eq!(target_of(0x21123456), format!("0x{}", "ff".repeat(32)));
+        assert_eq!(target_of(0xFF123456), format!("0x{}", "ff".repeat(32)));
+        assert_eq!(target_of(0xFF000000), format!("0x{}", "00".repeat(32)));
+    }
+
     #[test]
     fn test_merkle_root() {
         let block = Block {
@@ -194,6 +623,7 @@ mod tests {
             merkle_root: HashValue::new([0; 32]),
             difficulty: 0x04123456_u32,
             nonce: 0,
+            merkle_cache: MerkleCache::default(),
         };
         let merkle_root = block.calc_merkle_root();
         println!("{}", merkle_root);
@@ -210,12 +640,110 @@ mod tests {
             merkle_root: HashValue::new([0; 32]),
             difficulty: 0x04123456_u32,
             nonce: 143,
+            merkle_cache: MerkleCache::default(),
         };
 
         let hash = block.sha256().sha256();
         println!("{}", hash);
     }
 
+    #[test]
+    fn test_block_header() {
+        let mut block = Block {
+            version: "0.1v test".to_string(),
+            index: 3,
+            data: vec![Transaction::new(
+                vec![],
+                vec![],
+                HashValue::new([0u8; 32]),
+                dec!(0.0),
+                None,
+            )],
+            timestamp: 1_700_000_000_u64,
+            prev_hash: HashValue::new([1; 32]),
+            hash: HashValue::new([0; 32]),
+            merkle_root: HashValue::new([0; 32]),
+            difficulty: 0x207FFFFF_u32,
+            nonce: 0,
+            merkle_cache: MerkleCache::default(),
+        };
+        block.merkle_root = block.calc_merkle_root();
+        block.update_hash_and_nonce();
+
+        let header = block.header();
+        assert_eq!(header.sha256(), block.sha256());
+        assert_eq!(header.sha256().sha256(), block.hash);
+    }
+
+    #[test]
+    fn test_difficulty_human() {
+        let block_of = |difficulty: u32| Block {
+            version: "0.1v test".to_string(),
+            index: 0,
+            data: Vec::new(),
+            timestamp: 0u64,
+            prev_hash: HashValue::new([0; 32]),
+            hash: HashValue::new([0; 32]),
+            merkle_root: HashValue::new([0; 32]),
+            difficulty,
+            nonce: 0,
+            merkle_cache: MerkleCache::default(),
+        };
+        let easy = block_of(0x207FFFFF);
+        let hard = block_of(0x1D00FFFF);
+
+        assert_eq!(hard.difficulty_human(), 1.0);
+        assert!(hard.difficulty_human() > easy.difficulty_human());
+        // the raw nBits is shown alongside the human difficulty
+        assert!(format!("{hard}").contains("-difficulty: 0x1D00FFFF (1)"));
+    }
+
+    #[test]
+    fn test_from_header_and_txs() {
+        let data = (0..3u8)
+            .map(|i| {
+                Transaction::new(
+                    vec![],
+                    vec![],
+                    HashValue::new([0u8; 32]),
+                    dec!(0.0),
+                    Some(vec![i]),
+                )
+            })
+            .collect::<Vec<Transaction>>();
+        let mut block = Block {
+            version: "0.1v test".to_string(),
+            index: 1,
+            data: data.clone(),
+            timestamp: 1_700_000_000_u64,
+            prev_hash: HashValue::new([1; 32]),
+            hash: HashValue::new([0; 32]),
+            merkle_root: HashValue::new([0; 32]),
+            difficulty: 0x207FFFFF_u32,
+            nonce: 0,
+            merkle_cache: MerkleCache::default(),
+        };
+        block.merkle_root = block.calc_merkle_root();
+        block.update_hash_and_nonce();
+
+        let assembled = Block::from_header_and_txs(block.header(), data.clone()).unwrap();
+        assert_eq!(assembled, block);
+
+        // swapping a transaction breaks the merkle root
+        let mut swapped = data;
+        swapped[1] = Transaction::new(
+            vec![],
+            vec![],
+            HashValue::new([0u8; 32]),
+            dec!(0.0),
+            Some(vec![42]),
+        );
+        assert!(matches!(
+            Block::from_header_and_txs(block.header(), swapped),
+            Err(RustyCoinError::MerkleRootMismatch)
+        ));
+    }
+
     #[test]
     fn test_mining() {
         let mut block = Block {
@@ -228,8 +756,215 @@ mod tests {
             merkle_root: HashValue::new([0; 32]),
             difficulty: 0x1E123456_u32,
             nonce: 0,
+            merkle_cache: MerkleCache::default(),
         };
         block.update_hash_and_nonce();
         println!("{}", block);
     }
+
+    #[test]
+    fn test_mining_parallel() {
+        let mut block = Block {
+            version: "0.1v test".to_string(),
+            index: 0,
+            data: Vec::new(),
+            timestamp: 0_u64,
+            prev_hash: HashValue::new([0; 32]),
+            hash: HashValue::new([0; 32]),
+            merkle_root: HashValue::new([0; 32]),
+            difficulty: 0x1F7FFFFF_u32,
+            nonce: 0,
+            merkle_cache: MerkleCache::default(),
+        };
+        block.mine_parallel(4);
+        assert_eq!(block.hash, block.sha256().sha256());
+        assert!(block
+            .sha256()
+            .sha256()
+            .meets_target(&block.target_threshold()));
+    }
+
+    #[test]
+    fn test_mining_past_nonce_exhaustion() {
+        let mut block = Block {
+            version: "0.1v test".to_string(),
+            index: 0,
+            data: Vec::new(),
+            timestamp: 0_u64,
+            prev_hash: HashValue::new([0; 32]),
+            hash: HashValue::new([0; 32]),
+            merkle_root: HashValue::new([0; 32]),
+            difficulty: 0x1F7FFFFF_u32,
+            nonce: i64::MAX - 1,
+            merkle_cache: MerkleCache::default(),
+        };
+        let mut block_copied = block.clone();
+
+        // neither of the last two nonces is valid, so the search must move on to a new timestamp
+        block.update_hash_and_nonce();
+        assert_eq!(block.timestamp, 1);
+        assert!(block.nonce >= 0);
+        assert!(block
+            .sha256()
+            .sha256()
+            .meets_target(&block.target_threshold()));
+
+        block_copied.mine_parallel(4);
+        assert_eq!(block_copied.timestamp, 1);
+        assert!(block_copied.nonce >= 0);
+        assert!(block_copied
+            .sha256()
+            .sha256()
+            .meets_target(&block_copied.target_threshold()));
+    }
+
+    #[test]
+    fn test_mine_until() {
+        let mut block = Block {
+            version: "0.1v test".to_string(),
+            index: 0,
+            data: Vec::new(),
+            timestamp: 0_u64,
+            prev_hash: HashValue::new([0; 32]),
+            hash: HashValue::new([0; 32]),
+            merkle_root: HashValue::new([0; 32]),
+            difficulty: 0x1D00FFFF_u32,
+            nonce: 0,
+            merkle_cache: MerkleCache::default(),
+        };
+
+        // far too hard to be mined before an immediate deadline
+        let start = Instant::now();
+        assert!(!block.mine_until(Instant::now()));
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(block.hash, HashValue::new([0; 32]));
+
+        // cancelled from another thread
+        let cancel = AtomicBool::new(false);
+        let mined = thread::scope(|scope| {
+            let miner = scope.spawn(|| block.mine_until_cancelled(&cancel));
+            thread::sleep(Duration::from_millis(50));
+            cancel.store(true, Ordering::Relaxed);
+            miner.join().unwrap()
+        });
+        assert!(!mined);
+        assert_eq!(block.hash, HashValue::new([0; 32]));
+
+        // an easy block is mined long before the deadline
+        block.difficulty = 0x207FFFFF_u32;
+        assert!(block.mine_until(Instant::now() + Duration::from_secs(60)));
+        assert_eq!(block.hash, block.sha256().sha256());
+        assert!(block.hash.meets_target(&block.target_threshold()));
+    }
+
+    #[test]
+    fn test_merkle_cache() {
+        let data = (0..5u8)
+            .map(|i| {
+                let mut tx = Transaction::new(
+                    vec![],
+                    vec![],
+                    HashValue::new([0u8; 32]),
+                    dec!(0.0),
+                    Some(vec![i]),
+                );
+                tx.update_digest();
+                tx
+            })
+            .collect::<Vec<Transaction>>();
+        let mut block = Block {
+            version: "0.1v test".to_string(),
+            index: 0,
+            data: data.clone(),
+            timestamp: 0_u64,
+            prev_hash: HashValue::new([0; 32]),
+            hash: HashValue::new([0; 32]),
+            merkle_root: HashValue::new([0; 32]),
+            difficulty: 0x207FFFFF_u32,
+            nonce: 0,
+            merkle_cache: MerkleCache::default(),
+        };
+        assert!(block.merkle_cache.0.get().is_none());
+        let root = block.calc_merkle_root();
+        assert_eq!(
+            root,
+            merkle_root(data.iter().map(Transaction::sha256).collect())
+        );
+
+        // the tree is built once, then the cached root is returned
+        let layers = block.merkle_cache.0.get().unwrap().as_ptr();
+        assert_eq!(block.calc_merkle_root(), root);
+        assert_eq!(block.merkle_cache.0.get().unwrap().as_ptr(), layers);
+        assert_eq!(block.merkle_cache.0.get().unwrap().len(), 4); // 5 -> 3 -> 2 -> 1
+
+        // changing the transactions clears the cache
+        block.data_mut().pop();
+        assert!(block.merkle_cache.0.get().is_none());
+        assert_ne!(block.calc_merkle_root(), root);
+
+        // the cache is not serialized
+        let block: Block = serde_json::from_str(&serde_json::to_string(&block).unwrap()).unwrap();
+        assert!(block.merkle_cache.0.get().is_none());
+    }
+
+    #[test]
+    fn test_merkle_proof() {
+        let data = (0..5u8)
+            .map(|i| {
+                let mut tx = Transaction::new(
+                    vec![],
+                    vec![],
+                    HashValue::new([0u8; 32]),
+                    dec!(0.0),
+                    Some(vec![i]),
+                );
+                tx.update_digest();
+                tx
+            })
+            .collect::<Vec<Transaction>>();
+        let mut block = Block {
+            version: "0.1v test".to_string(),
+            index: 0,
+            data,
+            timestamp: 0_u64,
+            prev_hash: HashValue::new([0; 32]),
+            hash: HashValue::new([0; 32]),
+            merkle_root: HashValue::new([0; 32]),
+            difficulty: 0x207FFFFF_u32,
+            nonce: 0,
+            merkle_cache: MerkleCache::default(),
+        };
+        block.merkle_root = block.calc_merkle_root();
+
+        // every transaction can be proven, including the promoted odd one
+        for tx in block.data.iter() {
+            let tx_id = tx.get_transaction_id();
+            let proof = block.merkle_proof(tx_id).unwrap();
+            assert!(verify_merkle_proof(tx_id, &proof, block.merkle_root));
+        }
+        assert!(block.merkle_proof(HashValue::new([1u8; 32])).is_none());
+
+        // tampered proofs are rejected
+        let tx_id = block.data[2].get_transaction_id();
+        let proof = block.merkle_proof(tx_id).unwrap();
+        let mut flipped_proof = proof.clone();
+        flipped_proof[0].1 = !flipped_proof[0].1;
+        assert!(!verify_merkle_proof(
+            tx_id,
+            &flipped_proof,
+            block.merkle_root
+        ));
+        let mut replaced_proof = proof.clone();
+        replaced_proof[1].0 = HashValue::new([1u8; 32]);
+        assert!(!verify_merkle_proof(
+            tx_id,
+            &replaced_proof,
+            block.merkle_root
+        ));
+        assert!(!verify_merkle_proof(
+            block.data[3].get_transaction_id(),
+            &proof,
+            block.merkle_root
+        ));
+    }
 }
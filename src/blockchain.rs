@@ -1,45 +1,145 @@
-use crate::block::Block;
+use crate::block::{Block, MerkleCache};
+use crate::config::{FeePolicy, GenesisConfig, DEFAULT_TARGET_BLOCK_INTERVAL_SECS};
+use crate::errors::RustyCoinError;
+use crate::hasher::{Hasher, Sha256dHasher};
 /// The core part of rusty coin
 /// The mining rule of rusty coin:
-/// * 10 seconds per block, adjust difficulty every hour
+/// * 10 seconds per block by default, adjust difficulty every `RETARGET_INTERVAL` blocks (= 1 hour),
+///   see `GenesisConfig::target_block_interval_secs`
 /// * the first transaction in every block should be the coinbase transaction
 /// * a coinbase UTXO need get 6 * 24 (= 1 day) confirmation before spent
 /// * a regular UTXO need get 6 (= 1 min) confirmation before spent
 ///
-/// The reward rule of rusty coin is a convergent infinite geometric series:
-/// * $ reward = $
-use crate::transaction::{Output, Transaction};
-use crate::types::HashValue;
-use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+/// The reward rule of rusty coin is a halving schedule:
+/// * the reward starts at 50 rusty coin, and halves every `HALVING_INTERVAL` blocks
+/// * the reward is 0 once it falls below the smallest unit, 10^-8 rusty coin
+/// * so the total supply is capped at 20,999,999.9769 rusty coin
+use crate::transaction::{sum_amounts, Output, SignatureCheck, Transaction};
+use crate::types::{scale_target, target_to_nbits, HashValue};
+use crate::utxo_set::{OutPoint, UtxoSet};
+use crate::wallet::UTXO;
+use log::{debug, info, warn};
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 use serde::{Deserialize, Serialize};
-use std::cmp::Ordering;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::Display;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::sync::atomic::{self, AtomicBool};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+
+/// number of blocks between two halvings of the reward
+pub const HALVING_INTERVAL: usize = 210_000;
+/// the reward of the first `HALVING_INTERVAL` blocks, in the smallest unit (10^-8 rusty coin)
+const INITIAL_REWARD_UNITS: u64 = 50 * 100_000_000;
+/// the largest size of a block, in bytes of its serialized transactions, see `Block::size`
+pub const MAX_BLOCK_SIZE: usize = 1_000_000;
+/// the room `select_transactions_for_block` leaves in a block for its coinbase transaction, in bytes,
+/// enough for a coinbase transaction paying a handful of receivers
+pub const COINBASE_RESERVED_SIZE: usize = 1_000;
+/// confirmations a coinbase output needs before it can be spent, 6 * 24 blocks (= 1 day)
+pub const COINBASE_MATURITY: usize = 6 * 24;
+/// confirmations a regular output needs before it can be spent, 6 blocks (= 1 min)
+pub const REGULAR_MATURITY: usize = 6;
+/// how often a miner spawned by `spawn_miner` checks the tip of the chain and the shutdown signal
+const MINER_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// number of the latest blocks whose transaction fee rates are used by `estimate_fee_rate`, 6 * 10 blocks (= 10 min)
+pub const FEE_ESTIMATE_WINDOW: usize = 6 * 10;
+/// the most blocks `resolve_conflicts` abandons by default, 6 * 24 blocks (= 1 day),
+/// the blocks confirmed for longer can't be rewritten by a deeper fork
+pub const DEFAULT_MAX_REORG_DEPTH: usize = 6 * 24;
+/// number of blocks between two difficulty adjustments, 6 * 60 blocks (= 1 hour at 10 seconds per block)
+pub const RETARGET_INTERVAL: usize = 6 * 60;
+/// the most a difficulty adjustment scales the target by, up or down
+const MAX_RETARGET_FACTOR: u64 = 4;
+/// how many target block intervals the timestamp of a block can be ahead of the current time
+const MAX_FUTURE_BLOCK_INTERVALS: u64 = 6;
+/// the difficulty of the genesis block of `Blockchain::new`, in nBits format,
+/// the blocks on top of it are mined at this trivial difficulty until the first retarget
+pub const DEFAULT_GENESIS_DIFFICULTY: u32 = 0x207FFFFF;
+/// the most transactions the transaction pool holds by default,
+/// beyond it the ones paying the lowest fee rate are evicted
+pub const DEFAULT_MAX_POOL_SIZE: usize = 10_000;
+/// the lowest fee rate (per byte) the transaction pool accepts by default, one smallest unit,
+/// so transactions paying no fee at all are never relayed
+pub const DEFAULT_MIN_RELAY_FEE_RATE: Decimal = dec!(0.00000001);
+/// the most orphan blocks kept waiting for their parent, beyond it the oldest ones are dropped
+pub const MAX_ORPHAN_POOL_SIZE: usize = 100;
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct Blockchain {
     blockchain: Vec<Block>,    // store the blockchain / pieces of the blockchain
     tx_pool: Vec<Transaction>, // store the unpacked transactions
+    utxo_set: UtxoSet,         // unspent transaction outputs of the blockchain
+    tx_index: BTreeMap<HashValue, (usize, usize)>, // transaction ID -> (block index, position in the block)
+    block_index: BTreeMap<HashValue, usize>,       // block hash -> block index
+    #[serde(default)]
+    pruned_height: usize,     // the blocks below this index have their transactions pruned
+    #[serde(default)]
+    chain_id: u32,            // the network of the chain, committed by the genesis transaction
+    #[serde(default = "default_max_reorg_depth")]
+    max_reorg_depth: usize, // the most blocks a candidate chain can replace, see `resolve_conflicts`
+    #[serde(default)]
+    pool_entry_times: BTreeMap<HashValue, u64>, // transaction ID -> when it entered the pool, in seconds
+    #[serde(default = "default_max_pool_size")]
+    max_pool_size: usize, // the most transactions the pool holds, see `set_max_pool_size`
+    #[serde(default)]
+    fee_policy: FeePolicy, // how much of the fees the coinbase transaction can claim
+    #[serde(default = "default_target_block_interval_secs")]
+    target_block_interval_secs: u64, // the time between two blocks the difficulty is retargeted toward
+    #[serde(default)]
+    cumulative_work: Vec<u128>, // block index -> the work of the chain up to the block, see `total_work`
+    #[serde(default)]
+    testnet: bool, // whether coins can be minted out of thin air, see `mint_to`
+    #[serde(default)]
+    checkpoints: BTreeMap<usize, HashValue>, // block index -> the trusted hash of the block, see `set_checkpoints`
+    #[serde(default)]
+    orphan_pool: Vec<Block>, // the blocks whose parent is not on the chain yet, oldest first
+    #[serde(default = "default_min_relay_fee_rate")]
+    min_relay_fee_rate: Decimal, // the lowest fee rate the pool accepts, see `set_min_relay_fee_rate`
+}
+
+fn default_max_reorg_depth() -> usize {
+    DEFAULT_MAX_REORG_DEPTH
+}
+
+fn default_max_pool_size() -> usize {
+    DEFAULT_MAX_POOL_SIZE
+}
+
+fn default_min_relay_fee_rate() -> Decimal {
+    DEFAULT_MIN_RELAY_FEE_RATE
+}
+
+fn default_target_block_interval_secs() -> u64 {
+    DEFAULT_TARGET_BLOCK_INTERVAL_SECS
 }
 
 impl Blockchain {
     /// create a new blockchain, including the genesis block
     pub fn new(genesis_msg: &str) -> Self {
         let genesis_block = Self::create_genesis_block(genesis_msg);
-        Self {
-            blockchain: vec![genesis_block],
-            tx_pool: vec![],
-        }
+        Self::new_chain_start_with(genesis_block)
+    }
+
+    /// create a new blockchain with the genesis block built from `config`,
+    /// blockchains created from the same config share an identical genesis block
+    pub fn new_with_genesis(config: &GenesisConfig) -> Self {
+        let mut blockchain = Self::new_chain_start_with(Self::create_genesis_block_from(config));
+        blockchain.set_target_block_interval_secs(config.target_block_interval_secs);
+        blockchain.testnet = config.testnet;
+        blockchain
     }
 
     /// create a new blockchain, start with a given genesis block
     pub fn new_chain_start_with(genesis_block: Block) -> Self {
-        Self {
-            blockchain: vec![genesis_block],
-            tx_pool: vec![],
-        }
+        Self::from_vec(&[genesis_block])
     }
 
     /// create a new chain from a Block Vector
@@ -47,7 +147,300 @@ impl Blockchain {
         Self {
             blockchain: chain.to_vec(),
             tx_pool: vec![],
+            utxo_set: UtxoSet::from_blocks(chain),
+            tx_index: Self::index_transactions(chain),
+            block_index: Self::index_blocks(chain),
+            pruned_height: 0,
+            chain_id: Self::genesis_chain_id(chain),
+            max_reorg_depth: DEFAULT_MAX_REORG_DEPTH,
+            pool_entry_times: BTreeMap::new(),
+            max_pool_size: DEFAULT_MAX_POOL_SIZE,
+            fee_policy: FeePolicy::default(),
+            target_block_interval_secs: DEFAULT_TARGET_BLOCK_INTERVAL_SECS,
+            cumulative_work: Self::accumulate_work(chain),
+            testnet: false,
+            checkpoints: BTreeMap::new(),
+            orphan_pool: vec![],
+            min_relay_fee_rate: DEFAULT_MIN_RELAY_FEE_RATE,
+        }
+    }
+
+    /// give `chain` the consensus parameters of this blockchain, the ones its blocks are verified under
+    fn copy_consensus_params_to(&self, chain: &mut Blockchain) {
+        chain.target_block_interval_secs = self.target_block_interval_secs;
+        chain.fee_policy = self.fee_policy.clone();
+    }
+
+    /// replace the blocks from `fork_point` on with the ones of `candidate_chain`,
+    /// then rebuild the transaction pool on top of the new chain:
+    /// the transactions of the abandoned blocks first, then the pooled ones
+    fn adopt_chain(&mut self, candidate_chain: &[Block], fork_point: usize) {
+        let abandoned_blocks = self.blockchain[fork_point..].to_vec();
+        self.replace_chain(candidate_chain);
+
+        let pooled_txs = std::mem::take(&mut self.tx_pool);
+        self.recycle_transactions(&abandoned_blocks);
+        for tx in pooled_txs {
+            let tx_id = tx.get_transaction_id();
+            if self.add_to_pool(tx).is_err() {
+                // confirmed by the new chain, or no longer valid on it
+                self.pool_entry_times.remove(&tx_id);
+            }
+        }
+    }
+
+    /// add the regular transactions of `blocks` to the transaction pool, see `add_to_pool`,
+    /// the ones it rejects are dropped, e.g. already confirmed by the chain
+    fn recycle_transactions(&mut self, blocks: &[Block]) {
+        for block in blocks {
+            for tx in block.data.iter().skip(1) {
+                // skip the coinbase transaction
+                let _ = self.add_to_pool(tx.clone());
+            }
+        }
+    }
+
+    /// replace the blocks of the chain, the UTXO set and the indexes are rebuilt accordingly
+    fn replace_chain(&mut self, chain: &[Block]) {
+        self.blockchain = chain.to_vec();
+        self.utxo_set = UtxoSet::from_blocks(chain);
+        self.tx_index = Self::index_transactions(chain);
+        self.block_index = Self::index_blocks(chain);
+        self.pruned_height = 0;
+        self.chain_id = Self::genesis_chain_id(chain);
+        self.cumulative_work = Self::accumulate_work(chain);
+    }
+
+    /// the chain ID committed by the genesis transaction, 0 if there is none
+    fn genesis_chain_id(chain: &[Block]) -> u32 {
+        chain
+            .first()
+            .and_then(|genesis_block| genesis_block.data.first())
+            .map_or(0, Transaction::get_chain_id)
+    }
+
+    /// whether this chain is a testnet, see `GenesisConfig::testnet`
+    pub fn is_testnet(&self) -> bool {
+        self.testnet
+    }
+
+    /// get the ID of the network of this chain, see `GenesisConfig::chain_id`.
+    /// only the transactions bound to this chain ID are valid on the chain
+    pub fn get_chain_id(&self) -> u32 {
+        self.chain_id
+    }
+
+    /// drop the transactions of all the blocks but the last `keep_last` ones, to bound the memory.
+    ///
+    /// the headers of the pruned blocks are kept, so the `prev_hash` links can still be checked
+    /// and the headers can still be served, and new blocks are verified against the UTXO set,
+    /// which is kept as it is. but the pruned transactions can't be found anymore,
+    /// and the pruned blocks don't match their merkle roots, so they can't be verified again,
+    /// nor served to the peers catching up, see `/chain` and `/blocks` in `node`.
+    pub fn prune(&mut self, keep_last: usize) {
+        let cutoff = self.blockchain.len().saturating_sub(keep_last);
+        for block in self
+            .blockchain
+            .iter_mut()
+            .take(cutoff)
+            .skip(self.pruned_height)
+        {
+            for tx in block.data_mut().drain(..) {
+                self.tx_index.remove(&tx.get_transaction_id());
+            }
+            block.data_mut().shrink_to_fit();
+        }
+        self.pruned_height = self.pruned_height.max(cutoff);
+    }
+
+    /// set the most blocks `resolve_conflicts` abandons to adopt a candidate chain,
+    /// `DEFAULT_MAX_REORG_DEPTH` by default
+    pub fn set_max_reorg_depth(&mut self, max_reorg_depth: usize) {
+        self.max_reorg_depth = max_reorg_depth;
+    }
+
+    /// get the most blocks `resolve_conflicts` abandons to adopt a candidate chain
+    pub fn get_max_reorg_depth(&self) -> usize {
+        self.max_reorg_depth
+    }
+
+    /// set the trusted hashes of the blocks at some heights (block index -> block hash).
+    ///
+    /// `resolve_conflicts` and `sync_range` reject any chain whose block at a checkpoint height
+    /// has another hash, so the chain can never be reorganized below a checkpoint.
+    /// the blocks up to the highest checkpoint reached are committed to by its hash,
+    /// their P2PKH signatures are not verified again, see `verify_candidate_chain`
+    pub fn set_checkpoints(&mut self, checkpoints: BTreeMap<usize, HashValue>) {
+        self.checkpoints = checkpoints;
+    }
+
+    /// get the trusted hashes of the blocks by their index, see `set_checkpoints`
+    pub fn get_checkpoints(&self) -> &BTreeMap<usize, HashValue> {
+        &self.checkpoints
+    }
+
+    /// check if a block has the hash of the checkpoint at its height, if there is one
+    pub fn matches_checkpoint(&self, block: &Block) -> bool {
+        self.checkpoints
+            .get(&block.index)
+            .is_none_or(|hash| *hash == block.hash)
+    }
+
+    /// set how the transaction fees are split between the miner and the burn,
+    /// honored by `expected_coinbase_reward` and the verification of the coinbase transactions.
+    /// by default all the fees are paid to the miner, inflated by 3%
+    ///
+    /// panics if `miner_share` of the policy is not between 0 and 1
+    pub fn set_fee_policy(&mut self, fee_policy: FeePolicy) {
+        assert!(
+            (Decimal::ZERO..=Decimal::ONE).contains(&fee_policy.miner_share),
+            "the miner share must be between 0 and 1"
+        );
+        self.fee_policy = fee_policy;
+    }
+
+    /// get how the transaction fees are split between the miner and the burn
+    pub fn get_fee_policy(&self) -> &FeePolicy {
+        &self.fee_policy
+    }
+
+    /// set the time between two blocks the difficulty is retargeted toward, see `next_difficulty`,
+    /// `GenesisConfig::target_block_interval_secs` for the chains created by `new_with_genesis`
+    ///
+    /// panics if `secs` is 0
+    pub fn set_target_block_interval_secs(&mut self, secs: u64) {
+        assert_ne!(secs, 0, "the target block interval must be positive");
+        self.target_block_interval_secs = secs;
+    }
+
+    /// get the time between two blocks the difficulty is retargeted toward, in seconds
+    pub fn get_target_block_interval_secs(&self) -> u64 {
+        self.target_block_interval_secs
+    }
+
+    /// get the difficulty of the next block of this blockchain, in nBits format,
+    /// the difficulty a block must be mined at to extend the chain.
+    ///
+    /// the blocks start at the difficulty of the genesis block,
+    /// then the difficulty is adjusted every `RETARGET_INTERVAL` blocks:
+    /// the target of the latest block is scaled by the ratio of the timespan of the latest
+    /// `RETARGET_INTERVAL` blocks to the timespan expected at `target_block_interval_secs` per block,
+    /// clamped to `MAX_RETARGET_FACTOR` times up or down.
+    /// in between, the next block keeps the difficulty of the latest block
+    pub fn next_difficulty(&self) -> u32 {
+        let last_block = self.get_last_block().unwrap();
+        let height = self.len();
+        if height < RETARGET_INTERVAL || !height.is_multiple_of(RETARGET_INTERVAL) {
+            return last_block.difficulty;
+        }
+
+        let first_block = &self.blockchain[height - RETARGET_INTERVAL];
+        let expected_timespan = (RETARGET_INTERVAL as u64 - 1) * self.target_block_interval_secs;
+        let actual_timespan = last_block
+            .timestamp
+            .saturating_sub(first_block.timestamp)
+            .clamp(
+                expected_timespan / MAX_RETARGET_FACTOR,
+                expected_timespan * MAX_RETARGET_FACTOR,
+            );
+        let target = scale_target(
+            &last_block.target_threshold(),
+            actual_timespan,
+            expected_timespan,
+        );
+        target_to_nbits(&target)
+    }
+
+    /// get the index below which the blocks have been pruned, see `prune`,
+    /// 0 if no block has been pruned
+    pub fn get_pruned_height(&self) -> usize {
+        self.pruned_height
+    }
+
+    /// index the blocks by their hashes
+    fn index_blocks(blocks: &[Block]) -> BTreeMap<HashValue, usize> {
+        blocks
+            .iter()
+            .map(|block| (block.hash, block.index))
+            .collect()
+    }
+
+    /// the work of the chain up to each block, see `Blockchain::chain_work`
+    fn accumulate_work(blocks: &[Block]) -> Vec<u128> {
+        blocks
+            .iter()
+            .scan(0u128, |sum, block| {
+                *sum = sum.saturating_add(block.work());
+                Some(*sum)
+            })
+            .collect()
+    }
+
+    /// index all the transactions of the blocks by their IDs
+    fn index_transactions(blocks: &[Block]) -> BTreeMap<HashValue, (usize, usize)> {
+        blocks
+            .iter()
+            .flat_map(|block| {
+                block
+                    .data
+                    .iter()
+                    .enumerate()
+                    .map(|(position, tx)| (tx.get_transaction_id(), (block.index, position)))
+            })
+            .collect()
+    }
+
+    /// build a blockchain from untrusted JSON, e.g. received from a peer.
+    ///
+    /// only the blocks, the consensus parameters and the transaction pool are taken from the JSON:
+    /// - the blocks must pass `verify_candidate_chain` under the consensus parameters,
+    ///   otherwise the whole blockchain is rejected
+    /// - the UTXO set and the indexes are rebuilt from the blocks
+    /// - the pooled transactions are added back by `add_to_pool`, the invalid ones are dropped
+    ///
+    /// returns `InvalidChain` if the JSON is malformed or the chain doesn't verify
+    pub fn try_from_json(s: &str) -> Result<Self, RustyCoinError> {
+        let untrusted: Blockchain =
+            serde_json::from_str(s).map_err(|_| RustyCoinError::InvalidChain)?;
+        if !untrusted.verify_candidate_chain(&untrusted.blockchain) {
+            return Err(RustyCoinError::InvalidChain);
+        }
+
+        let mut blockchain = Self::from_vec(&untrusted.blockchain);
+        untrusted.copy_consensus_params_to(&mut blockchain);
+        for tx in untrusted.tx_pool {
+            let tx_id = tx.get_transaction_id();
+            // an invalid transaction is simply left out of the pool
+            if blockchain.add_to_pool(tx).is_ok() {
+                // keep the time it entered the pool, so it doesn't outlive its expiry
+                if let Some(&entry_time) = untrusted.pool_entry_times.get(&tx_id) {
+                    blockchain.pool_entry_times.insert(tx_id, entry_time);
+                }
+            }
+        }
+        Ok(blockchain)
+    }
+
+    /// save the whole blockchain to a binary file (bincode),
+    /// including the transaction pool and the UTXO set.
+    ///
+    /// later the blockchain can be recovered from method
+    ///
+    /// `Blockchain::load_from_file(path: &str)`
+    pub fn save_to_file(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let writer = BufWriter::new(File::create(path)?);
+        bincode::serialize_into(writer, self)?;
+        Ok(())
+    }
+
+    /// load a blockchain from a binary file created by `Blockchain::save_to_file`
+    pub fn load_from_file(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut blockchain: Blockchain = bincode::deserialize_from(reader)?;
+        if blockchain.cumulative_work.len() != blockchain.len() {
+            blockchain.cumulative_work = Self::accumulate_work(&blockchain.blockchain);
         }
+        Ok(blockchain)
     }
 
     pub fn filter_transactions_by_algo<F>(&self, algorithm: F) -> &[Transaction]
@@ -57,605 +450,5104 @@ impl Blockchain {
         algorithm(&self.tx_pool)
     }
 
-    /// get the reward of the next block of this blockchain
+    /// select at most `max_count` transactions from the transaction pool to be packed into a block,
+    /// the ones paying the highest fee rate (see `Transaction::fee_rate`) first,
+    /// so a small transaction beats a large one paying the same fee.
+    ///
+    /// a transaction that doesn't fit in the rest of the block (see `MAX_BLOCK_SIZE`) is skipped,
+    /// leaving `COINBASE_RESERVED_SIZE` bytes for the coinbase transaction, so a block template packs
+    /// all the selected transactions and the reward of `expected_coinbase_reward` stays valid.
+    /// transactions paying the same fee rate keep their order in the pool.
+    pub fn select_transactions_for_block(&self, max_count: usize) -> Vec<Transaction> {
+        let mut candidates = self.tx_pool.iter().collect::<Vec<&Transaction>>();
+        candidates.sort_by_key(|tx| Reverse(tx.fee_rate()));
+
+        let mut block_size = COINBASE_RESERVED_SIZE;
+        let mut selected = vec![];
+        for tx in candidates {
+            if selected.len() >= max_count {
+                break;
+            }
+            let tx_size = tx.size();
+            if block_size + tx_size > MAX_BLOCK_SIZE {
+                continue;
+            }
+            block_size += tx_size;
+            selected.push(tx.clone());
+        }
+        selected
+    }
+
+    /// suggest a fee rate (see `Transaction::fee_rate`) likely to be confirmed within `target_blocks` blocks,
+    /// by the fee rates of the regular transactions in the latest `FEE_ESTIMATE_WINDOW` blocks.
+    /// the fee of a transaction is the rate multiplied by its size, see `Transaction::size`.
     ///
-    /// the inflation rate of the rusty coin is 2%
-    pub fn get_latest_reward(&self, unpacked_transactions: &[Transaction]) -> Decimal {
-        let aggregate_tx_fee = unpacked_transactions
+    /// the estimate is the fee rate that `target_blocks / (target_blocks + 1)` of those transactions
+    /// paid at least, e.g. the median for 1 block, the 25th percentile for 3 blocks,
+    /// so the longer one can wait the lower the fee rate is.
+    /// the minimum relay fee rate is suggested if there are no recent transactions,
+    /// see `set_min_relay_fee_rate`.
+    pub fn estimate_fee_rate(&self, target_blocks: usize) -> Decimal {
+        let mut fee_rates = self
+            .blockchain
             .iter()
-            .fold(dec!(0.0), |sum, tx| sum + tx.get_transaction_fee());
+            .rev()
+            .take(FEE_ESTIMATE_WINDOW)
+            .flat_map(|block| block.data.iter().skip(1)) // skip the coinbase transaction
+            .map(Transaction::fee_rate)
+            .collect::<Vec<Decimal>>();
+        if fee_rates.is_empty() {
+            return self.min_relay_fee_rate;
+        }
+        fee_rates.sort();
+
+        // nearest-rank percentile of 1 / (target_blocks + 1)
+        let rank = fee_rates.len().div_ceil(target_blocks.max(1) + 1);
+        fee_rates[rank - 1]
+    }
+
+    /// get the largest amount the coinbase transaction of the next block of this blockchain
+    /// can claim, if the block packs the regular transactions `txs`:
+    /// the block reward plus the miner's share of the transaction fees, see `set_fee_policy`
+    ///
+    /// returns `AmountOverflow` if the fees of the transactions overflow
+    pub fn expected_coinbase_reward(&self, txs: &[Transaction]) -> Result<Decimal, RustyCoinError> {
+        self.coinbase_reward(self.len(), txs)
+    }
+
+    /// the largest amount the coinbase transaction of the block at `block_index` can claim,
+    /// shared by the generation and the verification of blocks
+    fn coinbase_reward(
+        &self,
+        block_index: usize,
+        txs: &[Transaction],
+    ) -> Result<Decimal, RustyCoinError> {
+        let aggregate_tx_fee = sum_amounts(txs.iter().map(Transaction::get_transaction_fee))?;
 
-        Self::reward_algorithm(self.get_last_block().unwrap().index + 1)
-            + Self::inflated_tx_fee(aggregate_tx_fee)
+        aggregate_tx_fee
+            .checked_mul(self.fee_policy.miner_share)
+            .and_then(|fee| {
+                if self.fee_policy.inflate_fees {
+                    Self::inflated_tx_fee(fee)
+                } else {
+                    Some(fee)
+                }
+            })
+            .and_then(|fee| fee.checked_add(Self::reward_algorithm(block_index)))
+            .ok_or(RustyCoinError::AmountOverflow)
     }
 
-    /// the inflation rate of the rusty coin is 3%
-    fn inflated_tx_fee(tx_fee: Decimal) -> Decimal {
-        tx_fee * dec!(1.03)
+    /// the inflation rate of the rusty coin is 3%, `None` on overflow
+    fn inflated_tx_fee(tx_fee: Decimal) -> Option<Decimal> {
+        tx_fee.checked_mul(dec!(1.03))
     }
 
     /// generate a new block, including the coinbase transaction.
     ///
     /// this function include the mining process, which is time-consuming
+    ///
+    /// # Errors
+    /// * `InvalidOutputAmount` - an amount of the receivers is invalid, see `Output::new`,
+    ///   or they claim more than `expected_coinbase_reward` of the packed transactions
+    /// * `AmountOverflow` - the amounts of the receivers or the fees overflow when summed up
     /// # Arguments:
     /// * `address`: `HashValue` - the address of the miner
     /// * `protocol_version`: String - the version of the protocol
     /// * `time_millis`: u64 - the timestamp of the block
     /// * `prev_block`: &Block - the previous block
     /// * `difficulty`: u32 - the difficulty of the block
-    /// * `tx_sorting_algo`: F - the sorting algorithm of the transactions
-    ///    - the sorting algorithm may sort the transactions by their transaction fee
+    /// * `unpacked_transactions`: Option<Vec<Transaction>> - the transactions to be packed, in order
+    ///    - if `None`, the transactions paying the highest fee rate in the transaction pool are packed,
+    ///      see `Blockchain::select_transactions_for_block`
     pub fn generate_new_block(
         &self,
         receivers: Vec<(HashValue, Decimal)>,
         protocol_version: String,
         time_millis: u64,
         difficulty: u32,
-        unpacked_transactions: Vec<Transaction>,
-    ) -> Block {
+        unpacked_transactions: Option<Vec<Transaction>>,
+    ) -> Result<Block, RustyCoinError> {
+        let mut block = self.generate_block_template(
+            receivers,
+            protocol_version,
+            time_millis,
+            difficulty,
+            unpacked_transactions,
+        )?;
+        block.update_hash_and_nonce(); // POW algorithm, 2 rounds of sha256
+        Ok(block)
+    }
+
+    /// same as `generate_new_block`, but the block is not mined yet,
+    /// e.g. to mine it elsewhere with `Block::mine_until_cancelled`
+    pub fn generate_block_template(
+        &self,
+        receivers: Vec<(HashValue, Decimal)>,
+        protocol_version: String,
+        time_millis: u64,
+        difficulty: u32,
+        unpacked_transactions: Option<Vec<Transaction>>,
+    ) -> Result<Block, RustyCoinError> {
         let prev_block = self.get_last_block().unwrap();
-        // if the output is not valid, then panic
-        let output_fee_sum = receivers.iter().fold(dec!(0.0), |sum, (_address, amount)| {
-            if *amount < dec!(0.0) {
-                panic!("Invalid output amount");
+        let output_fee_sum = sum_amounts(receivers.iter().map(|(_address, amount)| *amount))?;
+
+        // the amounts of the receivers are checked by `Output::new`
+        let coinbase_transaction =
+            Self::create_coinbase_transaction(receivers, prev_block.index + 1, self.chain_id)?;
+        let unpacked_transactions =
+            unpacked_transactions.unwrap_or_else(|| self.select_transactions_for_block(usize::MAX));
+
+        // pack the transactions in order, until the block is full
+        let mut block_size =
+            bincode::serialized_size(&vec![&coinbase_transaction]).unwrap() as usize;
+        let mut transactions = vec![coinbase_transaction];
+        for tx in unpacked_transactions {
+            let tx_size = tx.size();
+            if block_size + tx_size > MAX_BLOCK_SIZE {
+                break;
             }
-            sum + amount
-        });
-        if output_fee_sum > Self::reward_algorithm(prev_block.index + 1) {
-            panic!("Invalid output amount");
+            block_size += tx_size;
+            transactions.push(tx);
+        }
+        if output_fee_sum > self.expected_coinbase_reward(&transactions[1..])? {
+            return Err(RustyCoinError::InvalidOutputAmount);
         }
 
-        let coinbase_transaction = Self::create_coinbase_transaction(receivers);
-        let mut unpacked_transactions = unpacked_transactions;
-        unpacked_transactions.insert(0, coinbase_transaction);
         let mut block = Block {
             version: protocol_version,
             index: prev_block.index + 1,
-            data: unpacked_transactions,
+            data: transactions,
             timestamp: time_millis,
             prev_hash: prev_block.hash,
             hash: HashValue::new([0; 32]),
             merkle_root: HashValue::new([0; 32]),
             difficulty,
             nonce: 0,
+            merkle_cache: MerkleCache::default(),
         };
         block.merkle_root = block.calc_merkle_root();
-        block.update_hash_and_nonce(); // POW algorithm, 2 rounds of sha256
-        block
+        Ok(block)
+    }
+
+    /// testnet faucet: mine a block whose coinbase transaction pays `amount` to `address`.
+    /// the block is mined at the difficulty the chain requires, see `next_difficulty`,
+    /// and verified like any other block, a testnet is meant to start at a trivial difficulty.
+    ///
+    /// the minted output is a coinbase output like any other,
+    /// it can only be spent once `COINBASE_MATURITY` more blocks are mined on top of it.
+    ///
+    /// # Errors
+    /// * `MintingDisabled` - the chain is not a testnet, see `GenesisConfig::testnet`
+    /// * `InvalidOutputAmount` - `amount` is negative, or more than the block reward
+    /// * `InvalidChain` - a minted block fails the verification
+    pub fn mint_to(&mut self, address: HashValue, amount: Decimal) -> Result<(), RustyCoinError> {
+        if !self.testnet {
+            return Err(RustyCoinError::MintingDisabled);
+        }
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let difficulty = self.next_difficulty();
+        let block = self.generate_new_block(
+            vec![(address, amount)],
+            self.get_last_block().unwrap().version.clone(),
+            now,
+            difficulty,
+            Some(vec![]),
+        )?;
+        self.add_block_checked(block, difficulty)
+            .map_err(|_| RustyCoinError::InvalidChain)
+    }
+
+    /// spawn a tokio task mining blocks on top of `chain` at the difficulty it requires,
+    /// see `next_difficulty`, until `shutdown` is set.
+    ///
+    /// the task repeatedly packs the transactions of the pool into a block paying the reward
+    /// to `reward_address`, mines it on a blocking thread, and appends it to the chain if it is valid.
+    /// the mining restarts on the new tip whenever the tip changes underneath it,
+    /// e.g. a block from a peer is accepted meanwhile.
+    /// the task stops if the mining thread panics, instead of panicking itself.
+    pub fn spawn_miner(
+        chain: Arc<RwLock<Blockchain>>,
+        reward_address: HashValue,
+        shutdown: Arc<AtomicBool>,
+    ) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            while !shutdown.load(atomic::Ordering::Relaxed) {
+                let template = {
+                    let blockchain = chain.read().await;
+                    let txs = blockchain.select_transactions_for_block(usize::MAX);
+                    let now = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs();
+                    blockchain
+                        .expected_coinbase_reward(&txs)
+                        .and_then(|reward| {
+                            blockchain.generate_block_template(
+                                vec![(reward_address, reward)],
+                                blockchain.get_last_block().unwrap().version.clone(),
+                                now,
+                                blockchain.next_difficulty(),
+                                Some(txs),
+                            )
+                        })
+                };
+                let mut block = match template {
+                    Ok(block) => block,
+                    Err(e) => {
+                        warn!("failed to build a block to mine: {}", e);
+                        tokio::time::sleep(MINER_POLL_INTERVAL).await;
+                        continue;
+                    }
+                };
+
+                let prev_hash = block.prev_hash;
+                let cancel = Arc::new(AtomicBool::new(false));
+                let miner = tokio::task::spawn_blocking({
+                    let cancel = cancel.clone();
+                    move || block.mine_until_cancelled(&cancel).then_some(block)
+                });
+                // stop mining a stale block, or once the miner is shut down
+                while !miner.is_finished() {
+                    tokio::time::sleep(MINER_POLL_INTERVAL).await;
+                    let tip_changed = chain
+                        .read()
+                        .await
+                        .get_last_block()
+                        .map(|last_block| last_block.hash)
+                        != Some(prev_hash);
+                    if tip_changed || shutdown.load(atomic::Ordering::Relaxed) {
+                        cancel.store(true, atomic::Ordering::Relaxed);
+                    }
+                }
+                let block = match miner.await {
+                    Ok(Some(block)) => block,
+                    Ok(None) => continue, // cancelled
+                    Err(e) => {
+                        warn!("the mining thread failed, stopped mining: {}", e);
+                        return;
+                    }
+                };
+
+                let mut blockchain = chain.write().await;
+                if blockchain
+                    .get_last_block()
+                    .map(|last_block| last_block.hash)
+                    != Some(prev_hash)
+                {
+                    continue; // the tip changed after the block was mined
+                }
+                let difficulty = blockchain.next_difficulty();
+                if !blockchain.verify_block(&block, difficulty) {
+                    drop(blockchain);
+                    tokio::time::sleep(MINER_POLL_INTERVAL).await;
+                    continue;
+                }
+                info!("mined block {}: {}", block.index, block.hash);
+                blockchain.add_block(block);
+            }
+        })
     }
 
-    fn create_coinbase_transaction(receivers: Vec<(HashValue, Decimal)>) -> Transaction {
+    /// create the coinbase transaction of the block at `block_index` on the network `chain_id`,
+    /// the height is committed in the additional data, so every coinbase transaction has a unique ID
+    ///
+    /// returns `InvalidOutputAmount` if an amount of the receivers is invalid, see `Output::new`
+    fn create_coinbase_transaction(
+        receivers: Vec<(HashValue, Decimal)>,
+        block_index: usize,
+        chain_id: u32,
+    ) -> Result<Transaction, RustyCoinError> {
         let reward_outputs = receivers
             .into_iter()
             .map(|(address, amount)| Output::new(amount, address.to_vec()))
-            .collect::<Vec<Output>>();
+            .collect::<Result<Vec<Output>, RustyCoinError>>()?;
         let mut res = Transaction::new(
             vec![],
             reward_outputs,
             HashValue::new([0u8; 32]),
             dec!(0.0),
-            None,
+            Some(Self::coinbase_height_commitment(block_index)),
         );
+        res.set_chain_id(chain_id);
         res.update_digest(); // update coinbase transaction's digest (transaction_id, hash value of the transaction)
-        res
+        Ok(res)
     }
 
-    /// add a block to the blockchain
-    /// * please verify the block before calling this function!!!
-    /// * please verify the block before calling this function!!!
-    /// * please verify the block before calling this function!!!
-    pub fn add_block(&mut self, block: Block) {
-        self.blockchain.push(block);
+    /// the height committed by a coinbase transaction: the block index as 8 big-endian bytes,
+    /// it may be followed by arbitrary bytes, e.g. an extra nonce
+    fn coinbase_height_commitment(block_index: usize) -> Vec<u8> {
+        (block_index as u64).to_be_bytes().to_vec()
     }
 
-    /// get the block by its index
-    pub fn get_block(&self, index: usize) -> Option<&Block> {
-        self.blockchain.get(index)
+    /// verify a block with `verify_block_detailed`, and add it to the blockchain only if it is valid.
+    ///
+    /// the UTXO set, the indexes and the transaction pool are updated together with the chain,
+    /// an invalid block leaves the blockchain untouched
+    pub fn add_block_checked(
+        &mut self,
+        block: Block,
+        network_difficulty: u32,
+    ) -> Result<(), BlockValidationError> {
+        self.verify_block_detailed(&block, network_difficulty)?;
+        self.add_block(block);
+        Ok(())
     }
 
-    /// resolve conflicts:
-    /// - the longest chain wins
-    /// - the hardest chain wins
+    /// keep a block whose parent is not on the chain yet, e.g. received out of order,
+    /// until `try_connect_orphans` attaches it.
     ///
-    /// unpacked transactions in the abandoned block will be re-added
-    /// to the transaction pool after being verified
-    ///
-    /// # Arguments
-    /// * `candidate_chain`: `&[Block]` - the candidate chain in a correct order
+    /// a block already on the chain or in the orphan pool is ignored,
+    /// once the pool holds `MAX_ORPHAN_POOL_SIZE` blocks the oldest one is dropped
+    pub fn add_orphan(&mut self, block: Block) {
+        if self.block_index.contains_key(&block.hash)
+            || self
+                .orphan_pool
+                .iter()
+                .any(|orphan| orphan.hash == block.hash)
+        {
+            return;
+        }
+        if self.orphan_pool.len() >= MAX_ORPHAN_POOL_SIZE {
+            let dropped = self.orphan_pool.remove(0);
+            debug!(
+                "dropped the orphan block {}: {}",
+                dropped.index, dropped.hash
+            );
+        }
+        self.orphan_pool.push(block);
+    }
+
+    /// attach the orphan blocks extending the tip of the chain, one after another,
+    /// each of them is verified by `add_block_checked` against the difficulty the chain requires,
+    /// see `next_difficulty`, and dropped if it is invalid.
     ///
-    /// # Returns
-    /// * `bool` - if the original chain has been replaced, return true, else return false
-    pub fn resolve_conflicts(&mut self, candidate_chain: &[Block]) -> bool {
-        // search the bifurcation node, from the chain head to the tail
-        // compare the hash instead of the whole block (the candidate chain has already been verified, so the hash should be valid)
+    /// the orphans whose parent is still missing, or not the tip, are kept.
+    /// return the number of blocks added to the chain
+    pub fn try_connect_orphans(&mut self) -> usize {
+        let mut connected = 0;
+        while let Some(position) = self.orphan_pool.iter().position(|orphan| {
+            orphan.index == self.len()
+                && self
+                    .get_last_block()
+                    .is_some_and(|last_block| last_block.hash == orphan.prev_hash)
+        }) {
+            let orphan = self.orphan_pool.remove(position);
+            let (index, difficulty) = (orphan.index, self.next_difficulty());
+            match self.add_block_checked(orphan, difficulty) {
+                Ok(()) => connected += 1,
+                Err(e) => warn!("dropped the invalid orphan block {}: {}", index, e),
+            }
+        }
+        connected
+    }
 
-        // first check the genesis block, if the genesis block is different, then the two chains are totally different, reject the new chain directly
-        if self.blockchain[0].hash != candidate_chain[0].hash {
-            return false;
+    /// get the blocks waiting for their parent, oldest first, see `add_orphan`
+    pub fn get_orphans(&self) -> &[Block] {
+        &self.orphan_pool
+    }
+
+    /// add a block to the blockchain without verifying it
+    /// * please verify the block before calling this function!!!
+    /// * use `add_block_checked` unless the block has just been verified
+    pub fn add_block(&mut self, block: Block) {
+        self.utxo_set.apply_block(&block);
+        // the packed transactions are no longer pending
+        let tx_ids = block
+            .data
+            .iter()
+            .map(|tx| tx.get_transaction_id())
+            .collect::<Vec<HashValue>>();
+        self.remove_from_pool(&tx_ids);
+        for (position, tx_id) in tx_ids.into_iter().enumerate() {
+            self.tx_index.insert(tx_id, (block.index, position));
         }
+        self.block_index.insert(block.hash, block.index);
+        self.cumulative_work
+            .push(self.total_work().saturating_add(block.work()));
+        self.blockchain.push(block);
+    }
 
-        // find the fork point
-        let mut fork_point = 0;
-        for (block, candidate_block) in self.blockchain.iter().zip(candidate_chain.iter()) {
-            if block.hash != candidate_block.hash {
-                break;
-            }
-            fork_point += 1;
+    /// add a transaction to the transaction pool, waiting to be packed into a block.
+    ///
+    /// the transaction is rejected if:
+    /// - it is malformed, see `Transaction::is_valid_structure`
+    /// - an input refers to a block beyond the tip of the chain (`InvalidBlockIndex`)
+    /// - it is not a valid regular transaction (coinbase transactions are never pooled)
+    /// - its lock time will not be reached by the next block
+    /// - it pays a lower fee rate than the minimum relay fee rate (`FeeRateTooLow`),
+    ///   see `set_min_relay_fee_rate`
+    /// - a transaction with the same ID is already in the pool
+    /// - it spends an output already spent by a transaction in the pool
+    /// - the pool is full, and it doesn't pay a higher fee rate than any pooled transaction
+    ///   (`InsufficientFee`), otherwise the one paying the lowest fee rate is evicted
+    pub fn add_to_pool(&mut self, tx: Transaction) -> Result<(), RustyCoinError> {
+        self.verify_pool_candidate(&tx)?;
+
+        if tx.fee_rate() < self.min_relay_fee_rate {
+            return Err(RustyCoinError::FeeRateTooLow);
         }
 
-        // longest chain wins
-        match self.blockchain.len().cmp(&candidate_chain.len()) {
-            Ordering::Less => {
-                // the candidate chain is longer, replace the current chain with the candidate chain
-                self.blockchain = candidate_chain.to_vec();
-                // add the unpacked transactions in the abandoned chain to the transaction pool
-                for block in self.blockchain.iter().skip(fork_point) {
-                    self.tx_pool.extend(block.data.iter().skip(1).cloned()); // skip the coinbase transaction
-                }
-                true
-            }
-            Ordering::Equal => {
-                // the hardest chain wins
-                let current_chain_work: u32 =
-                    self.blockchain.iter().map(|block| block.difficulty).sum();
+        if self
+            .tx_pool
+            .iter()
+            .any(|pooled_tx| pooled_tx.get_transaction_id() == tx.get_transaction_id())
+        {
+            return Err(RustyCoinError::DuplicateTransaction);
+        }
 
-                let candidate_chain_work: u32 =
-                    candidate_chain.iter().map(|block| block.difficulty).sum();
+        let pooled_outpoints = self
+            .tx_pool
+            .iter()
+            .flat_map(|pooled_tx| pooled_tx.get_inputs())
+            .map(|input| (input.get_prev_tx_hash(), input.get_prev_output_index()))
+            .collect::<BTreeSet<(HashValue, usize)>>();
+        if tx.get_inputs().iter().any(|input| {
+            pooled_outpoints.contains(&(input.get_prev_tx_hash(), input.get_prev_output_index()))
+        }) {
+            return Err(RustyCoinError::DoubleSpend);
+        }
 
-                match current_chain_work.cmp(&candidate_chain_work) {
-                    Ordering::Greater => {
-                        // the current chain is harder, no need to change
-                        // add the unpacked transactions in the candidate chain to the transaction pool
-                        for block in candidate_chain.iter().skip(fork_point) {
-                            self.tx_pool.extend(block.data.iter().skip(1).cloned());
-                            // skip the coinbase transaction
-                        }
-                        false
-                    }
-                    Ordering::Less => {
-                        // the candidate chain is harder, replace the current chain with the candidate chain
-                        self.blockchain = candidate_chain.to_vec();
-                        // add the unpacked transactions in the abandoned chain to the transaction pool
-                        for block in self.blockchain.iter().skip(fork_point) {
-                            self.tx_pool.extend(block.data.iter().skip(1).cloned());
-                            // skip the coinbase transaction
-                        }
-                        true
-                    }
-                    Ordering::Equal => {
-                        // if the work of the two chains are the same again, then:
-                        // the first chain wins
-                        // add the unpacked transactions in the candidate chain to the transaction pool
-                        for block in candidate_chain.iter().skip(fork_point) {
-                            self.tx_pool.extend(block.data.iter().skip(1).cloned());
-                            // skip the coinbase transaction
-                        }
-                        false
-                    }
-                }
-            }
-            Ordering::Greater => {
-                // the current chain is longer, no need to change
-                // add the unpacked transactions in the candidate chain to the transaction pool
-                for block in candidate_chain.iter().skip(fork_point) {
-                    self.tx_pool.extend(block.data.iter().skip(1).cloned()); // skip the coinbase transaction
-                }
-                false
-            }
+        if self.tx_pool.len() >= self.max_pool_size
+            && self
+                .tx_pool
+                .iter()
+                .all(|pooled_tx| tx.fee_rate() <= pooled_tx.fee_rate())
+        {
+            return Err(RustyCoinError::InsufficientFee);
         }
-    }
 
-    /// get the latest block of the blockchain
-    pub fn get_last_block(&self) -> Option<&Block> {
-        self.blockchain.last()
+        self.push_to_pool(tx);
+        Ok(())
     }
 
-    /// verify all the block in the chain one by one,
-    ///
-    /// the blocks must be arranged in the correct order:
+    /// replace the pooled transactions spending the same outputs as `new_tx` with it,
+    /// e.g. to bump the fee of a transaction stuck in the pool (replace-by-fee).
     ///
-    /// genesis block -> block 1 -> block 2 -> ... -> block n
-    pub fn verify_chain(chain: &[Block]) -> bool {
-        let new_chain = Blockchain::from_vec(chain);
+    /// # Errors
+    /// * `InvalidBlockIndex` - an input of `new_tx` refers to a block beyond the tip of the chain
+    /// * `InvalidTransaction` - `new_tx` is not a valid regular transaction, see `add_to_pool`
+    /// * `FeeRateTooLow` - `new_tx` pays a lower fee rate than the minimum relay fee rate
+    /// * `DuplicateTransaction` - `new_tx` is already in the pool
+    /// * `NoConflictingTransaction` - no pooled transaction spends an output spent by `new_tx`
+    /// * `InsufficientFee` - the fee of `new_tx` is not higher than the fees of the replaced ones
+    pub fn replace_by_fee(&mut self, new_tx: Transaction) -> Result<(), RustyCoinError> {
+        self.verify_pool_candidate(&new_tx)?;
 
-        for block in &new_chain.blockchain {
-            if !new_chain.verify_block(block, block.difficulty) {
-                // fn`verify_difficulty()` in the `verify_block()` will be always true
-                return false;
-            }
+        if new_tx.fee_rate() < self.min_relay_fee_rate {
+            return Err(RustyCoinError::FeeRateTooLow);
         }
 
-        true
-    }
-
-    /// verify a block's integrity, check if it is valid
-    /// - check all regular transactions in the block
-    /// - check the coinbase transaction
-    ///     - if it follows the reward rule of this blockchain
-    ///     - if it equals to the sum of transaction fee
-    /// - check the merkle root of the block
-    /// - check the difficulty of the block
-    /// - check the hash value of the block
-    /// - check the timestamp of the block
-    pub fn verify_block(&self, block: &Block, network_difficulty: u32) -> bool {
-        self.verify_transactions(&block.data, block.index)
-            && self.verify_merkle_root(block)
-            && self.verify_difficulty(block, network_difficulty)
-            && self.verify_block_hash(block)
-            && self.verify_prev_hash(block)
-            && self.verify_timestamp(block.timestamp)
-    }
-
-    pub fn verify_transactions(&self, transactions: &[Transaction], block_index: usize) -> bool {
-        transactions.iter().all(|tx| {
-            if Transaction::is_coinbase_transaction(tx) {
-                self.verify_coinbase_transaction(tx, transactions, block_index)
-            } else {
-                self.verify_regular_transaction(tx)
-            }
-        })
-    }
-
-    /// verify a coinbase transaction's integrity, check if it is valid.
-    /// - check if the transaction hash is equal to the transaction ID
-    /// - check if the reward is valid
-    ///
-    /// # Arguments:
-    /// * `coinbase_tx`: `&Transaction` - the coinbase transaction to be verified
-    /// * `transactions`: `&[Transaction]` - all the transactions in the block
-    /// * `block_index`: `usize` - the index of the block
-    ///
-    /// returns: `bool` - if the coinbase transaction is valid, return true, else return false
-    fn verify_coinbase_transaction(
-        &self,
-        coinbase_tx: &Transaction,
-        transactions: &[Transaction],
-        block_index: usize,
-    ) -> bool {
-        let aggregate_tx_fee = transactions
+        if self
+            .tx_pool
             .iter()
-            .skip(1) // skip the coinbase transaction
-            .fold(dec!(0.0), |sum, tx| sum + tx.get_transaction_fee());
+            .any(|pooled_tx| pooled_tx.get_transaction_id() == new_tx.get_transaction_id())
+        {
+            return Err(RustyCoinError::DuplicateTransaction);
+        }
 
-        // check if the transaction hash is equal to the transaction ID
-        if coinbase_tx.sha256() != coinbase_tx.get_transaction_id() {
-            return false;
+        let outpoints = new_tx
+            .get_inputs()
+            .iter()
+            .map(|input| (input.get_prev_tx_hash(), input.get_prev_output_index()))
+            .collect::<BTreeSet<(HashValue, usize)>>();
+        let conflicting_txs = self
+            .tx_pool
+            .iter()
+            .filter(|pooled_tx| {
+                pooled_tx.get_inputs().iter().any(|input| {
+                    outpoints.contains(&(input.get_prev_tx_hash(), input.get_prev_output_index()))
+                })
+            })
+            .collect::<Vec<&Transaction>>();
+        if conflicting_txs.is_empty() {
+            return Err(RustyCoinError::NoConflictingTransaction);
         }
 
-        // sum the output fee of the coinbase transaction
-        let mut output_fee_sum = dec!(0.0);
-        for output in coinbase_tx.get_outputs() {
-            if output.get_amount() < dec!(0.0) {
-                return false;
-            }
-            output_fee_sum += output.get_amount();
+        let replaced_fee = sum_amounts(conflicting_txs.iter().map(|tx| tx.get_transaction_fee()))?;
+        if new_tx.get_transaction_fee() <= replaced_fee {
+            return Err(RustyCoinError::InsufficientFee);
         }
 
-        output_fee_sum
-            <= Self::reward_algorithm(block_index) + Self::inflated_tx_fee(aggregate_tx_fee)
+        let replaced_tx_ids = conflicting_txs
+            .iter()
+            .map(|tx| tx.get_transaction_id())
+            .collect::<Vec<HashValue>>();
+        self.remove_from_pool(&replaced_tx_ids);
+        self.push_to_pool(new_tx);
+        Ok(())
     }
 
-    fn verify_merkle_root(&self, block: &Block) -> bool {
-        block.merkle_root == block.calc_merkle_root()
+    /// check if a transaction can enter the transaction pool on its own:
+    /// a valid regular transaction whose lock time will be reached by the next block,
+    /// return the error of `Transaction::is_valid_structure` if it is malformed,
+    /// `InvalidBlockIndex` if an input refers to a block that doesn't exist (yet),
+    /// `InvalidTransaction` otherwise
+    fn verify_pool_candidate(&self, tx: &Transaction) -> Result<(), RustyCoinError> {
+        // the cheap checks first, before looking up the chain
+        tx.is_valid_structure()?;
+        if tx
+            .get_inputs()
+            .iter()
+            .any(|input| input.get_prev_block_index() >= self.len())
+        {
+            return Err(RustyCoinError::InvalidBlockIndex);
+        }
+
+        // the transaction will be packed into the next block at the earliest
+        let next_block_index = self.get_last_block().map_or(0, |block| block.index + 1);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        if Transaction::is_coinbase_transaction(tx)
+            || !tx.is_final(next_block_index, now)
+            || !self.verify_regular_transaction(tx, next_block_index, &BTreeSet::new())
+        {
+            return Err(RustyCoinError::InvalidTransaction);
+        }
+        Ok(())
     }
 
-    fn verify_difficulty(&self, block: &Block, network_difficulty: u32) -> bool {
-        block.difficulty == network_difficulty
+    /// remove transactions from the transaction pool by their IDs,
+    /// e.g. after they have been packed into a block
+    pub fn remove_from_pool(&mut self, tx_ids: &[HashValue]) {
+        self.tx_pool
+            .retain(|tx| !tx_ids.contains(&tx.get_transaction_id()));
+        for tx_id in tx_ids {
+            self.pool_entry_times.remove(tx_id);
+        }
     }
 
-    fn verify_block_hash(&self, block: &Block) -> bool {
-        block.sha256().sha256() == block.hash && block.hash <= block.target_threshold()
+    /// add a transaction to the transaction pool, recording when it entered the pool,
+    /// then evict the transactions paying the lowest fee rate beyond `max_pool_size`
+    fn push_to_pool(&mut self, tx: Transaction) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        self.pool_entry_times
+            .entry(tx.get_transaction_id())
+            .or_insert(now);
+        self.tx_pool.push(tx);
+        self.evict_beyond_max_pool_size();
     }
 
-    fn verify_prev_hash(&self, block: &Block) -> bool {
-        if let Some(prev_block) = self.get_block(block.index - 1) {
-            prev_block.hash == block.prev_hash
-        } else {
-            false
+    /// evict the transactions paying the lowest fee rate until the pool holds at most `max_pool_size`
+    fn evict_beyond_max_pool_size(&mut self) {
+        while self.tx_pool.len() > self.max_pool_size {
+            // among the same fee rate, the latest transaction is evicted first
+            let Some(lowest_fee_tx) = self
+                .tx_pool
+                .iter()
+                .rev()
+                .min_by_key(|pooled_tx| pooled_tx.fee_rate())
+                .map(Transaction::get_transaction_id)
+            else {
+                break;
+            };
+            debug!("evicted transaction {} from the full pool", lowest_fee_tx);
+            self.remove_from_pool(&[lowest_fee_tx]);
         }
     }
 
-    /// verify timestamp (in seconds, UTC +0:00) of the block:
-    /// the timestamp of the block should be less than or equal to the current time
-    /// and the timestamp of the block should be larger than or equal to the average timestamps of the previous 10 blocks
-    /// (if there are less than 10 blocks, then use the average timestamps of all the previous blocks)
-    fn verify_timestamp(&self, timestamp: u64) -> bool {
-        let current_time = SystemTime::now()
+    /// drop the transactions that have waited in the transaction pool for more than `max_age_secs` seconds,
+    /// e.g. the ones paying too low a fee to ever be packed
+    pub fn evict_expired(&mut self, max_age_secs: u64) {
+        let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_secs();
-        let mut average_timestamp = 0_u64;
-        let mut count = 0;
-        for block in self.blockchain.iter().rev() {
-            if count >= 10 {
+        let expired_tx_ids = self
+            .tx_pool
+            .iter()
+            .map(Transaction::get_transaction_id)
+            .filter(|tx_id| {
+                self.pool_entry_times
+                    .get(tx_id)
+                    .is_some_and(|&entry_time| now.saturating_sub(entry_time) > max_age_secs)
+            })
+            .collect::<Vec<HashValue>>();
+        if !expired_tx_ids.is_empty() {
+            debug!(
+                "evicted {} expired transactions from the pool",
+                expired_tx_ids.len()
+            );
+        }
+        self.remove_from_pool(&expired_tx_ids);
+    }
+
+    /// set the most transactions the transaction pool holds, `DEFAULT_MAX_POOL_SIZE` by default,
+    /// the ones paying the lowest fee rate are evicted beyond it
+    pub fn set_max_pool_size(&mut self, max_pool_size: usize) {
+        self.max_pool_size = max_pool_size;
+        self.evict_beyond_max_pool_size();
+    }
+
+    /// set the lowest fee rate (per byte, see `Transaction::fee_rate`) `add_to_pool` accepts,
+    /// `DEFAULT_MIN_RELAY_FEE_RATE` by default, 0 accepts the transactions paying no fee.
+    /// the pooled transactions are kept, even if they pay less, and so are the transactions
+    /// of the blocks abandoned by `resolve_conflicts`, they are put back into the pool as they are
+    pub fn set_min_relay_fee_rate(&mut self, min_relay_fee_rate: Decimal) {
+        self.min_relay_fee_rate = min_relay_fee_rate;
+    }
+
+    /// get the lowest fee rate `add_to_pool` accepts
+    pub fn get_min_relay_fee_rate(&self) -> Decimal {
+        self.min_relay_fee_rate
+    }
+
+    /// get the transactions waiting in the transaction pool
+    pub fn get_tx_pool(&self) -> &[Transaction] {
+        &self.tx_pool
+    }
+
+    /// find a transaction packed in the chain by its ID,
+    /// return the index of the block containing it and the transaction itself
+    pub fn find_transaction(&self, tx_id: HashValue) -> Option<(usize, &Transaction)> {
+        let &(block_index, position) = self.tx_index.get(&tx_id)?;
+        let tx = self.get_block(block_index)?.data.get(position)?;
+        Some((block_index, tx))
+    }
+
+    /// the number of confirmations of a transaction:
+    /// 1 if it is packed in the latest block, plus 1 for every block mined on top of it.
+    ///
+    /// return `None` if the transaction is not on the chain, e.g. still waiting in the transaction pool,
+    /// or packed in a pruned block
+    pub fn confirmations(&self, tx_id: HashValue) -> Option<usize> {
+        let (block_index, _tx) = self.find_transaction(tx_id)?;
+        Some(self.len() - block_index)
+    }
+
+    /// find an output packed in the chain by its outpoint, spent or not
+    pub fn find_output(&self, tx_id: HashValue, output_index: usize) -> Option<&Output> {
+        self.find_transaction(tx_id)?
+            .1
+            .get_output_by_index(output_index)
+    }
+
+    /// get the UTXO set of the blockchain
+    pub fn get_utxo_set(&self) -> &UtxoSet {
+        &self.utxo_set
+    }
+
+    /// get the commitment hash of the UTXO set,
+    /// two nodes agree on their UTXO sets if their commitments are equal
+    pub fn utxo_commitment(&self) -> HashValue {
+        self.utxo_set.commitment()
+    }
+
+    /// get the balance of an address, the sum of all its unspent outputs,
+    /// `AmountOverflow` if the sum overflows
+    pub fn get_balance(&self, address: &HashValue) -> Result<Decimal, RustyCoinError> {
+        sum_amounts(
+            self.utxo_set
+                .get_by_locking_script(address.as_ref())
+                .iter()
+                .map(|entry| entry.output.get_amount()),
+        )
+    }
+
+    /// get the unspent outputs of an address with at least `min_confirmations` confirmations,
+    /// which can be used as the inputs of a new transaction, sorted by amount, smallest first.
+    ///
+    /// an output packed in the latest block has 1 confirmation, pass 0 to get every unspent output.
+    /// otherwise a coinbase output is returned only once it is mature,
+    /// with `COINBASE_MATURITY` confirmations
+    pub fn get_utxos_for(&self, address: &HashValue, min_confirmations: usize) -> Vec<UTXO> {
+        let mut utxos = self
+            .utxo_set
+            .get_by_locking_script(address.as_ref())
+            .iter()
+            .filter(|entry| {
+                let confirmations = self.len().saturating_sub(entry.block_index);
+                min_confirmations == 0
+                    || (confirmations >= min_confirmations
+                        && (!entry.is_coinbase || confirmations >= COINBASE_MATURITY))
+            })
+            .map(|entry| UTXO::from_entry(entry))
+            .collect::<Vec<UTXO>>();
+        utxos.sort_by_key(UTXO::get_amount);
+        utxos
+    }
+
+    /// get the balance an address can spend in the next block:
+    /// the sum of its unspent outputs with `REGULAR_MATURITY` confirmations, see `get_utxos_for`,
+    /// `AmountOverflow` if the sum overflows
+    pub fn get_spendable_balance(&self, address: &HashValue) -> Result<Decimal, RustyCoinError> {
+        sum_amounts(
+            self.get_utxos_for(address, REGULAR_MATURITY)
+                .iter()
+                .map(UTXO::get_amount),
+        )
+    }
+
+    /// get the block by its index
+    pub fn get_block(&self, index: usize) -> Option<&Block> {
+        self.blockchain.get(index)
+    }
+
+    /// get a block by its hash, e.g. the `prev_hash` of another block
+    pub fn get_block_by_hash(&self, hash: &HashValue) -> Option<&Block> {
+        self.get_block(*self.block_index.get(hash)?)
+    }
+
+    /// number of blocks in the blockchain, including the genesis block
+    pub fn len(&self) -> usize {
+        self.blockchain.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blockchain.is_empty()
+    }
+
+    /// iterate over the blocks, from the genesis block to the latest block
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Block> {
+        self.blockchain.iter()
+    }
+
+    /// resolve conflicts:
+    /// - an invalid candidate chain is rejected, so is a chain violating a checkpoint,
+    ///   see `set_checkpoints`
+    /// - a candidate chain forking more than `max_reorg_depth` blocks behind the tip is rejected,
+    ///   see `set_max_reorg_depth`
+    /// - the longest chain wins
+    /// - the hardest chain wins
+    ///
+    /// the transactions of the abandoned blocks are re-added to the transaction pool
+    /// through the checks of `add_to_pool`, and the pooled transactions confirmed by the adopted chain
+    /// or conflicting with it are dropped. if the local chain is kept,
+    /// the transactions of the candidate blocks are re-added the same way
+    ///
+    /// # Arguments
+    /// * `candidate_chain`: `&[Block]` - the candidate chain in a correct order
+    ///
+    /// # Returns
+    /// * `bool` - if the original chain has been replaced, return true, else return false
+    pub fn resolve_conflicts(&mut self, candidate_chain: &[Block]) -> bool {
+        // the candidate chain may come straight from the network, never adopt it unverified
+        if !self.verify_candidate_chain(candidate_chain) {
+            warn!("rejected an invalid candidate chain");
+            return false;
+        }
+
+        // search the bifurcation node, from the chain head to the tail
+        // compare the hash instead of the whole block (the candidate chain has been verified, so the hash is valid)
+
+        // first check the genesis block, if the genesis block is different, then the two chains are totally different, reject the new chain directly
+        if self.blockchain[0].hash != candidate_chain[0].hash {
+            return false;
+        }
+
+        // find the fork point
+        let mut fork_point = 0;
+        for (block, candidate_block) in self.blockchain.iter().zip(candidate_chain.iter()) {
+            if block.hash != candidate_block.hash {
                 break;
             }
-            average_timestamp += block.timestamp;
-            count += 1;
+            fork_point += 1;
+        }
+        debug!(
+            "the chains fork after {} common blocks: the local chain has {} blocks, the candidate chain has {}",
+            fork_point,
+            self.blockchain.len(),
+            candidate_chain.len()
+        );
+
+        // never rewrite the history confirmed for long, whatever work the candidate chain has
+        let reorg_depth = self.blockchain.len() - fork_point;
+        if reorg_depth > self.max_reorg_depth {
+            warn!(
+                "rejected a candidate chain forking {} blocks behind the tip, the limit is {}",
+                reorg_depth, self.max_reorg_depth
+            );
+            return false;
+        }
+
+        // longest chain wins
+        match self.blockchain.len().cmp(&candidate_chain.len()) {
+            Ordering::Less => {
+                // the candidate chain is longer, replace the current chain with the candidate chain
+                info!("adopted the candidate chain, it is longer");
+                self.adopt_chain(candidate_chain, fork_point);
+                true
+            }
+            Ordering::Equal => {
+                // the hardest chain wins, the chains share the work up to the fork point
+                let current_chain_work = self.total_work();
+                let candidate_chain_work = self.cumulative_work[fork_point - 1]
+                    .saturating_add(Self::chain_work(&candidate_chain[fork_point..]));
+
+                match current_chain_work.cmp(&candidate_chain_work) {
+                    Ordering::Greater => {
+                        // the current chain is harder, no need to change
+                        info!("kept the local chain, it has more work");
+                        // add the unpacked transactions in the candidate chain to the transaction pool
+                        self.recycle_transactions(&candidate_chain[fork_point..]);
+                        false
+                    }
+                    Ordering::Less => {
+                        // the candidate chain is harder, replace the current chain with the candidate chain
+                        info!("adopted the candidate chain, it has more work");
+                        self.adopt_chain(candidate_chain, fork_point);
+                        true
+                    }
+                    Ordering::Equal => {
+                        // if the work of the two chains are the same again, then:
+                        // the first chain wins
+                        info!("kept the local chain, the chains have the same work");
+                        // add the unpacked transactions in the candidate chain to the transaction pool
+                        self.recycle_transactions(&candidate_chain[fork_point..]);
+                        false
+                    }
+                }
+            }
+            Ordering::Greater => {
+                // the current chain is longer, no need to change
+                info!("kept the local chain, it is longer");
+                // add the unpacked transactions in the candidate chain to the transaction pool
+                self.recycle_transactions(&candidate_chain[fork_point..]);
+                false
+            }
         }
-        average_timestamp /= count;
-        average_timestamp <= timestamp && timestamp <= current_time
     }
 
-    /// verify a regular transaction's integrity, check if it is valid.
-    ///
-    /// if it is a coinbase transaction, please use `fn verify_coinbase_transaction` instead.
-    /// - check if the transaction hash is equal to the transaction ID
-    /// - check if the transaction fee is valid
-    /// - check if the inputs are legal, the inputs should be unspent
-    ///     - check if the unlock script is valid
-    ///     - check if the previous transaction hash is valid
-    ///     - check if the previous output index is valid
+    /// fetch the chain of a peer from its `/chain` endpoint, and resolve the conflicts with it,
+    /// see `Blockchain::resolve_conflicts`.
     ///
-    /// # Arguments:
-    /// * `transaction`: &Transaction - the transaction to be verified
+    /// returns whether the local chain has been replaced,
+    /// e.g. `Ok(false)` if the chain of the peer is shorter or invalid,
+    /// or an error if the peer is unreachable or responds with something else than a chain
+    pub async fn sync_from_peer(&mut self, addr: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        let response = reqwest::get(format!("{addr}/chain"))
+            .await?
+            .error_for_status()?;
+        let candidate_chain: Vec<Block> = serde_json::from_str(&response.text().await?)?;
+        Ok(self.resolve_conflicts(&candidate_chain))
+    }
+
+    /// catch up with the chain of a peer range by range from its `/blocks` endpoint,
+    /// `batch_size` blocks per request, without downloading the whole chain at once:
+    /// - the blocks extending the local chain are verified and appended one by one,
+    ///   each of them must be mined at the difficulty the chain requires, see `next_difficulty`
+    /// - if the chain of the peer forks from the local chain, it is downloaded in ranges
+    ///   from the genesis block, and the conflicts are resolved, see `Blockchain::resolve_conflicts`
     ///
-    /// returns: bool - if the transaction is valid, return true, else return false
-    fn verify_regular_transaction(&self, transaction: &Transaction) -> bool {
-        // check if inputs are legal:
-        // - check prev_transaction_hash
-        // - check unlocking_script
-        let mut input_fee_sum = dec!(0.0);
-        for input in transaction.get_inputs() {
-            // get previous block that contain this input tx, if it is None, then return false
-            let block = match self.blockchain.get(input.get_prev_block_index()) {
-                Some(block) => block,
-                None => {
-                    return false;
+    /// returns whether the local chain has changed, it stops at the first invalid block,
+    /// or an error if the peer is unreachable or responds with something else than blocks
+    pub async fn sync_range(
+        &mut self,
+        addr: &str,
+        batch_size: usize,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let mut changed = false;
+        loop {
+            let blocks = Self::fetch_blocks(addr, self.len(), batch_size).await?;
+            if blocks.is_empty() {
+                return Ok(changed);
+            }
+            for block in blocks {
+                let tip_hash = self.get_last_block().map(|last_block| last_block.hash);
+                if block.index != self.len() || Some(block.prev_hash) != tip_hash {
+                    info!("the chain of {addr} forks from the local chain, downloading it");
+                    let candidate_chain = Self::fetch_all_blocks(addr, batch_size).await?;
+                    return Ok(self.resolve_conflicts(&candidate_chain) || changed);
                 }
-            };
-            // get the previous transaction, if it is None, then return false
-            let prev_tx = match block.get_tx_by_id(input.get_prev_tx_hash()) {
-                Some(prev_tx) => prev_tx,
-                None => {
-                    return false;
+                let difficulty = self.next_difficulty();
+                if !self.matches_checkpoint(&block) || !self.verify_block(&block, difficulty) {
+                    warn!("{addr} sent the invalid block {}", block.index);
+                    return Ok(changed);
                 }
-            };
-            // get the output, if the index is invalid, then return false
-            let prev_output = match prev_tx.get_output_by_index(input.get_prev_output_index()) {
-                Some(prev_output) => prev_output,
+                self.add_block(block);
+                changed = true;
+            }
+        }
+    }
+
+    /// fetch at most `count` blocks from `from` on from the `/blocks` endpoint of a peer
+    async fn fetch_blocks(
+        addr: &str,
+        from: usize,
+        count: usize,
+    ) -> Result<Vec<Block>, Box<dyn std::error::Error>> {
+        let response = reqwest::get(format!("{addr}/blocks?from={from}&count={count}"))
+            .await?
+            .error_for_status()?;
+        Ok(serde_json::from_str(&response.text().await?)?)
+    }
+
+    /// fetch the whole chain of a peer from its `/blocks` endpoint, `batch_size` blocks per request
+    async fn fetch_all_blocks(
+        addr: &str,
+        batch_size: usize,
+    ) -> Result<Vec<Block>, Box<dyn std::error::Error>> {
+        let mut chain = vec![];
+        loop {
+            let blocks = Self::fetch_blocks(addr, chain.len(), batch_size).await?;
+            if blocks.is_empty() {
+                return Ok(chain);
+            }
+            chain.extend(blocks);
+        }
+    }
+
+    /// get the latest block of the blockchain
+    pub fn get_last_block(&self) -> Option<&Block> {
+        self.blockchain.last()
+    }
+
+    /// the message of the genesis transaction, see `Blockchain::new`,
+    /// `None` if it is not valid UTF-8, or the genesis block has been pruned
+    pub fn genesis_message(&self) -> Option<String> {
+        let genesis_tx = self.get_block(0)?.data.first()?;
+        String::from_utf8(genesis_tx.get_additional_data()?.to_vec()).ok()
+    }
+
+    /// summarize the chain, e.g. for a status endpoint
+    pub fn chain_metadata(&self) -> ChainMetadata {
+        let hash_of = |block: Option<&Block>| block.map_or(HashValue::new([0u8; 32]), |b| b.hash);
+        ChainMetadata {
+            height: self.len().saturating_sub(1),
+            total_work: self.total_work(),
+            genesis_hash: hash_of(self.get_block(0)),
+            tip_hash: hash_of(self.get_last_block()),
+            chain_id: self.chain_id,
+        }
+    }
+
+    /// summarize every block of the chain for a block explorer,
+    /// without the bodies of the transactions, see `ExplorerSnapshot`,
+    /// `AmountOverflow` if the outputs of a block sum up beyond `Decimal`
+    pub fn explorer_snapshot(&self) -> Result<ExplorerSnapshot, RustyCoinError> {
+        let blocks = self
+            .blockchain
+            .iter()
+            .map(|block| {
+                Ok(BlockSummary {
+                    height: block.index,
+                    hash: block.hash,
+                    timestamp: block.timestamp,
+                    tx_count: block.data.len(),
+                    total_output_value: sum_amounts(
+                        block
+                            .data
+                            .iter()
+                            .flat_map(Transaction::get_outputs)
+                            .map(Output::get_amount),
+                    )?,
+                    difficulty: block.difficulty,
+                })
+            })
+            .collect::<Result<Vec<BlockSummary>, RustyCoinError>>()?;
+        Ok(ExplorerSnapshot {
+            metadata: self.chain_metadata(),
+            blocks,
+        })
+    }
+
+    /// run a battery of consistency checks over the whole blockchain,
+    /// and report every problem found instead of stopping at the first one:
+    /// - the index of each block matches its position in the chain
+    /// - each block links to the hash of its previous block
+    /// - the cumulative work of the chain (the sum of `Block::work`) never wraps around
+    /// - no transaction ID appears twice
+    /// - the UTXO set matches a fresh scan of the chain, unless the chain has been pruned
+    /// - the cumulative work kept up to date (see `total_work`) matches a recomputation
+    ///
+    /// returns: `Vec<HealthIssue>` - an empty vector if the blockchain is healthy
+    pub fn self_check(&self) -> Vec<HealthIssue> {
+        let mut issues = vec![];
+
+        let mut chain_work = 0_u128;
+        let mut tx_ids = BTreeSet::new();
+        for (position, block) in self.blockchain.iter().enumerate() {
+            if block.index != position {
+                issues.push(HealthIssue::BlockIndexMismatch {
+                    position,
+                    index: block.index,
+                });
+            }
+
+            if position > 0 && block.prev_hash != self.blockchain[position - 1].hash {
+                issues.push(HealthIssue::BrokenLink { position });
+            }
+
+            match chain_work.checked_add(block.work()) {
+                Some(new_chain_work) => chain_work = new_chain_work,
                 None => {
-                    return false;
+                    issues.push(HealthIssue::NonMonotonicWork { position });
+                    chain_work = u128::MAX;
                 }
-            };
+            }
 
-            input_fee_sum += prev_output.get_amount();
-            // verify the unlock script
-            if !Transaction::verify_scripts(
-                prev_tx,
-                prev_output.get_locking_script(),
-                input.get_unlock_script(),
-            ) {
-                return false;
+            for tx in &block.data {
+                if !tx_ids.insert(tx.get_transaction_id()) {
+                    issues.push(HealthIssue::DuplicateTransaction {
+                        position,
+                        tx_id: tx.get_transaction_id(),
+                    });
+                }
             }
         }
-        // check the transaction hash, if it is equal to the transaction ID
-        if transaction.get_transaction_id() != transaction.sha256() {
-            return false;
+
+        // the transactions of a pruned chain can't be scanned anymore
+        if self.pruned_height == 0 && UtxoSet::from_blocks(&self.blockchain) != self.utxo_set {
+            issues.push(HealthIssue::UtxoSetMismatch);
         }
-        // check if the transaction fee is valid
-        // calculate the output sum
-        for output in transaction.get_outputs() {
-            // all the output amount should be positive (>= 0)
-            if output.get_amount() < dec!(0.0) {
-                return false;
-            }
-            input_fee_sum -= output.get_amount();
+
+        if Self::accumulate_work(&self.blockchain) != self.cumulative_work {
+            issues.push(HealthIssue::CumulativeWorkMismatch);
         }
-        if transaction.get_transaction_fee() != input_fee_sum {
-            return false;
+
+        issues
+    }
+
+    /// get the cumulative proof-of-work of this blockchain in O(1),
+    /// it is kept up to date as the blocks are added, see `Blockchain::chain_work`
+    pub fn total_work(&self) -> u128 {
+        self.cumulative_work.last().copied().unwrap_or(0)
+    }
+
+    /// calculate the cumulative proof-of-work of a chain, the sum of `Block::work` of all its blocks
+    pub fn chain_work(chain: &[Block]) -> u128 {
+        chain
+            .iter()
+            .fold(0u128, |sum, block| sum.saturating_add(block.work()))
+    }
+
+    /// verify all the block in the chain one by one,
+    ///
+    /// the blocks must be arranged in the correct order:
+    ///
+    /// genesis block -> block 1 -> block 2 -> ... -> block n
+    ///
+    /// every block is verified against the blocks before it,
+    /// so the UTXO set and the timestamps are the ones at the time it was mined,
+    /// and it must be mined at the difficulty they require, see `next_difficulty`.
+    ///
+    /// the chain is verified under the default consensus parameters,
+    /// e.g. `DEFAULT_TARGET_BLOCK_INTERVAL_SECS`, see `verify_candidate_chain` for the others
+    pub fn verify_chain(chain: &[Block]) -> bool {
+        let Some(genesis_block) = chain.first() else {
+            return false;
+        };
+        Self::new_chain_start_with(genesis_block.clone()).verify_candidate_chain(chain)
+    }
+
+    /// same as `verify_chain`, under the consensus parameters of this blockchain,
+    /// its target block interval and its fee policy, see `set_target_block_interval_secs`
+    /// and `set_fee_policy`,
+    /// and the chain must also match its checkpoints at the heights it reaches.
+    ///
+    /// the hash of the highest checkpoint reached commits to every block up to it,
+    /// so the P2PKH signatures of these blocks are assumed valid instead of verified,
+    /// all the other checks are still done
+    pub fn verify_candidate_chain(&self, chain: &[Block]) -> bool {
+        let checkpoints = &self.checkpoints;
+        if let Some((height, _hash)) = checkpoints.iter().find(|(height, hash)| {
+            chain
+                .get(**height)
+                .is_some_and(|block| block.hash != **hash)
+        }) {
+            warn!("the block {} doesn't match the checkpoint", height);
+            return false;
+        }
+        let assumed_valid_height = checkpoints
+            .range(..chain.len())
+            .next_back()
+            .map(|(height, _hash)| *height);
+
+        let Some(genesis_block) = chain.first() else {
+            return false;
+        };
+        let mut new_chain = Blockchain::new_chain_start_with(genesis_block.clone());
+        self.copy_consensus_params_to(&mut new_chain);
+        if !new_chain.verify_genesis_block(genesis_block) {
+            return false;
+        }
+
+        for block in &chain[1..] {
+            let difficulty = new_chain.next_difficulty();
+            let verified = if assumed_valid_height.is_some_and(|height| block.index <= height) {
+                new_chain
+                    .verify_block_inner(block, difficulty, &Sha256dHasher, false)
+                    .inspect_err(|e| warn!("invalid block {}: {}", block.index, e))
+                    .is_ok()
+            } else {
+                new_chain.verify_block(block, difficulty)
+            };
+            if !verified {
+                return false;
+            }
+            new_chain.add_block(block.clone());
+        }
+
+        true
+    }
+
+    /// verify the genesis block, it is not mined and has no previous block:
+    /// - check the previous hash, it should be all zeros
+    /// - check the merkle root of the block
+    /// - check the hash value of the block
+    fn verify_genesis_block(&self, block: &Block) -> bool {
+        block.index == 0
+            && self.verify_prev_hash(block)
+            && self.verify_merkle_root(block)
+            && block.sha256() == block.hash
+    }
+
+    /// verify a block's integrity, check if it is valid
+    /// - check the block has at least one transaction, the coinbase transaction
+    /// - check all regular transactions in the block
+    /// - check the coinbase transaction
+    ///     - if it follows the reward rule of this blockchain
+    ///     - if it equals to the sum of transaction fee
+    /// - check the size of the block, it should not exceed `MAX_BLOCK_SIZE`
+    /// - check the P2PKH signatures of the block in one batch,
+    ///   see `verify_block_signatures_batched`
+    /// - check the merkle root of the block
+    /// - check the difficulty of the block
+    /// - check the hash value of the block
+    /// - check the timestamp of the block
+    ///
+    /// the first failed check is logged as a warning,
+    /// use `verify_block_detailed` to get it as an error
+    pub fn verify_block(&self, block: &Block, network_difficulty: u32) -> bool {
+        self.verify_block_with(block, network_difficulty, &Sha256dHasher)
+    }
+
+    /// verify a block whose proof of work is computed by `hasher`, see `verify_block`
+    pub fn verify_block_with<H: Hasher + ?Sized>(
+        &self,
+        block: &Block,
+        network_difficulty: u32,
+        hasher: &H,
+    ) -> bool {
+        self.verify_block_detailed_with(block, network_difficulty, hasher)
+            .inspect_err(|e| warn!("invalid block {}: {}", block.index, e))
+            .is_ok()
+    }
+
+    /// same as `verify_block`, return the first failed check instead of logging it
+    pub fn verify_block_detailed(
+        &self,
+        block: &Block,
+        network_difficulty: u32,
+    ) -> Result<(), BlockValidationError> {
+        self.verify_block_detailed_with(block, network_difficulty, &Sha256dHasher)
+    }
+
+    /// verify a block whose proof of work is computed by `hasher`, see `verify_block_detailed`
+    pub fn verify_block_detailed_with<H: Hasher + ?Sized>(
+        &self,
+        block: &Block,
+        network_difficulty: u32,
+        hasher: &H,
+    ) -> Result<(), BlockValidationError> {
+        self.verify_block_inner(block, network_difficulty, hasher, true)
+    }
+
+    /// see `verify_block_detailed_with`, the P2PKH signatures are assumed valid
+    /// unless `check_signatures` is set, e.g. below a checkpoint
+    fn verify_block_inner<H: Hasher + ?Sized>(
+        &self,
+        block: &Block,
+        network_difficulty: u32,
+        hasher: &H,
+        check_signatures: bool,
+    ) -> Result<(), BlockValidationError> {
+        if block.index != 0 && block.data.is_empty() {
+            return Err(BlockValidationError::EmptyBlock);
+        }
+        if block.size() > MAX_BLOCK_SIZE {
+            return Err(BlockValidationError::TooLarge { size: block.size() });
+        }
+        let verified_signatures = if check_signatures {
+            self.batch_verify_signatures(block)
+        } else {
+            Some(Self::all_inputs(block))
+        };
+        let Some(verified_signatures) = verified_signatures else {
+            return Err(BlockValidationError::InvalidSignatures);
+        };
+        if !self.verify_transactions_with(
+            &block.data,
+            block.index,
+            block.timestamp,
+            &verified_signatures,
+        ) {
+            return Err(BlockValidationError::InvalidTransactions);
+        }
+        if !self.verify_merkle_root(block) {
+            return Err(BlockValidationError::MerkleRootMismatch {
+                merkle_root: block.merkle_root,
+                expected: block.calc_merkle_root(),
+            });
+        }
+        if !self.verify_difficulty(block, network_difficulty) {
+            return Err(BlockValidationError::DifficultyMismatch {
+                difficulty: block.difficulty,
+                expected: network_difficulty,
+            });
+        }
+        if !self.verify_block_hash(block, hasher) {
+            return Err(BlockValidationError::InvalidHash { hash: block.hash });
+        }
+        if !self.verify_prev_hash(block) {
+            return Err(BlockValidationError::PrevHashMismatch {
+                prev_hash: block.prev_hash,
+            });
+        }
+        if !self.verify_timestamp(block.timestamp) {
+            return Err(BlockValidationError::InvalidTimestamp {
+                timestamp: block.timestamp,
+            });
+        }
+        Ok(())
+    }
+
+    /// verify all the transactions of the block at `block_index` mined at `block_time`:
+    /// - the first transaction is the coinbase transaction, and it is the only one
+    /// - no output is spent twice within the block
+    /// - the lock time of every transaction has been reached, see `Transaction::is_final`
+    /// - every transaction is valid on its own
+    pub fn verify_transactions(
+        &self,
+        transactions: &[Transaction],
+        block_index: usize,
+        block_time: u64,
+    ) -> bool {
+        self.verify_transactions_with(transactions, block_index, block_time, &BTreeSet::new())
+    }
+
+    /// verify the signatures of all the P2PKH inputs of a block together, instead of
+    /// one by one in the script interpreter.
+    ///
+    /// the signatures are split into chunks verified in parallel, if any of them is invalid,
+    /// they are checked one by one to log the offending input.
+    /// the inputs that don't refer to an unspent P2PKH output are left to `verify_transactions`.
+    pub fn verify_block_signatures_batched(&self, block: &Block) -> bool {
+        self.batch_verify_signatures(block).is_some()
+    }
+
+    /// all the inputs (transaction ID and input index) of a block
+    fn all_inputs(block: &Block) -> BTreeSet<(HashValue, usize)> {
+        block
+            .data
+            .iter()
+            .flat_map(|tx| {
+                (0..tx.get_inputs().len()).map(|input_index| (tx.get_transaction_id(), input_index))
+            })
+            .collect()
+    }
+
+    /// verify the P2PKH signatures of a block in one batch,
+    /// return the inputs (transaction ID and input index) whose signatures are valid,
+    /// or `None` if any signature is invalid
+    fn batch_verify_signatures(&self, block: &Block) -> Option<BTreeSet<(HashValue, usize)>> {
+        let mut inputs = vec![];
+        let mut checks: Vec<SignatureCheck> = vec![];
+        for tx in &block.data {
+            for (input_index, input) in tx.get_inputs().iter().enumerate() {
+                let outpoint =
+                    OutPoint::new(input.get_prev_tx_hash(), input.get_prev_output_index());
+                let Some(prev_entry) = self.utxo_set.get(&outpoint) else {
+                    continue;
+                };
+                if let Some(check) =
+                    tx.p2pkh_signature_check(input_index, prev_entry.output.get_locking_script())
+                {
+                    inputs.push((tx.get_transaction_id(), input_index));
+                    checks.push(check);
+                }
+            }
+        }
+
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        let chunk_size = checks.len().div_ceil(threads).max(1);
+        let all_valid = thread::scope(|scope| {
+            let handles: Vec<_> = checks
+                .chunks(chunk_size)
+                .map(|chunk| scope.spawn(|| chunk.iter().all(SignatureCheck::verify)))
+                .collect();
+            handles.into_iter().all(|handle| handle.join().unwrap())
+        });
+        if !all_valid {
+            // fall back to one by one to find the offending input
+            if let Some((tx_id, input_index)) = checks
+                .iter()
+                .zip(&inputs)
+                .find(|(check, _)| !check.verify())
+                .map(|(_, input)| input)
+            {
+                warn!(
+                    "invalid signature of input {} of transaction {}",
+                    input_index, tx_id
+                );
+            }
+            return None;
+        }
+        Some(inputs.into_iter().collect())
+    }
+
+    /// same as `verify_transactions`, the signatures of the `verified_signatures` inputs
+    /// (transaction ID and input index) are not checked again
+    fn verify_transactions_with(
+        &self,
+        transactions: &[Transaction],
+        block_index: usize,
+        block_time: u64,
+        verified_signatures: &BTreeSet<(HashValue, usize)>,
+    ) -> bool {
+        // the first transaction in every block is the coinbase transaction, and no other one
+        let Some((coinbase_tx, regular_txs)) = transactions.split_first() else {
+            return false;
+        };
+        if !Transaction::is_coinbase_transaction(coinbase_tx)
+            || regular_txs.iter().any(Transaction::is_coinbase_transaction)
+        {
+            return false;
+        }
+
+        // every outpoint can only be spent once in a block
+        let mut spent_outpoints = BTreeSet::new();
+        let no_double_spend = transactions
+            .iter()
+            .flat_map(|tx| tx.get_inputs())
+            .all(|input| {
+                spent_outpoints.insert(OutPoint::new(
+                    input.get_prev_tx_hash(),
+                    input.get_prev_output_index(),
+                ))
+            });
+        if !no_double_spend {
+            return false;
+        }
+
+        if !transactions
+            .iter()
+            .all(|tx| tx.is_final(block_index, block_time))
+        {
+            return false;
+        }
+
+        self.verify_coinbase_transaction(coinbase_tx, transactions, block_index)
+            && regular_txs
+                .iter()
+                .all(|tx| self.verify_regular_transaction(tx, block_index, verified_signatures))
+    }
+
+    /// verify a coinbase transaction's integrity, check if it is valid.
+    /// - check if the transaction hash is equal to the transaction ID
+    /// - check if the transaction is bound to the chain ID of this chain
+    /// - check if the amounts have at most `AMOUNT_DECIMAL_PLACES` decimal places
+    /// - check if the committed height is equal to the block index
+    /// - check if the reward is valid
+    ///
+    /// # Arguments:
+    /// * `coinbase_tx`: `&Transaction` - the coinbase transaction to be verified
+    /// * `transactions`: `&[Transaction]` - all the transactions in the block
+    /// * `block_index`: `usize` - the index of the block
+    ///
+    /// returns: `bool` - if the coinbase transaction is valid, return true, else return false
+    fn verify_coinbase_transaction(
+        &self,
+        coinbase_tx: &Transaction,
+        transactions: &[Transaction],
+        block_index: usize,
+    ) -> bool {
+        // check if the transaction hash is equal to the transaction ID
+        if coinbase_tx.sha256() != coinbase_tx.get_transaction_id() {
+            return false;
+        }
+
+        // check if the transaction is bound to this network
+        if coinbase_tx.get_chain_id() != self.chain_id {
+            return false;
+        }
+
+        // check if the amounts can be counted in the smallest unit
+        if !coinbase_tx.has_valid_amount_precision() {
+            return false;
+        }
+
+        // check if the committed height is equal to the block index
+        let height_commitment = Self::coinbase_height_commitment(block_index);
+        if !coinbase_tx
+            .get_additional_data()
+            .is_some_and(|data| data.starts_with(&height_commitment))
+        {
+            return false;
+        }
+
+        // sum the output fee of the coinbase transaction
+        if coinbase_tx
+            .get_outputs()
+            .iter()
+            .any(|output| output.get_amount() < dec!(0.0))
+        {
+            return false;
+        }
+        let Ok(output_fee_sum) =
+            sum_amounts(coinbase_tx.get_outputs().iter().map(Output::get_amount))
+        else {
+            return false;
+        };
+
+        // skip the coinbase transaction
+        self.coinbase_reward(block_index, &transactions[1..])
+            .is_ok_and(|reward| output_fee_sum <= reward)
+    }
+
+    /// verify the merkle root of the block matches its transactions,
+    /// the all-zero root of no transaction is only accepted for the genesis block
+    fn verify_merkle_root(&self, block: &Block) -> bool {
+        (block.index == 0 || !block.data.is_empty())
+            && block.merkle_root == block.calc_merkle_root()
+    }
+
+    fn verify_difficulty(&self, block: &Block, network_difficulty: u32) -> bool {
+        block.difficulty == network_difficulty
+    }
+
+    fn verify_block_hash<H: Hasher + ?Sized>(&self, block: &Block, hasher: &H) -> bool {
+        block.pow_hash(hasher).ct_eq(&block.hash)
+            && block.hash.meets_target(&block.target_threshold())
+    }
+
+    fn verify_prev_hash(&self, block: &Block) -> bool {
+        // the genesis block has no previous block
+        if block.index == 0 {
+            return block.prev_hash == HashValue::new([0u8; 32]);
+        }
+        if let Some(prev_block) = self.get_block(block.index - 1) {
+            prev_block.hash == block.prev_hash
+        } else {
+            false
+        }
+    }
+
+    /// verify timestamp (in seconds, UTC +0:00) of the block:
+    /// the timestamp of the block should be at most `MAX_FUTURE_BLOCK_INTERVALS` target block intervals
+    /// ahead of the current time
+    /// and the timestamp of the block should be larger than or equal to the average timestamps of the previous 10 blocks
+    /// (if there are less than 10 blocks, then use the average timestamps of all the previous blocks)
+    fn verify_timestamp(&self, timestamp: u64) -> bool {
+        let current_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let mut average_timestamp = 0_u64;
+        let mut count = 0;
+        for block in self.blockchain.iter().rev() {
+            if count >= 10 {
+                break;
+            }
+            average_timestamp += block.timestamp;
+            count += 1;
+        }
+        average_timestamp /= count;
+        let max_drift = MAX_FUTURE_BLOCK_INTERVALS * self.target_block_interval_secs;
+        average_timestamp <= timestamp && timestamp <= current_time.saturating_add(max_drift)
+    }
+
+    /// verify a regular transaction's integrity, check if it is valid.
+    ///
+    /// if it is a coinbase transaction, please use `fn verify_coinbase_transaction` instead.
+    /// - check if the transaction hash is equal to the transaction ID
+    /// - check if the transaction is bound to the chain ID of this chain,
+    ///   so transactions of other networks can't be replayed
+    /// - check if the transaction fee is valid: it is derived by `Transaction::compute_fee`,
+    ///   and the declared fee must be consistent with it
+    /// - check if the amounts have at most `AMOUNT_DECIMAL_PLACES` decimal places
+    /// - check if the inputs are legal, the inputs should be unspent
+    ///     - check if no outpoint is spent twice by the transaction, it would be counted twice by the fee
+    ///     - check if the unlock script is valid
+    ///     - check if the previous transaction hash, block index and output index
+    ///       refer to an output in the UTXO set
+    ///     - check if the previous output is mature: a coinbase output needs
+    ///       `COINBASE_MATURITY` confirmations, a regular output needs `REGULAR_MATURITY`
+    ///
+    /// # Arguments:
+    /// * `transaction`: &Transaction - the transaction to be verified
+    /// * `block_index`: usize - the index of the block the transaction is packed into
+    /// * `verified_signatures`: &BTreeSet<(HashValue, usize)> - the inputs whose signatures
+    ///   have already been verified, see `verify_block_signatures_batched`
+    ///
+    /// returns: bool - if the transaction is valid, return true, else return false
+    fn verify_regular_transaction(
+        &self,
+        transaction: &Transaction,
+        block_index: usize,
+        verified_signatures: &BTreeSet<(HashValue, usize)>,
+    ) -> bool {
+        // the signatures of a transaction of another network are valid there only
+        if transaction.get_chain_id() != self.chain_id {
+            return false;
+        }
+        // amounts finer than the smallest unit are not real denominations
+        if !transaction.has_valid_amount_precision() {
+            return false;
+        }
+        // an outpoint listed twice would be counted twice by the fee
+        let mut outpoints = BTreeSet::new();
+        if !transaction.get_inputs().iter().all(|input| {
+            outpoints.insert(OutPoint::new(
+                input.get_prev_tx_hash(),
+                input.get_prev_output_index(),
+            ))
+        }) {
+            return false;
+        }
+        // check if inputs are legal:
+        // - check prev_transaction_hash
+        // - check unlocking_script
+        for (input_index, input) in transaction.get_inputs().iter().enumerate() {
+            // get the unspent output this input refers to, if it is None
+            // (never existed, already spent, or a data output), then return false
+            let outpoint = OutPoint::new(input.get_prev_tx_hash(), input.get_prev_output_index());
+            let prev_entry = match self.utxo_set.get(&outpoint) {
+                Some(entry) if entry.block_index == input.get_prev_block_index() => entry,
+                _ => {
+                    return false;
+                }
+            };
+            // check if the previous output has got enough confirmations
+            let maturity = if prev_entry.is_coinbase {
+                COINBASE_MATURITY
+            } else {
+                REGULAR_MATURITY
+            };
+            if block_index.saturating_sub(prev_entry.block_index) < maturity {
+                return false;
+            }
+            let prev_output = &prev_entry.output;
+
+            // verify the unlock script
+            if !Transaction::verify_scripts_with(
+                transaction,
+                input_index,
+                input.get_unlock_script(),
+                prev_output.get_locking_script(),
+                verified_signatures.contains(&(transaction.get_transaction_id(), input_index)),
+            ) {
+                return false;
+            }
+        }
+        // check the transaction hash, if it is equal to the transaction ID
+        if transaction.get_transaction_id() != transaction.sha256() {
+            return false;
+        }
+        // check if the transaction fee is valid,
+        // the declared fee must match the one derived from the inputs and outputs
+        match transaction.compute_fee(self) {
+            Ok(fee) => fee == transaction.get_transaction_fee(),
+            Err(_) => false,
+        }
+    }
+
+    /// reward rule of the coinbase transaction
+    /// - the reward of the coinbase transaction is 50 rusty coin at the beginning
+    /// - the reward halves every `HALVING_INTERVAL` blocks, rounding down to the smallest unit
+    ///
+    /// the reward is calculated in integer arithmetic, so there is no float rounding
+    fn reward_algorithm(index: usize) -> Decimal {
+        let halvings = index / HALVING_INTERVAL;
+        // the reward has fallen below the smallest unit after 33 halvings, shifting by 64 or more overflows
+        let reward_units = if halvings >= 64 {
+            0
+        } else {
+            INITIAL_REWARD_UNITS >> halvings
+        };
+        Decimal::new(reward_units as i64, 8)
+    }
+
+    /// create the first block of a blockchain, the genesis block
+    ///
+    /// # Arguments
+    /// * `init_msg: &str` - the message of the genesis transaction of the genesis block
+    fn create_genesis_block(init_msg: &str) -> Block {
+        let init_time = match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(duration) => duration.as_secs(),
+            Err(e) => {
+                panic!("SystemTimeError: {}", e);
+            }
+        };
+
+        Self::create_genesis_block_from(&GenesisConfig {
+            version: "0.1v test".to_string(),
+            timestamp: init_time,
+            difficulty: DEFAULT_GENESIS_DIFFICULTY,
+            message: init_msg.to_string(),
+            chain_id: 0,
+            target_block_interval_secs: DEFAULT_TARGET_BLOCK_INTERVAL_SECS,
+            testnet: false,
+        })
+    }
+
+    /// create the genesis block from a genesis config
+    fn create_genesis_block_from(config: &GenesisConfig) -> Block {
+        let mut genesis_transaction = Transaction::new(
+            vec![],
+            vec![],
+            HashValue::new([0u8; 32]),
+            dec!(0.0),
+            Some(config.message.as_bytes().to_vec()),
+        );
+        genesis_transaction.set_chain_id(config.chain_id);
+
+        genesis_transaction.update_digest(); // update genesis transaction's digest (transaction_id, hash value of the transaction)
+
+        let mut genesis_block = Block {
+            version: config.version.clone(),
+            index: 0,
+            data: vec![genesis_transaction],
+            timestamp: config.timestamp,
+            prev_hash: HashValue::new([0; 32]),
+            hash: HashValue::new([0; 32]),
+            merkle_root: HashValue::new([0; 32]),
+            difficulty: config.difficulty,
+            nonce: 0,
+            merkle_cache: MerkleCache::default(),
+        };
+
+        genesis_block.merkle_root = genesis_block.calc_merkle_root(); // update merkle root of the genesis block
+        genesis_block.hash = genesis_block.sha256(); // update hash value of the genesis block
+        genesis_block
+    }
+}
+
+#[cfg(any(test, feature = "test-utils"))]
+impl Blockchain {
+    /// build a reproducible blockchain of `n` blocks on top of the genesis block,
+    /// a shared fixture for tests and benchmarks.
+    ///
+    /// everything is derived from `seed`: the keys of the wallets, the timestamps and the transactions,
+    /// so two chains built with the same seed are identical.
+    /// - the blocks are mined at a trivial difficulty
+    /// - each block pays its reward to one of three seeded wallets
+    /// - once the rewards are mature, i.e. from block `COINBASE_MATURITY + 1` on,
+    ///   each block also contains a transaction spending the reward of block `index - COINBASE_MATURITY`
+    pub fn deterministic_chain(n: usize, seed: u64) -> Blockchain {
+        use crate::wallet::Wallet;
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+        use secp256k1::SecretKey;
+
+        const GENESIS_TIME: u64 = 1_700_000_000;
+        const BLOCK_INTERVAL: u64 = 10;
+        const DIFFICULTY: u32 = 0x207FFFFF;
+
+        let mut rng = StdRng::seed_from_u64(seed);
+        let wallets = (0..3)
+            .map(|_| Wallet::from_secret_key(SecretKey::new(&mut rng)))
+            .collect::<Vec<Wallet>>();
+
+        let mut blockchain = Blockchain::new_with_genesis(&GenesisConfig {
+            version: "0.1v test".to_string(),
+            timestamp: GENESIS_TIME,
+            difficulty: DIFFICULTY,
+            message: format!("deterministic chain, seed: {seed}"),
+            chain_id: 0,
+            target_block_interval_secs: DEFAULT_TARGET_BLOCK_INTERVAL_SECS,
+            testnet: false,
+        });
+
+        for index in 1..=n {
+            let miner = &wallets[index % wallets.len()];
+            let mut transactions = vec![];
+
+            if index > COINBASE_MATURITY {
+                // spend the mature reward, half to the next wallet, half back as change
+                let prev_index = index - COINBASE_MATURITY;
+                let prev_miner = &wallets[prev_index % wallets.len()];
+                let prev_coinbase = blockchain.get_block(prev_index).unwrap().data[0].clone();
+                let amount = prev_coinbase.get_outputs()[0].get_amount();
+                let receiver = &wallets[(index + 1) % wallets.len()];
+                let tx = prev_miner
+                    .transfer_credits(
+                        vec![UTXO::new(prev_coinbase, prev_index, 0)],
+                        vec![
+                            (amount / dec!(2), receiver.get_address()),
+                            (amount - amount / dec!(2), prev_miner.get_address()),
+                        ],
+                        None,
+                    )
+                    .unwrap();
+                transactions.push(tx);
+            }
+
+            let block = blockchain
+                .generate_new_block(
+                    vec![(miner.get_address(), Self::reward_algorithm(index))],
+                    "0.1v test".to_string(),
+                    GENESIS_TIME + index as u64 * BLOCK_INTERVAL,
+                    DIFFICULTY,
+                    Some(transactions),
+                )
+                .unwrap();
+            blockchain.add_block(block);
+        }
+
+        blockchain
+    }
+}
+
+/// a problem detected by `Blockchain::self_check`
+#[derive(Debug, Clone, PartialEq)]
+pub enum HealthIssue {
+    BlockIndexMismatch { position: usize, index: usize }, // the index of a block differs from its position
+    BrokenLink { position: usize }, // the prev_hash of a block is not the hash of the previous block
+    NonMonotonicWork { position: usize }, // the cumulative work wrapped around at this block
+    DuplicateTransaction { position: usize, tx_id: HashValue }, // a transaction ID appeared before
+    UtxoSetMismatch,                // the UTXO set differs from a fresh scan of the chain
+    CumulativeWorkMismatch,         // the cumulative work differs from a recomputation
+}
+
+impl Display for HealthIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HealthIssue::BlockIndexMismatch { position, index } => {
+                write!(f, "block at position {position} has index {index}")
+            }
+            HealthIssue::BrokenLink { position } => write!(
+                f,
+                "block at position {position} does not link to the previous block"
+            ),
+            HealthIssue::NonMonotonicWork { position } => {
+                write!(f, "cumulative work wrapped around at position {position}")
+            }
+            HealthIssue::DuplicateTransaction { position, tx_id } => write!(
+                f,
+                "transaction {tx_id} in block at position {position} appeared before"
+            ),
+            HealthIssue::UtxoSetMismatch => {
+                write!(f, "UTXO set does not match a fresh scan of the chain")
+            }
+            HealthIssue::CumulativeWorkMismatch => {
+                write!(f, "cumulative work does not match a recomputation")
+            }
+        }
+    }
+}
+
+/// the first failed check of a block, see `Blockchain::verify_block_detailed`
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlockValidationError {
+    /// a block other than the genesis block has no transaction, not even the coinbase transaction
+    EmptyBlock,
+    /// the serialized transactions exceed `MAX_BLOCK_SIZE`
+    TooLarge { size: usize },
+    /// a P2PKH signature is invalid
+    InvalidSignatures,
+    /// a transaction is invalid, or the coinbase transaction is misplaced
+    InvalidTransactions,
+    /// the merkle root doesn't match the transactions
+    MerkleRootMismatch {
+        merkle_root: HashValue,
+        expected: HashValue,
+    },
+    /// the difficulty isn't the one of the network
+    DifficultyMismatch { difficulty: u32, expected: u32 },
+    /// the hash is wrong or above the target
+    InvalidHash { hash: HashValue },
+    /// the previous hash is not the hash of the previous block
+    PrevHashMismatch { prev_hash: HashValue },
+    /// the timestamp is in the future or too early
+    InvalidTimestamp { timestamp: u64 },
+}
+
+impl Display for BlockValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BlockValidationError::EmptyBlock => write!(f, "no transaction, not even the coinbase"),
+            BlockValidationError::TooLarge { size } => {
+                write!(f, "size {size} exceeds {MAX_BLOCK_SIZE} bytes")
+            }
+            BlockValidationError::InvalidSignatures => write!(f, "invalid signatures"),
+            BlockValidationError::InvalidTransactions => write!(f, "invalid transactions"),
+            BlockValidationError::MerkleRootMismatch {
+                merkle_root,
+                expected,
+            } => write!(
+                f,
+                "merkle root {merkle_root} doesn't match the transactions, expected {expected}"
+            ),
+            BlockValidationError::DifficultyMismatch {
+                difficulty,
+                expected,
+            } => write!(
+                f,
+                "difficulty {difficulty:#010x}, expected {expected:#010x}"
+            ),
+            BlockValidationError::InvalidHash { hash } => {
+                write!(f, "hash {hash} is wrong or above the target")
+            }
+            BlockValidationError::PrevHashMismatch { prev_hash } => {
+                write!(f, "previous hash {prev_hash} doesn't match the chain")
+            }
+            BlockValidationError::InvalidTimestamp { timestamp } => {
+                write!(f, "timestamp {timestamp} is in the future or too early")
+            }
+        }
+    }
+}
+
+impl std::error::Error for BlockValidationError {}
+
+/// a summary of a blockchain, see `Blockchain::chain_metadata`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChainMetadata {
+    pub height: usize,           // the index of the latest block
+    pub total_work: u128,        // the cumulative proof-of-work, see `Blockchain::total_work`
+    pub genesis_hash: HashValue, // the hash of the genesis block, all zeros if the chain is empty
+    pub tip_hash: HashValue,     // the hash of the latest block, all zeros if the chain is empty
+    pub chain_id: u32,           // the network of the chain, see `GenesisConfig::chain_id`
+}
+
+/// a compact, machine-readable view of a blockchain for a block explorer,
+/// see `Blockchain::explorer_snapshot`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExplorerSnapshot {
+    pub metadata: ChainMetadata,   // the summary of the whole chain
+    pub blocks: Vec<BlockSummary>, // the summary of each block, from the genesis block to the tip
+}
+
+/// a summary of a block, see `Blockchain::explorer_snapshot`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockSummary {
+    pub height: usize,               // the index of the block
+    pub hash: HashValue,             // the hash of the block
+    pub timestamp: u64,              // the timestamp of the block, in seconds since the Unix Epoch
+    pub tx_count: usize,             // the number of transactions, 0 once the block is pruned
+    pub total_output_value: Decimal, // the sum of the outputs of all the transactions, coinbase included
+    pub difficulty: u32,             // the difficulty of the block, in nBits format
+}
+
+impl Default for Blockchain {
+    fn default() -> Self {
+        Self::new("Default Blockchain")
+    }
+}
+
+impl Display for Blockchain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "[Blockchain]:")?;
+        for block in self.blockchain.iter() {
+            write!(f, "{}\n\n\n", block)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::{Input, TransactionBuilder};
+    use crate::types::{nbits_to_target, DIFFICULTY_ONE_NBITS};
+    use crate::wallet::Wallet;
+    use log::{Level, LevelFilter, Log, Metadata, Record};
+    use secp256k1::SecretKey;
+    use std::sync::atomic::{self, AtomicUsize};
+    use std::sync::{Mutex, Once};
+    use std::thread::sleep;
+
+    /// a logger keeping the warnings in memory, shared by all the tests of this process
+    struct CapturingLogger {
+        warnings: Mutex<Vec<String>>,
+    }
+
+    impl Log for CapturingLogger {
+        fn enabled(&self, metadata: &Metadata) -> bool {
+            metadata.level() <= Level::Warn
+        }
+
+        fn log(&self, record: &Record) {
+            if self.enabled(record.metadata()) {
+                self.warnings
+                    .lock()
+                    .unwrap()
+                    .push(record.args().to_string());
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    static LOGGER: CapturingLogger = CapturingLogger {
+        warnings: Mutex::new(Vec::new()),
+    };
+
+    fn captured_warnings() -> Vec<String> {
+        static INIT: Once = Once::new();
+        INIT.call_once(|| {
+            log::set_logger(&LOGGER).unwrap();
+            log::set_max_level(LevelFilter::Warn);
+        });
+        LOGGER.warnings.lock().unwrap().clone()
+    }
+
+    /// mine `n` empty blocks at a trivial difficulty, paying the reward to nobody
+    fn mine_blocks(blockchain: &mut Blockchain, n: usize) {
+        for _ in 0..n {
+            blockchain.add_block(
+                blockchain
+                    .generate_new_block(
+                        vec![],
+                        "0.1v test".to_string(),
+                        SystemTime::now()
+                            .duration_since(UNIX_EPOCH)
+                            .unwrap()
+                            .as_secs(),
+                        0x207FFFFF_u32,
+                        Some(vec![]),
+                    )
+                    .unwrap(),
+            );
+        }
+    }
+
+    /// two chains of `RETARGET_INTERVAL + 1` blocks forking at the last block before the retarget:
+    /// the first one took the expected timespan, so its difficulty stays the same,
+    /// the second one was mined all at once, so its last block is mined at a harder difficulty
+    fn chains_forking_at_retarget() -> (Blockchain, Blockchain) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let expected_timespan = (RETARGET_INTERVAL as u64 - 1) * DEFAULT_TARGET_BLOCK_INTERVAL_SECS;
+        let genesis_time = now - expected_timespan;
+        let mut blockchain = Blockchain::new_with_genesis(&GenesisConfig {
+            version: "0.1v test".to_string(),
+            timestamp: genesis_time,
+            difficulty: DEFAULT_GENESIS_DIFFICULTY,
+            message: "hello world".to_string(),
+            chain_id: 0,
+            target_block_interval_secs: DEFAULT_TARGET_BLOCK_INTERVAL_SECS,
+            testnet: false,
+        });
+        let mine = |blockchain: &mut Blockchain, timestamp: u64| {
+            let difficulty = blockchain.next_difficulty();
+            blockchain.add_block(
+                blockchain
+                    .generate_new_block(
+                        vec![],
+                        "0.1v test".to_string(),
+                        timestamp,
+                        difficulty,
+                        Some(vec![]),
+                    )
+                    .unwrap(),
+            );
+        };
+        for index in 1..RETARGET_INTERVAL - 1 {
+            mine(&mut blockchain, genesis_time + index as u64);
+        }
+        let mut candidate = blockchain.clone();
+        mine(&mut blockchain, now);
+        mine(&mut candidate, genesis_time + RETARGET_INTERVAL as u64 - 1);
+        mine(&mut blockchain, now);
+        mine(&mut candidate, now);
+        (blockchain, candidate)
+    }
+
+    #[test]
+    fn test_new_blockchain() {
+        let blockchain = Blockchain::new("hello world");
+        println!("{:?}", blockchain);
+    }
+
+    #[test]
+    fn test_reward_algorithm() {
+        assert_eq!(Blockchain::reward_algorithm(0), dec!(50.0));
+        assert_eq!(
+            Blockchain::reward_algorithm(HALVING_INTERVAL - 1),
+            dec!(50.0)
+        );
+        assert_eq!(Blockchain::reward_algorithm(HALVING_INTERVAL), dec!(25.0));
+        assert_eq!(
+            Blockchain::reward_algorithm(HALVING_INTERVAL * 2),
+            dec!(12.5)
+        );
+        assert_eq!(
+            Blockchain::reward_algorithm(HALVING_INTERVAL * 32),
+            dec!(0.00000001)
+        );
+        assert_eq!(Blockchain::reward_algorithm(HALVING_INTERVAL * 33), dec!(0));
+        assert_eq!(Blockchain::reward_algorithm(1844674407370955161), dec!(0));
+        assert_eq!(Blockchain::reward_algorithm(usize::MAX), dec!(0));
+
+        // the total supply is capped
+        let total_supply: Decimal = (0..64)
+            .map(|halvings| {
+                Blockchain::reward_algorithm(halvings * HALVING_INTERVAL)
+                    * Decimal::from(HALVING_INTERVAL)
+            })
+            .sum();
+        assert_eq!(total_supply, dec!(20999999.9769));
+    }
+
+    #[test]
+    fn test_generate_new_block() {
+        let mut blockchain = Blockchain::new("hello world");
+        let latest_reward_fee = blockchain.expected_coinbase_reward(&[]).unwrap();
+        sleep(std::time::Duration::from_secs(1)); // simulate the time before creating a new block
+        let block = blockchain
+            .generate_new_block(
+                vec![(HashValue::new([0u8; 32]), latest_reward_fee)],
+                "0.1v test".to_string(),
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs(), // for the test, set the timestamp to 10 seconds later
+                0x1E123456_u32,
+                Some(vec![]),
+            )
+            .unwrap();
+        sleep(std::time::Duration::from_secs(1)); //simulate the time gap between mining and verifying process
+
+        let verification_res = blockchain.verify_block(&block, 0x1E123456_u32);
+
+        assert!(verification_res);
+
+        blockchain.add_block(block);
+        println!("{}", blockchain);
+    }
+
+    #[test]
+    fn test_expected_coinbase_reward() {
+        let mut blockchain = Blockchain::new("hello world");
+        let secret_key = SecretKey::from_slice(&[7u8; 32]).unwrap();
+        let wallet = Wallet::from_secret_key(secret_key);
+        let now = || {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+        };
+        blockchain.add_block(
+            blockchain
+                .generate_new_block(
+                    vec![(wallet.get_address(), dec!(25.0)); 2],
+                    "0.1v test".to_string(),
+                    now(),
+                    0x207FFFFF_u32,
+                    Some(vec![]),
+                )
+                .unwrap(),
+        );
+        mine_blocks(&mut blockchain, COINBASE_MATURITY);
+
+        // each transaction pays a fee of 1 rusty coin
+        let coinbase = blockchain.get_block(1).unwrap().data[0].clone();
+        let txs = (0..2)
+            .map(|output_index| {
+                let input = Input::new(coinbase.get_transaction_id(), 1, output_index, vec![]);
+                TransactionBuilder::new()
+                    .add_input(input, dec!(25.0))
+                    .add_output(Output::new(dec!(24.0), wallet.get_address().to_vec()).unwrap())
+                    .build_signed(|tx, input_index| {
+                        Some(Input::generate_unlock_script(
+                            tx,
+                            input_index,
+                            secret_key,
+                            wallet.get_public_key(),
+                        ))
+                    })
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        let expected_reward = blockchain.expected_coinbase_reward(&txs).unwrap();
+        assert_eq!(expected_reward, dec!(50.0) + dec!(2.0) * dec!(1.03));
+        let block = blockchain
+            .generate_new_block(
+                vec![(wallet.get_address(), expected_reward)],
+                "0.1v test".to_string(),
+                now(),
+                0x207FFFFF_u32,
+                Some(txs.clone()),
+            )
+            .unwrap();
+        assert_eq!(block.data.len(), 3);
+        assert!(blockchain.verify_block(&block, 0x207FFFFF_u32));
+
+        // claiming more than the expected reward is rejected
+        let mut data = block.data.clone();
+        data[0] = Blockchain::create_coinbase_transaction(
+            vec![(wallet.get_address(), expected_reward + dec!(0.00000001))],
+            blockchain.len(),
+            0,
+        )
+        .unwrap();
+        assert!(!blockchain.verify_transactions(&data, blockchain.len(), now()));
+    }
+
+    #[test]
+    fn test_coinbase_structure() {
+        let now = || {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+        };
+        let mut blockchain = Blockchain::new("hello world");
+        let mut wallet = Wallet::new();
+        blockchain.add_block(
+            blockchain
+                .generate_new_block(
+                    vec![(wallet.get_address(), dec!(50.0))],
+                    "0.1v test".to_string(),
+                    now(),
+                    0x207FFFFF_u32,
+                    Some(vec![]),
+                )
+                .unwrap(),
+        );
+        mine_blocks(&mut blockchain, COINBASE_MATURITY);
+        wallet.update_unspent_tx_outputs(blockchain.get_utxos_for(&wallet.get_address(), 0));
+        let tx = wallet
+            .send(vec![(dec!(20.0), Wallet::new().get_address())])
+            .unwrap();
+        let coinbase =
+            Blockchain::create_coinbase_transaction(vec![], blockchain.len(), 0).unwrap();
+        assert!(blockchain.verify_transactions(
+            &[coinbase.clone(), tx.clone()],
+            blockchain.len(),
+            now()
+        ));
+
+        // a second coinbase transaction, even one claiming nothing
+        assert!(!blockchain.verify_transactions(
+            &[coinbase.clone(), tx.clone(), coinbase.clone()],
+            blockchain.len(),
+            now()
+        ));
+        // the first transaction has inputs
+        assert!(!blockchain.verify_transactions(
+            &[tx.clone(), coinbase.clone()],
+            blockchain.len(),
+            now()
+        ));
+        assert!(!blockchain.verify_transactions(&[tx], blockchain.len(), now()));
+        assert!(!blockchain.verify_transactions(&[], blockchain.len(), now()));
+    }
+
+    #[test]
+    fn test_generate_new_block_rejects_invalid_receivers() {
+        let blockchain = Blockchain::new("hello world");
+        let address = Wallet::new().get_address();
+        let generate = |receivers: Vec<(HashValue, Decimal)>| {
+            blockchain.generate_new_block(
+                receivers,
+                "0.1v test".to_string(),
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs(),
+                0x207FFFFF_u32,
+                Some(vec![]),
+            )
+        };
+
+        // more than the reward of the block, even split across receivers
+        assert!(matches!(
+            generate(vec![(address, dec!(50.00000001))]),
+            Err(RustyCoinError::InvalidOutputAmount)
+        ));
+        assert!(matches!(
+            generate(vec![(address, dec!(30.0)), (address, dec!(30.0))]),
+            Err(RustyCoinError::InvalidOutputAmount)
+        ));
+        assert!(matches!(
+            generate(vec![(address, dec!(-1.0))]),
+            Err(RustyCoinError::InvalidOutputAmount)
+        ));
+        assert!(generate(vec![(address, dec!(25.0)), (address, dec!(25.0))]).is_ok());
+    }
+
+    #[test]
+    fn test_resolve_conflicts() {
+        let mut blockchain = Blockchain::new("hello world");
+        let mut blockchain_copied = blockchain.clone();
+
+        let tx1 = Transaction::new(
+            vec![],
+            vec![Output::new(dec!(50.0), HashValue::new([0u8; 32]).to_vec()).unwrap()],
+            HashValue::new([0_u8; 32]),
+            dec!(0.0),
+            Some("test fake tx 1".as_bytes().to_vec()),
+        );
+
+        let tx2 = Transaction::new(
+            vec![],
+            vec![Output::new(dec!(50.0), HashValue::new([0u8; 32]).to_vec()).unwrap()],
+            HashValue::new([1_u8; 32]),
+            dec!(0.0),
+            None,
+        );
+
+        blockchain.add_block(
+            blockchain
+                .generate_new_block(
+                    vec![(HashValue::new([0u8; 32]), dec!(50.0))],
+                    "0.1v test".to_string(),
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs(),
+                    0x1E123456_u32,
+                    Some(vec![tx1]),
+                )
+                .unwrap(),
+        );
+
+        blockchain_copied.add_block(
+            blockchain_copied
+                .generate_new_block(
+                    vec![(HashValue::new([0u8; 32]), dec!(50.0))],
+                    "0.1v test".to_string(),
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs(),
+                    0x1E123456_u32,
+                    Some(vec![tx2]),
+                )
+                .unwrap(),
+        );
+
+        let before_resolve = blockchain.clone();
+
+        let res = blockchain.resolve_conflicts(&blockchain_copied.blockchain);
+        // [Blockchain]:
+        // Block[0]:
+        // 	version: 0.1v test
+        // 	timestamp: 1709725083
+        // 	prev_hash: 0x0000000000000000000000000000000000000000000000000000000000000000
+        // 	hash: 0xb3fa77116dffb01cd560a6d246fd6c82ebcfe27b18bed173986170c7ad2d2244
+        // 	merkle_root: 0xae058a070f5ce7e44d1d6a54f5011e69a6aead1993409e8a7d79ef53abbe5bf2
+        // 	difficulty: 0
+        // 	nonce: 0
+        // 	data: [
+        // 		Transaction ID: 0xae058a070f5ce7e44d1d6a54f5011e69a6aead1993409e8a7d79ef53abbe5bf2
+        // 		Transaction Fee: 0.0
+        // 		Inputs:
+        // 		Outputs:
+        // 		Additional Data: Some([104, 101, 108, 108, 111, 32, 119, 111, 114, 108, 100])
+        // 	]
+        //
+        //
+        //
+        // Block[1]:
+        // 	version: 0.1v test
+        // 	timestamp: 1709725083
+        // 	prev_hash: 0xb3fa77116dffb01cd560a6d246fd6c82ebcfe27b18bed173986170c7ad2d2244
+        // 	hash: 0x00000b5bb6e201e16fdb7a8a1d373da12496db001a3df865941acaa321b23525
+        // 	merkle_root: 0x8923412372d89abca3f2863712f46a2b488cbdf70aa535a2b9a1f8fae0bc756c
+        // 	difficulty: 504509526
+        // 	nonce: 925788
+        // 	data: [
+        // 		Transaction ID: 0xb0768ef4fb2d549a1aa450415481ea40862d460f038792e8e4c0af03a47b5a87
+        // 		Transaction Fee: 0.0
+        // 		Inputs:
+        // 		Outputs:
+        // 			Amount: 50.0
+        // 			Length of Locking Script: 32
+        // 			Locking Script: 0x0000000000000000000000000000000000000000000000000000000000000000
+        // 		Additional Data: None
+        // 		Transaction ID: 0x0000000000000000000000000000000000000000000000000000000000000000
+        // 		Transaction Fee: 0.0
+        // 		Inputs:
+        // 		Outputs:
+        // 			Amount: 50.0
+        // 			Length of Locking Script: 32
+        // 			Locking Script: 0x0000000000000000000000000000000000000000000000000000000000000000
+        // 		Additional Data: Some([116, 101, 115, 116, 32, 102, 97, 107, 101, 32, 116, 120, 32, 49])
+        // 	]
+
+        println!("{}", blockchain);
+
+        assert_eq!(before_resolve.blockchain, blockchain.blockchain);
+
+        assert!(!res);
+    }
+
+    #[test]
+    fn test_resolve_conflicts_with_invalid_chain() {
+        let mut blockchain = Blockchain::new("hello world");
+        mine_blocks(&mut blockchain, 1);
+        let mut candidate = blockchain.clone();
+        mine_blocks(&mut candidate, 2);
+
+        // the longer candidate chain is tampered with
+        let mut tampered_chain = candidate.blockchain.clone();
+        tampered_chain[2].hash = HashValue::new([0u8; 32]);
+
+        let before_resolve = blockchain.clone();
+        assert!(!blockchain.resolve_conflicts(&tampered_chain));
+        assert_eq!(before_resolve.blockchain, blockchain.blockchain);
+        assert_eq!(
+            before_resolve.utxo_commitment(),
+            blockchain.utxo_commitment()
+        );
+
+        // the valid candidate chain replaces the local chain
+        assert!(blockchain.resolve_conflicts(&candidate.blockchain));
+        assert_eq!(blockchain.blockchain, candidate.blockchain);
+    }
+
+    #[test]
+    fn test_mint_to() {
+        let wallet = Wallet::new();
+        let config = GenesisConfig {
+            version: "0.1v test".to_string(),
+            timestamp: 1_700_000_000,
+            difficulty: DEFAULT_GENESIS_DIFFICULTY,
+            message: "faucet".to_string(),
+            chain_id: 0,
+            target_block_interval_secs: DEFAULT_TARGET_BLOCK_INTERVAL_SECS,
+            testnet: true,
+        };
+        let mut blockchain = Blockchain::new_with_genesis(&config);
+        assert!(blockchain.is_testnet());
+
+        blockchain
+            .mint_to(wallet.get_address(), dec!(25.0))
+            .unwrap();
+        assert_eq!(blockchain.len(), 2);
+        assert_eq!(
+            blockchain.get_balance(&wallet.get_address()).unwrap(),
+            dec!(25.0)
+        );
+        let utxos = blockchain.get_utxos_for(&wallet.get_address(), 0);
+        assert_eq!(utxos.len(), 1);
+
+        // the minted coins are spendable once they are mature
+        let tx = wallet
+            .transfer_credits(utxos, vec![(dec!(20.0), Wallet::new().get_address())], None)
+            .unwrap();
+        assert!(blockchain.add_to_pool(tx.clone()).is_err());
+        mine_blocks(&mut blockchain, COINBASE_MATURITY);
+        assert!(blockchain.add_to_pool(tx).is_ok());
+
+        // no more than the block reward
+        assert!(matches!(
+            blockchain.mint_to(wallet.get_address(), dec!(50.00000001)),
+            Err(RustyCoinError::InvalidOutputAmount)
+        ));
+
+        // only on a testnet
+        let mut mainnet = Blockchain::new_with_genesis(&GenesisConfig {
+            testnet: false,
+            ..config
+        });
+        assert!(matches!(
+            mainnet.mint_to(wallet.get_address(), dec!(25.0)),
+            Err(RustyCoinError::MintingDisabled)
+        ));
+        assert_eq!(mainnet.len(), 1);
+    }
+
+    #[test]
+    fn test_total_work() {
+        let mut blockchain = Blockchain::new("hello world");
+        // the genesis block carries the work of its difficulty, like any other block
+        assert_eq!(
+            blockchain.total_work(),
+            blockchain.get_block(0).unwrap().work()
+        );
+
+        mine_blocks(&mut blockchain, 3);
+        let manual_sum = blockchain.iter().map(Block::work).sum::<u128>();
+        assert!(manual_sum > 0);
+        assert_eq!(blockchain.total_work(), manual_sum);
+        assert_eq!(blockchain.chain_metadata().total_work, manual_sum);
+
+        // a harder chain of the same length replaces it, the work is recomputed
+        let (mut blockchain, candidate) = chains_forking_at_retarget();
+        assert!(candidate.total_work() > blockchain.total_work());
+        assert!(blockchain.resolve_conflicts(&candidate.blockchain));
+        assert_eq!(blockchain.total_work(), candidate.total_work());
+        assert_eq!(
+            blockchain.total_work(),
+            Blockchain::chain_work(&candidate.blockchain)
+        );
+    }
+
+    #[test]
+    fn test_resolve_conflicts_with_deep_fork() {
+        let mut blockchain = Blockchain::new("hello world");
+        let mut candidate = blockchain.clone();
+        mine_blocks(&mut blockchain, 3);
+        candidate.add_block(
+            candidate
+                .generate_new_block(
+                    vec![(Wallet::new().get_address(), dec!(50.0))],
+                    "0.1v test".to_string(),
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs(),
+                    0x207FFFFF_u32,
+                    Some(vec![]),
+                )
+                .unwrap(),
+        );
+        mine_blocks(&mut candidate, 4);
+
+        // the longer candidate chain forks 3 blocks behind the tip
+        blockchain.set_max_reorg_depth(2);
+        let before_resolve = blockchain.clone();
+        assert!(!blockchain.resolve_conflicts(&candidate.blockchain));
+        assert_eq!(before_resolve, blockchain);
+
+        blockchain.set_max_reorg_depth(3);
+        assert!(blockchain.resolve_conflicts(&candidate.blockchain));
+        assert_eq!(blockchain.blockchain, candidate.blockchain);
+    }
+
+    #[test]
+    fn test_verify_chain_custom_interval() {
+        // a chain of 1 second blocks, right on target across the first retarget
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let genesis_time = now - RETARGET_INTERVAL as u64;
+        let mut blockchain = Blockchain::new_with_genesis(&GenesisConfig {
+            version: "0.1v test".to_string(),
+            timestamp: genesis_time,
+            difficulty: DEFAULT_GENESIS_DIFFICULTY,
+            message: "hello world".to_string(),
+            chain_id: 0,
+            target_block_interval_secs: 1,
+            testnet: false,
+        });
+        let mut candidate = blockchain.clone();
+        for index in 1..=RETARGET_INTERVAL {
+            let difficulty = candidate.next_difficulty();
+            candidate.add_block(
+                candidate
+                    .generate_new_block(
+                        vec![],
+                        "0.1v test".to_string(),
+                        genesis_time + index as u64,
+                        difficulty,
+                        Some(vec![]),
+                    )
+                    .unwrap(),
+            );
+        }
+
+        // at the default interval the blocks came too fast, the block after the retarget is too easy
+        assert!(candidate.verify_candidate_chain(&candidate.blockchain));
+        assert!(!Blockchain::verify_chain(&candidate.blockchain));
+
+        // the chain is verified under the interval of the local chain
+        assert!(blockchain.resolve_conflicts(&candidate.blockchain));
+        assert_eq!(blockchain.blockchain, candidate.blockchain);
+        let json = serde_json::to_string(&blockchain).unwrap();
+        assert_eq!(Blockchain::try_from_json(&json).unwrap(), blockchain);
+    }
+
+    #[test]
+    fn test_resolve_conflicts_by_chain_work() {
+        let (mut blockchain, candidate) = chains_forking_at_retarget();
+
+        // the candidate chain has a smaller sum of nBits, but more work
+        let n_bits_sum =
+            |chain: &Blockchain| chain.iter().map(|b| b.difficulty as u64).sum::<u64>();
+        assert!(n_bits_sum(&candidate) < n_bits_sum(&blockchain));
+        assert!(
+            Blockchain::chain_work(&candidate.blockchain)
+                > Blockchain::chain_work(&blockchain.blockchain)
+        );
+
+        assert!(blockchain.resolve_conflicts(&candidate.blockchain));
+        assert_eq!(blockchain.blockchain, candidate.blockchain);
+    }
+
+    #[test]
+    fn test_resolve_conflicts_recycles_transactions() {
+        let wallet = Wallet::new();
+        let mut blockchain = chain_with_mature_rewards(&wallet, 3);
+        let utxos = blockchain.get_utxos_for(&wallet.get_address(), 0);
+        let receiver = Wallet::new().get_address();
+        let pay = |utxo: &UTXO, amount: Decimal| {
+            wallet
+                .transfer_credits(vec![utxo.clone()], vec![(amount, receiver)], None)
+                .unwrap()
+        };
+        let add_block_with = |blockchain: &mut Blockchain, txs: Vec<Transaction>| {
+            let block = blockchain
+                .generate_new_block(
+                    vec![(wallet.get_address(), dec!(50.0))],
+                    "0.1v test".to_string(),
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs(),
+                    0x207FFFFF_u32,
+                    Some(txs),
+                )
+                .unwrap();
+            blockchain.add_block(block);
+        };
+
+        // the local chain confirms `abandoned_tx`, the longer candidate chain confirms `confirmed_tx`
+        let mut candidate = blockchain.clone();
+        let abandoned_tx = pay(&utxos[0], dec!(49.9));
+        add_block_with(&mut blockchain, vec![abandoned_tx.clone()]);
+        let confirmed_tx = pay(&utxos[1], dec!(49.9));
+        add_block_with(&mut candidate, vec![confirmed_tx.clone()]);
+        mine_blocks(&mut candidate, 1);
+
+        // the pool holds a transaction conflicting with `confirmed_tx`, and an unrelated one
+        let conflicting_tx = pay(&utxos[1], dec!(49.8));
+        let unrelated_tx = pay(&utxos[2], dec!(49.9));
+        blockchain.add_to_pool(conflicting_tx).unwrap();
+        blockchain.add_to_pool(unrelated_tx.clone()).unwrap();
+
+        // the abandoned transaction returns to the pool, the conflicting one is dropped
+        let local_chain = blockchain.blockchain.clone();
+        assert!(blockchain.resolve_conflicts(&candidate.blockchain));
+        assert_eq!(blockchain.get_tx_pool(), &[abandoned_tx, unrelated_tx]);
+        assert_eq!(blockchain.pool_entry_times.len(), 2);
+
+        // the transactions of a rejected candidate chain go through the same checks, no duplicate is pooled
+        assert!(!blockchain.resolve_conflicts(&local_chain));
+        assert_eq!(blockchain.get_tx_pool().len(), 2);
+    }
+
+    #[test]
+    fn test_utxo_set_tracking() {
+        let mut blockchain = Blockchain::new("hello world");
+        let wallet1 = Wallet::new();
+        let wallet2 = Wallet::new();
+        let now = || {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+        };
+
+        // mine one block to each wallet
+        blockchain.add_block(
+            blockchain
+                .generate_new_block(
+                    vec![(wallet1.get_address(), dec!(50.0))],
+                    "0.1v test".to_string(),
+                    now(),
+                    0x207FFFFF_u32,
+                    Some(vec![]),
+                )
+                .unwrap(),
+        );
+        blockchain.add_block(
+            blockchain
+                .generate_new_block(
+                    vec![(wallet2.get_address(), dec!(30.0))],
+                    "0.1v test".to_string(),
+                    now(),
+                    0x207FFFFF_u32,
+                    Some(vec![]),
+                )
+                .unwrap(),
+        );
+
+        assert_eq!(
+            blockchain.get_balance(&wallet1.get_address()).unwrap(),
+            dec!(50.0)
+        );
+        assert_eq!(
+            blockchain.get_balance(&wallet2.get_address()).unwrap(),
+            dec!(30.0)
+        );
+
+        // wallet1 pays 20 to wallet2, 29 back to itself, 1 as transaction fee
+        let utxos = blockchain.get_utxos_for(&wallet1.get_address(), 0);
+        assert_eq!(utxos.len(), 1);
+        let tx = wallet1
+            .transfer_credits(
+                utxos,
+                vec![
+                    (dec!(20.0), wallet2.get_address()),
+                    (dec!(29.0), wallet1.get_address()),
+                ],
+                None,
+            )
+            .unwrap();
+        blockchain.add_block(
+            blockchain
+                .generate_new_block(
+                    vec![(HashValue::new([0u8; 32]), dec!(1.0))],
+                    "0.1v test".to_string(),
+                    now(),
+                    0x207FFFFF_u32,
+                    Some(vec![tx.clone()]),
+                )
+                .unwrap(),
+        );
+
+        assert_eq!(
+            blockchain.get_balance(&wallet1.get_address()).unwrap(),
+            dec!(29.0)
+        );
+        assert_eq!(
+            blockchain.get_balance(&wallet2.get_address()).unwrap(),
+            dec!(50.0)
+        );
+
+        let wallet1_utxos = blockchain.get_utxos_for(&wallet1.get_address(), 0);
+        assert_eq!(wallet1_utxos.len(), 1);
+        assert_eq!(wallet1_utxos[0].prev_tx_hash, tx.get_transaction_id());
+        assert_eq!(wallet1_utxos[0].prev_block_index, 3);
+        assert_eq!(wallet1_utxos[0].prev_output_index, 1);
+
+        let wallet2_utxos = blockchain.get_utxos_for(&wallet2.get_address(), 0);
+        assert_eq!(wallet2_utxos.len(), 2);
+        assert!(wallet2_utxos.iter().any(
+            |utxo| utxo.prev_tx_hash == tx.get_transaction_id() && utxo.prev_output_index == 0
+        ));
+        assert!(wallet2_utxos.iter().any(|utxo| utxo.prev_block_index == 2));
+    }
+
+    #[test]
+    fn test_deterministic_chain() {
+        let chain1 = Blockchain::deterministic_chain(5, 42);
+        let chain2 = Blockchain::deterministic_chain(5, 42);
+        let chain3 = Blockchain::deterministic_chain(5, 43);
+
+        assert_eq!(chain1.blockchain.len(), 6);
+        assert_eq!(
+            serde_json::to_vec(&chain1).unwrap(),
+            serde_json::to_vec(&chain2).unwrap()
+        );
+        assert_ne!(
+            serde_json::to_vec(&chain1).unwrap(),
+            serde_json::to_vec(&chain3).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_verify_block_with_signed_transaction() {
+        let mut blockchain = Blockchain::new("hello world");
+        let wallet1 = Wallet::new();
+        let wallet2 = Wallet::new();
+        let now = || {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+        };
+
+        blockchain.add_block(
+            blockchain
+                .generate_new_block(
+                    vec![(wallet1.get_address(), dec!(50.0))],
+                    "0.1v test".to_string(),
+                    now(),
+                    0x207FFFFF_u32,
+                    Some(vec![]),
+                )
+                .unwrap(),
+        );
+        mine_blocks(&mut blockchain, COINBASE_MATURITY - 1);
+
+        let tx = wallet1
+            .transfer_credits(
+                blockchain.get_utxos_for(&wallet1.get_address(), 0),
+                vec![(dec!(50.0), wallet2.get_address())],
+                None,
+            )
+            .unwrap();
+        let block = blockchain
+            .generate_new_block(
+                vec![(wallet1.get_address(), dec!(1.0))],
+                "0.1v test".to_string(),
+                now(),
+                0x207FFFFF_u32,
+                Some(vec![tx.clone()]),
+            )
+            .unwrap();
+        assert!(blockchain.verify_block(&block, 0x207FFFFF_u32));
+
+        // redirect the output to a third party, keeping the original unlocking script
+        let mut tampered_tx = Transaction::new(
+            tx.get_inputs().clone(),
+            vec![Output::new(dec!(50.0), HashValue::new([1u8; 32]).to_vec()).unwrap()],
+            HashValue::new([0u8; 32]),
+            dec!(0.0),
+            None,
+        );
+        tampered_tx.update_digest();
+        let block = blockchain
+            .generate_new_block(
+                vec![(wallet1.get_address(), dec!(1.0))],
+                "0.1v test".to_string(),
+                now(),
+                0x207FFFFF_u32,
+                Some(vec![tampered_tx]),
+            )
+            .unwrap();
+        assert!(!blockchain.verify_block(&block, 0x207FFFFF_u32));
+    }
+
+    #[test]
+    fn test_self_check() {
+        // the work of a healthy chain never wraps around, however long it grows
+        let mut blockchain = Blockchain::new("hello world");
+        mine_blocks(&mut blockchain, 20);
+        assert!(blockchain.self_check().is_empty());
+
+        let mut blockchain = Blockchain::deterministic_chain(3, 7);
+        assert!(blockchain.self_check().is_empty());
+
+        // break the link between block 1 and block 2, and corrupt the UTXO set
+        blockchain.blockchain[2].prev_hash = HashValue::new([0u8; 32]);
+        blockchain.utxo_set = UtxoSet::new();
+        blockchain.blockchain[3].index = 4;
+
+        let issues = blockchain.self_check();
+        assert_eq!(
+            issues,
+            vec![
+                HealthIssue::BrokenLink { position: 2 },
+                HealthIssue::BlockIndexMismatch {
+                    position: 3,
+                    index: 4
+                },
+                HealthIssue::UtxoSetMismatch,
+            ]
+        );
+
+        // the cumulative work is recomputed
+        let mut blockchain = Blockchain::deterministic_chain(3, 7);
+        blockchain.cumulative_work[2] += 1;
+        assert_eq!(
+            blockchain.self_check(),
+            vec![HealthIssue::CumulativeWorkMismatch]
+        );
+    }
+
+    #[test]
+    fn test_utxo_commitment() {
+        let mut blockchain = Blockchain::deterministic_chain(3, 11);
+        let blockchain_copied = Blockchain::deterministic_chain(3, 11);
+        assert_eq!(
+            blockchain.utxo_commitment(),
+            blockchain_copied.utxo_commitment()
+        );
+
+        // spend the reward of the last block
+        let spender = Wallet::new();
+        let coinbase = blockchain.get_last_block().unwrap().data[0].clone();
+        let block = blockchain
+            .generate_new_block(
+                vec![(spender.get_address(), dec!(1.0))],
+                "0.1v test".to_string(),
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs(),
+                0x207FFFFF_u32,
+                Some(vec![Transaction::new(
+                    vec![Input::new(coinbase.get_transaction_id(), 3, 0, vec![])],
+                    vec![],
+                    HashValue::new([0u8; 32]),
+                    dec!(0.0),
+                    None,
+                )]),
+            )
+            .unwrap();
+        blockchain.add_block(block);
+
+        assert_ne!(
+            blockchain.utxo_commitment(),
+            blockchain_copied.utxo_commitment()
+        );
+        assert_eq!(
+            blockchain.utxo_commitment(),
+            UtxoSet::from_blocks(&blockchain.blockchain).commitment()
+        );
+    }
+
+    #[test]
+    fn test_save_and_load_from_a_file() {
+        const FILE_PATH: &str = "./test_blockchain.rscchain";
+        let mut blockchain = Blockchain::deterministic_chain(COINBASE_MATURITY + 1, 5);
+        let spending_tx = blockchain.get_last_block().unwrap().data[1].clone();
+        blockchain.tx_pool.push(spending_tx);
+
+        blockchain.save_to_file(FILE_PATH).unwrap();
+        let blockchain_loaded = Blockchain::load_from_file(FILE_PATH).unwrap();
+        // delete the file after testing
+        std::fs::remove_file(FILE_PATH).expect("Delete Fail: No such file");
+
+        assert_eq!(blockchain, blockchain_loaded);
+    }
+
+    #[test]
+    fn test_transaction_pool() {
+        let mut blockchain = Blockchain::new("hello world");
+        let wallet1 = Wallet::new();
+        let wallet2 = Wallet::new();
+        blockchain.add_block(
+            blockchain
+                .generate_new_block(
+                    vec![(wallet1.get_address(), dec!(50.0))],
+                    "0.1v test".to_string(),
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs(),
+                    0x207FFFFF_u32,
+                    Some(vec![]),
+                )
+                .unwrap(),
+        );
+        mine_blocks(&mut blockchain, COINBASE_MATURITY - 1);
+
+        let utxos = || blockchain.get_utxos_for(&wallet1.get_address(), 0);
+        let tx = wallet1
+            .transfer_credits(utxos(), vec![(dec!(40.0), wallet2.get_address())], None)
+            .unwrap();
+        let double_spend_tx = wallet1
+            .transfer_credits(utxos(), vec![(dec!(45.0), wallet2.get_address())], None)
+            .unwrap();
+        let coinbase = blockchain.get_last_block().unwrap().data[0].clone();
+
+        assert!(blockchain.add_to_pool(tx.clone()).is_ok());
+        assert!(matches!(
+            blockchain.add_to_pool(tx.clone()),
+            Err(RustyCoinError::DuplicateTransaction)
+        ));
+        assert!(matches!(
+            blockchain.add_to_pool(double_spend_tx),
+            Err(RustyCoinError::DoubleSpend)
+        ));
+        assert!(matches!(
+            blockchain.add_to_pool(coinbase),
+            Err(RustyCoinError::InvalidTransaction)
+        ));
+        assert_eq!(blockchain.get_tx_pool().len(), 1);
+
+        // the pool is cleaned up once the transaction is packed
+        blockchain.add_block(
+            blockchain
+                .generate_new_block(
+                    vec![(wallet1.get_address(), dec!(1.0))],
+                    "0.1v test".to_string(),
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs(),
+                    0x207FFFFF_u32,
+                    Some(vec![tx.clone()]),
+                )
+                .unwrap(),
+        );
+        assert!(blockchain.get_tx_pool().is_empty());
+
+        // the output is now spent on chain, spending it again is invalid
+        assert!(matches!(
+            blockchain.add_to_pool(tx),
+            Err(RustyCoinError::InvalidTransaction)
+        ));
+    }
+
+    #[test]
+    fn test_pool_rejects_nonexistent_block_index() {
+        let wallet = Wallet::new();
+        let mut blockchain = chain_with_mature_rewards(&wallet, 1);
+        let utxo = blockchain.get_utxos_for(&wallet.get_address(), 0).remove(0);
+
+        // the output claims to be in a block that doesn't exist yet, or never will
+        for prev_block_index in [blockchain.len(), usize::MAX] {
+            let forged_utxo = UTXO {
+                prev_block_index,
+                ..utxo.clone()
+            };
+            let tx = wallet
+                .transfer_credits(
+                    vec![forged_utxo],
+                    vec![(dec!(40.0), Wallet::new().get_address())],
+                    None,
+                )
+                .unwrap();
+            assert!(matches!(
+                blockchain.add_to_pool(tx),
+                Err(RustyCoinError::InvalidBlockIndex)
+            ));
+        }
+        assert!(blockchain.get_tx_pool().is_empty());
+
+        let tx = wallet
+            .transfer_credits(
+                vec![utxo],
+                vec![(dec!(40.0), Wallet::new().get_address())],
+                None,
+            )
+            .unwrap();
+        assert!(blockchain.add_to_pool(tx).is_ok());
+    }
+
+    #[test]
+    fn test_replace_by_fee() {
+        let mut blockchain = Blockchain::new("hello world");
+        let wallet1 = Wallet::new();
+        let wallet2 = Wallet::new();
+        for _ in 0..2 {
+            blockchain.add_block(
+                blockchain
+                    .generate_new_block(
+                        vec![(wallet1.get_address(), dec!(50.0))],
+                        "0.1v test".to_string(),
+                        SystemTime::now()
+                            .duration_since(UNIX_EPOCH)
+                            .unwrap()
+                            .as_secs(),
+                        0x207FFFFF_u32,
+                        Some(vec![]),
+                    )
+                    .unwrap(),
+            );
+        }
+        mine_blocks(&mut blockchain, COINBASE_MATURITY);
+
+        let utxos = blockchain.get_utxos_for(&wallet1.get_address(), 0);
+        let pay = |utxo: &UTXO, amount: Decimal| {
+            wallet1
+                .transfer_credits(
+                    vec![utxo.clone()],
+                    vec![(amount, wallet2.get_address())],
+                    None,
+                )
+                .unwrap()
+        };
+        let tx = pay(&utxos[0], dec!(40.0));
+        blockchain.add_to_pool(tx.clone()).unwrap();
+
+        // the replacement must pay a strictly higher fee
+        assert!(matches!(
+            blockchain.replace_by_fee(pay(&utxos[0], dec!(45.0))),
+            Err(RustyCoinError::InsufficientFee)
+        ));
+        let same_fee_tx = wallet1
+            .transfer_credits(
+                vec![utxos[0].clone()],
+                vec![(dec!(40.0), wallet1.get_address())],
+                None,
+            )
+            .unwrap();
+        assert!(matches!(
+            blockchain.replace_by_fee(same_fee_tx),
+            Err(RustyCoinError::InsufficientFee)
+        ));
+        assert!(matches!(
+            blockchain.replace_by_fee(tx.clone()),
+            Err(RustyCoinError::DuplicateTransaction)
+        ));
+        assert_eq!(blockchain.get_tx_pool(), std::slice::from_ref(&tx));
+
+        // nothing to replace
+        assert!(matches!(
+            blockchain.replace_by_fee(pay(&utxos[1], dec!(30.0))),
+            Err(RustyCoinError::NoConflictingTransaction)
+        ));
+
+        // bump the fee from 10 to 20
+        let bumped_tx = pay(&utxos[0], dec!(30.0));
+        blockchain.replace_by_fee(bumped_tx.clone()).unwrap();
+        assert_eq!(blockchain.get_tx_pool(), &[bumped_tx]);
+    }
+
+    /// a chain whose first `n` blocks after the genesis block pay 50 to `wallet`, all mature
+    fn chain_with_mature_rewards(wallet: &Wallet, n: usize) -> Blockchain {
+        let mut blockchain = Blockchain::new("hello world");
+        for _ in 0..n {
+            blockchain.add_block(
+                blockchain
+                    .generate_new_block(
+                        vec![(wallet.get_address(), dec!(50.0))],
+                        "0.1v test".to_string(),
+                        SystemTime::now()
+                            .duration_since(UNIX_EPOCH)
+                            .unwrap()
+                            .as_secs(),
+                        0x207FFFFF_u32,
+                        Some(vec![]),
+                    )
+                    .unwrap(),
+            );
+        }
+        mine_blocks(&mut blockchain, COINBASE_MATURITY);
+        blockchain
+    }
+
+    #[test]
+    fn test_spendable_utxos() {
+        let wallet = Wallet::new();
+        let mut blockchain = Blockchain::new("hello world");
+        let reward_block = |blockchain: &Blockchain, amount: Decimal| {
+            blockchain
+                .generate_new_block(
+                    vec![(wallet.get_address(), amount)],
+                    "0.1v test".to_string(),
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs(),
+                    0x207FFFFF_u32,
+                    Some(vec![]),
+                )
+                .unwrap()
+        };
+        blockchain.add_block(reward_block(&blockchain, dec!(50.0)));
+        blockchain.add_block(reward_block(&blockchain, dec!(20.0)));
+        mine_blocks(&mut blockchain, COINBASE_MATURITY - 2);
+        // the reward of the latest block is immature
+        blockchain.add_block(reward_block(&blockchain, dec!(30.0)));
+
+        let amounts = |min_confirmations: usize| {
+            blockchain
+                .get_utxos_for(&wallet.get_address(), min_confirmations)
+                .iter()
+                .map(UTXO::get_amount)
+                .collect::<Vec<Decimal>>()
+        };
+        assert_eq!(amounts(0), vec![dec!(20.0), dec!(30.0), dec!(50.0)]);
+        assert_eq!(amounts(1), vec![dec!(20.0), dec!(50.0)]);
+        assert_eq!(amounts(REGULAR_MATURITY), vec![dec!(20.0), dec!(50.0)]);
+        assert_eq!(amounts(COINBASE_MATURITY + 1), vec![dec!(50.0)]);
+
+        assert_eq!(
+            blockchain.get_balance(&wallet.get_address()).unwrap(),
+            dec!(100.0)
+        );
+        assert_eq!(
+            blockchain
+                .get_spendable_balance(&wallet.get_address())
+                .unwrap(),
+            dec!(70.0)
+        );
+    }
+
+    #[test]
+    fn test_pool_rejects_duplicate_inputs() {
+        let wallet = Wallet::new();
+        let mut blockchain = chain_with_mature_rewards(&wallet, 1);
+        let utxo = blockchain.get_utxos_for(&wallet.get_address(), 0).remove(0);
+
+        // the same 50 coins listed twice would cover 90 coins of outputs
+        let tx = wallet
+            .transfer_credits(
+                vec![utxo.clone(), utxo],
+                vec![(dec!(90.0), Wallet::new().get_address())],
+                None,
+            )
+            .unwrap();
+        assert!(matches!(
+            blockchain.add_to_pool(tx.clone()),
+            Err(RustyCoinError::DuplicateInput)
+        ));
+        assert!(blockchain.get_tx_pool().is_empty());
+        assert!(matches!(
+            blockchain.replace_by_fee(tx),
+            Err(RustyCoinError::DuplicateInput)
+        ));
+    }
+
+    #[test]
+    fn test_min_relay_fee_rate() {
+        let wallet = Wallet::new();
+        let mut blockchain = chain_with_mature_rewards(&wallet, 2);
+        let utxos = blockchain.get_utxos_for(&wallet.get_address(), 0);
+        let receiver = Wallet::new().get_address();
+        let pay = |utxo: &UTXO, amount: Decimal| {
+            wallet
+                .transfer_credits(vec![utxo.clone()], vec![(amount, receiver)], None)
+                .unwrap()
+        };
+
+        // no fee at all is spam by default
+        let free_tx = pay(&utxos[0], dec!(50.0));
+        assert_eq!(free_tx.fee_rate(), dec!(0.0));
+        assert!(matches!(
+            blockchain.add_to_pool(free_tx.clone()),
+            Err(RustyCoinError::FeeRateTooLow)
+        ));
+
+        // a fee rate right at the minimum is enough
+        let tx = pay(&utxos[1], dec!(49.99));
+        blockchain.set_min_relay_fee_rate(tx.fee_rate());
+        assert_eq!(blockchain.get_min_relay_fee_rate(), tx.fee_rate());
+        let cheaper_tx = pay(&utxos[0], dec!(49.995));
+        assert!(cheaper_tx.fee_rate() < tx.fee_rate());
+        assert!(matches!(
+            blockchain.add_to_pool(cheaper_tx),
+            Err(RustyCoinError::FeeRateTooLow)
+        ));
+        assert!(blockchain.add_to_pool(tx.clone()).is_ok());
+        assert_eq!(blockchain.get_tx_pool(), std::slice::from_ref(&tx));
+
+        // a replacement must pay the minimum fee rate too, whatever fee it pays
+        blockchain.set_min_relay_fee_rate(pay(&utxos[1], dec!(49.9)).fee_rate());
+        let replacement = pay(&utxos[1], dec!(49.95));
+        assert!(replacement.get_transaction_fee() > tx.get_transaction_fee());
+        assert!(matches!(
+            blockchain.replace_by_fee(replacement),
+            Err(RustyCoinError::FeeRateTooLow)
+        ));
+        assert_eq!(blockchain.get_tx_pool(), &[tx]);
+
+        // the minimum can be lifted
+        blockchain.set_min_relay_fee_rate(dec!(0.0));
+        assert!(blockchain.add_to_pool(free_tx).is_ok());
+    }
+
+    #[test]
+    fn test_evict_expired() {
+        let wallet = Wallet::new();
+        let mut blockchain = chain_with_mature_rewards(&wallet, 2);
+        let utxos = blockchain.get_utxos_for(&wallet.get_address(), 0);
+        let txs = utxos
+            .iter()
+            .map(|utxo| {
+                wallet
+                    .transfer_credits(
+                        vec![utxo.clone()],
+                        vec![(dec!(40.0), Wallet::new().get_address())],
+                        None,
+                    )
+                    .unwrap()
+            })
+            .collect::<Vec<Transaction>>();
+        for tx in &txs {
+            blockchain.add_to_pool(tx.clone()).unwrap();
+        }
+
+        // nothing has waited for long yet
+        blockchain.evict_expired(60);
+        assert_eq!(blockchain.get_tx_pool(), txs.as_slice());
+
+        // the first transaction entered the pool 2 minutes ago
+        *blockchain
+            .pool_entry_times
+            .get_mut(&txs[0].get_transaction_id())
+            .unwrap() -= 120;
+        blockchain.evict_expired(60);
+        assert_eq!(blockchain.get_tx_pool(), &txs[1..]);
+        assert!(!blockchain
+            .pool_entry_times
+            .contains_key(&txs[0].get_transaction_id()));
+    }
+
+    #[test]
+    fn test_pool_size_cap() {
+        let wallet = Wallet::new();
+        let mut blockchain = chain_with_mature_rewards(&wallet, 4);
+        let utxos = blockchain.get_utxos_for(&wallet.get_address(), 0);
+        let pay = |utxo: &UTXO, amount: Decimal| {
+            wallet
+                .transfer_credits(
+                    vec![utxo.clone()],
+                    vec![(amount, Wallet::new().get_address())],
+                    None,
+                )
+                .unwrap()
+        };
+        let low_fee_tx = pay(&utxos[0], dec!(45.0)); // fee 5
+        let high_fee_tx = pay(&utxos[1], dec!(35.0)); // fee 15
+        blockchain.add_to_pool(low_fee_tx).unwrap();
+        blockchain.add_to_pool(high_fee_tx.clone()).unwrap();
+        blockchain.set_max_pool_size(2);
+
+        // the full pool evicts the lowest fee rate for a higher one
+        let mid_fee_tx = pay(&utxos[2], dec!(40.0)); // fee 10
+        blockchain.add_to_pool(mid_fee_tx.clone()).unwrap();
+        assert_eq!(blockchain.get_tx_pool(), &[high_fee_tx.clone(), mid_fee_tx]);
+
+        // but rejects a transaction paying less than all the pooled ones
+        assert!(matches!(
+            blockchain.add_to_pool(pay(&utxos[3], dec!(49.0))),
+            Err(RustyCoinError::InsufficientFee)
+        ));
+        assert_eq!(blockchain.get_tx_pool().len(), 2);
+
+        // shrinking the pool keeps the highest fee rate
+        blockchain.set_max_pool_size(1);
+        assert_eq!(blockchain.get_tx_pool(), &[high_fee_tx]);
+        assert_eq!(blockchain.pool_entry_times.len(), 1);
+    }
+
+    #[test]
+    fn test_fee_policy() {
+        let wallet = Wallet::new();
+        let mut blockchain = chain_with_mature_rewards(&wallet, 1);
+        let utxos = blockchain.get_utxos_for(&wallet.get_address(), 0);
+        // pays a fee of 10 rusty coins
+        let tx = wallet
+            .transfer_credits(utxos, vec![(dec!(40.0), Wallet::new().get_address())], None)
+            .unwrap();
+        let txs = vec![tx];
+        let block_reward = Blockchain::reward_algorithm(blockchain.len());
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        // by default the fees are paid to the miner, inflated by 3%
+        assert_eq!(
+            blockchain.expected_coinbase_reward(&txs).unwrap(),
+            block_reward + dec!(10.3)
+        );
+
+        // all the fees are paid to the miner
+        blockchain.set_fee_policy(FeePolicy::burn(dec!(0.0)).unwrap());
+        assert_eq!(
+            blockchain.expected_coinbase_reward(&txs).unwrap(),
+            block_reward + dec!(10.0)
+        );
+
+        // half the fees are burned
+        blockchain.set_fee_policy(FeePolicy::burn(dec!(0.5)).unwrap());
+        let expected_reward = blockchain.expected_coinbase_reward(&txs).unwrap();
+        assert_eq!(expected_reward, block_reward + dec!(5.0));
+        let block = blockchain
+            .generate_new_block(
+                vec![(wallet.get_address(), expected_reward)],
+                "0.1v test".to_string(),
+                now,
+                0x207FFFFF_u32,
+                Some(txs.clone()),
+            )
+            .unwrap();
+        assert!(blockchain.verify_block(&block, 0x207FFFFF_u32));
+
+        // claiming the burned fees is rejected
+        assert!(matches!(
+            blockchain.generate_new_block(
+                vec![(wallet.get_address(), block_reward + dec!(10.0))],
+                "0.1v test".to_string(),
+                now,
+                0x207FFFFF_u32,
+                Some(txs.clone()),
+            ),
+            Err(RustyCoinError::InvalidOutputAmount)
+        ));
+        let mut data = block.data.clone();
+        data[0] = Blockchain::create_coinbase_transaction(
+            vec![(wallet.get_address(), expected_reward + dec!(0.00000001))],
+            blockchain.len(),
+            0,
+        )
+        .unwrap();
+        assert!(!blockchain.verify_transactions(&data, blockchain.len(), now));
+
+        // a candidate chain claiming the burned fees is rejected
+        let mut candidate = Blockchain::from_vec(&blockchain.blockchain);
+        let greedy_block = candidate
+            .generate_new_block(
+                vec![(wallet.get_address(), block_reward + dec!(10.3))],
+                "0.1v test".to_string(),
+                now,
+                0x207FFFFF_u32,
+                Some(txs.clone()),
+            )
+            .unwrap();
+        candidate.add_block(greedy_block);
+        assert!(Blockchain::verify_chain(&candidate.blockchain));
+        assert!(!blockchain.verify_candidate_chain(&candidate.blockchain));
+        assert!(!blockchain.resolve_conflicts(&candidate.blockchain));
+
+        assert!(FeePolicy::burn(dec!(1.5)).is_none());
+    }
+
+    #[test]
+    fn test_next_difficulty() {
+        // a chain of `RETARGET_INTERVAL` blocks at difficulty 1, `spacing` seconds apart
+        let chain_with_spacing = |spacing: u64| {
+            let blocks = (0..RETARGET_INTERVAL)
+                .map(|index| Block {
+                    version: "0.1v test".to_string(),
+                    index,
+                    data: vec![],
+                    timestamp: 1_700_000_000 + index as u64 * spacing,
+                    prev_hash: HashValue::new([0; 32]),
+                    hash: HashValue::new([0; 32]),
+                    merkle_root: HashValue::new([0; 32]),
+                    difficulty: DIFFICULTY_ONE_NBITS,
+                    nonce: 0,
+                    merkle_cache: MerkleCache::default(),
+                })
+                .collect::<Vec<Block>>();
+            let mut blockchain = Blockchain::from_vec(&blocks);
+            blockchain.set_target_block_interval_secs(1);
+            blockchain
+        };
+
+        // right on the 1 second target
+        let blockchain = chain_with_spacing(1);
+        assert_eq!(blockchain.next_difficulty(), DIFFICULTY_ONE_NBITS);
+
+        // twice as slow, the target doubles
+        let blockchain = chain_with_spacing(2);
+        assert_eq!(blockchain.next_difficulty(), 0x1D01FFFE);
+
+        // 10 times as slow, the target grows by at most 4 times
+        let mut blockchain = chain_with_spacing(10);
+        assert_eq!(blockchain.next_difficulty(), 0x1D03FFFC);
+        // at the default 10 seconds per block it is right on target
+        blockchain.set_target_block_interval_secs(DEFAULT_TARGET_BLOCK_INTERVAL_SECS);
+        assert_eq!(blockchain.next_difficulty(), DIFFICULTY_ONE_NBITS);
+
+        // all at once, the target shrinks by at most 4 times
+        let blockchain = chain_with_spacing(0);
+        let expected_timespan = RETARGET_INTERVAL as u64 - 1;
+        assert_eq!(
+            blockchain.next_difficulty(),
+            target_to_nbits(&scale_target(
+                &nbits_to_target(DIFFICULTY_ONE_NBITS),
+                expected_timespan / MAX_RETARGET_FACTOR,
+                expected_timespan
+            ))
+        );
+
+        // the difficulty is only adjusted every `RETARGET_INTERVAL` blocks
+        let mut blockchain = chain_with_spacing(2);
+        blockchain.blockchain.pop();
+        assert_eq!(blockchain.next_difficulty(), DIFFICULTY_ONE_NBITS);
+
+        // the timestamps may run ahead of the clock by a few target block intervals
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        assert!(blockchain.verify_timestamp(now + MAX_FUTURE_BLOCK_INTERVALS));
+        assert!(!blockchain.verify_timestamp(now + 60));
+
+        // the interval is part of the chain config
+        let blockchain = Blockchain::new_with_genesis(&GenesisConfig {
+            version: "0.1v test".to_string(),
+            timestamp: 1_700_000_000,
+            difficulty: DEFAULT_GENESIS_DIFFICULTY,
+            message: "testnet".to_string(),
+            chain_id: 1,
+            target_block_interval_secs: 1,
+            testnet: false,
+        });
+        assert_eq!(blockchain.get_target_block_interval_secs(), 1);
+    }
+
+    #[test]
+    fn test_select_transactions_for_block() {
+        let mut blockchain = Blockchain::new("hello world");
+        let wallet = Wallet::new();
+        let receiver = Wallet::new();
+        blockchain.add_block(
+            blockchain
+                .generate_new_block(
+                    vec![(wallet.get_address(), dec!(10.0)); 3],
+                    "0.1v test".to_string(),
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs(),
+                    0x207FFFFF_u32,
+                    Some(vec![]),
+                )
+                .unwrap(),
+        );
+        mine_blocks(&mut blockchain, COINBASE_MATURITY);
+
+        // spend each output in its own transaction, paying fees of 0.1, 1.0 and 0.5
+        let utxos = blockchain.get_utxos_for(&wallet.get_address(), 0);
+        assert_eq!(utxos.len(), 3);
+        let txs = utxos
+            .into_iter()
+            .zip([dec!(9.9), dec!(9.0), dec!(9.5)])
+            .map(|(utxo, amount)| {
+                wallet
+                    .transfer_credits(vec![utxo], vec![(amount, receiver.get_address())], None)
+                    .unwrap()
+            })
+            .collect::<Vec<Transaction>>();
+        for tx in &txs {
+            blockchain.add_to_pool(tx.clone()).unwrap();
+        }
+
+        let selected = blockchain.select_transactions_for_block(2);
+        assert_eq!(selected, vec![txs[1].clone(), txs[2].clone()]);
+        assert!(blockchain.select_transactions_for_block(0).is_empty());
+
+        // without supplied transactions, a new block packs the pool by fee rate
+        let block = blockchain
+            .generate_new_block(
+                vec![],
+                "0.1v test".to_string(),
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs(),
+                0x207FFFFF_u32,
+                None,
+            )
+            .unwrap();
+        assert_eq!(
+            block.data[1..],
+            [txs[1].clone(), txs[2].clone(), txs[0].clone()]
+        );
+        assert!(blockchain.verify_block(&block, 0x207FFFFF_u32));
+        blockchain.add_block(block);
+        assert!(blockchain.get_tx_pool().is_empty());
+    }
+
+    #[test]
+    fn test_lock_time() {
+        let mut blockchain = Blockchain::new("hello world");
+        let secret_key = SecretKey::from_slice(&[7u8; 32]).unwrap();
+        let wallet = Wallet::from_secret_key(secret_key);
+        let now = || {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+        };
+        blockchain.add_block(
+            blockchain
+                .generate_new_block(
+                    vec![(wallet.get_address(), dec!(50.0))],
+                    "0.1v test".to_string(),
+                    now(),
+                    0x207FFFFF_u32,
+                    Some(vec![]),
+                )
+                .unwrap(),
+        );
+        mine_blocks(&mut blockchain, COINBASE_MATURITY);
+
+        // locked until block `lock_time`, it can be packed into the block after it
+        let lock_time = blockchain.len() as u64 + 1;
+        let coinbase = blockchain.get_block(1).unwrap().data[0].clone();
+        let mut input = Input::new(coinbase.get_transaction_id(), 1, 0, vec![]);
+        input.set_sequence(0);
+        let tx = TransactionBuilder::new()
+            .add_input(input, dec!(50.0))
+            .add_output(Output::new(dec!(49.0), wallet.get_address().to_vec()).unwrap())
+            .lock_time(lock_time)
+            .build_signed(|tx, input_index| {
+                Some(Input::generate_unlock_script(
+                    tx,
+                    input_index,
+                    secret_key,
+                    wallet.get_public_key(),
+                ))
+            })
+            .unwrap();
+
+        let block_data = |blockchain: &Blockchain| {
+            vec![
+                Blockchain::create_coinbase_transaction(vec![], blockchain.len(), 0).unwrap(),
+                tx.clone(),
+            ]
+        };
+        for _ in 0..2 {
+            assert!(!blockchain.verify_transactions(
+                &block_data(&blockchain),
+                blockchain.len(),
+                now()
+            ));
+            assert!(matches!(
+                blockchain.add_to_pool(tx.clone()),
+                Err(RustyCoinError::InvalidTransaction)
+            ));
+            mine_blocks(&mut blockchain, 1);
+        }
+
+        assert_eq!(blockchain.len() as u64, lock_time + 1);
+        assert!(blockchain.verify_transactions(&block_data(&blockchain), blockchain.len(), now()));
+        assert!(blockchain.add_to_pool(tx.clone()).is_ok());
+        let block = blockchain
+            .generate_new_block(vec![], "0.1v test".to_string(), now(), 0x207FFFFF_u32, None)
+            .unwrap();
+        assert_eq!(block.data[1], tx);
+        assert!(blockchain.verify_block(&block, 0x207FFFFF_u32));
+    }
+
+    #[test]
+    fn test_coinbase_maturity() {
+        let mut blockchain = Blockchain::new("hello world");
+        // the transactions pay no fee
+        blockchain.set_min_relay_fee_rate(dec!(0.0));
+        let wallet1 = Wallet::new();
+        let wallet2 = Wallet::new();
+        let now = || {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+        };
+
+        blockchain.add_block(
+            blockchain
+                .generate_new_block(
+                    vec![(wallet1.get_address(), dec!(50.0))],
+                    "0.1v test".to_string(),
+                    now(),
+                    0x207FFFFF_u32,
+                    Some(vec![]),
+                )
+                .unwrap(),
+        );
+        let tx = wallet1
+            .transfer_credits(
+                blockchain.get_utxos_for(&wallet1.get_address(), 0),
+                vec![(dec!(50.0), wallet2.get_address())],
+                None,
+            )
+            .unwrap();
+
+        // the coinbase output of block 1 can't be spent before block 1 + COINBASE_MATURITY
+        mine_blocks(&mut blockchain, COINBASE_MATURITY - 2);
+        let immature_block = blockchain
+            .generate_new_block(
+                vec![],
+                "0.1v test".to_string(),
+                now(),
+                0x207FFFFF_u32,
+                Some(vec![tx.clone()]),
+            )
+            .unwrap();
+        assert_eq!(immature_block.index, COINBASE_MATURITY);
+        assert!(!blockchain.verify_block(&immature_block, 0x207FFFFF_u32));
+        assert!(matches!(
+            blockchain.add_to_pool(tx.clone()),
+            Err(RustyCoinError::InvalidTransaction)
+        ));
+
+        mine_blocks(&mut blockchain, 1);
+        let mature_block = blockchain
+            .generate_new_block(
+                vec![],
+                "0.1v test".to_string(),
+                now(),
+                0x207FFFFF_u32,
+                Some(vec![tx.clone()]),
+            )
+            .unwrap();
+        assert_eq!(mature_block.index, COINBASE_MATURITY + 1);
+        assert!(blockchain.verify_block(&mature_block, 0x207FFFFF_u32));
+        assert!(blockchain.add_to_pool(tx.clone()).is_ok());
+        blockchain.add_block(mature_block);
+
+        // a regular output needs REGULAR_MATURITY confirmations
+        let tx = wallet2
+            .transfer_credits(
+                blockchain.get_utxos_for(&wallet2.get_address(), 0),
+                vec![(dec!(50.0), wallet1.get_address())],
+                None,
+            )
+            .unwrap();
+        mine_blocks(&mut blockchain, REGULAR_MATURITY - 2);
+        let immature_block = blockchain
+            .generate_new_block(
+                vec![],
+                "0.1v test".to_string(),
+                now(),
+                0x207FFFFF_u32,
+                Some(vec![tx.clone()]),
+            )
+            .unwrap();
+        assert!(!blockchain.verify_block(&immature_block, 0x207FFFFF_u32));
+
+        mine_blocks(&mut blockchain, 1);
+        let mature_block = blockchain
+            .generate_new_block(
+                vec![],
+                "0.1v test".to_string(),
+                now(),
+                0x207FFFFF_u32,
+                Some(vec![tx]),
+            )
+            .unwrap();
+        assert!(blockchain.verify_block(&mature_block, 0x207FFFFF_u32));
+    }
+
+    #[test]
+    fn test_coinbase_height_commitment() {
+        let mut blockchain = Blockchain::new("hello world");
+        let wallet = Wallet::new();
+        for _ in 0..2 {
+            blockchain.add_block(
+                blockchain
+                    .generate_new_block(
+                        vec![(wallet.get_address(), dec!(50.0))],
+                        "0.1v test".to_string(),
+                        SystemTime::now()
+                            .duration_since(UNIX_EPOCH)
+                            .unwrap()
+                            .as_secs(),
+                        0x207FFFFF_u32,
+                        Some(vec![]),
+                    )
+                    .unwrap(),
+            );
+        }
+
+        // the same reward to the same address, but distinct coinbase transactions
+        let coinbase1 = &blockchain.get_block(1).unwrap().data[0];
+        let coinbase2 = &blockchain.get_block(2).unwrap().data[0];
+        assert_eq!(coinbase1.get_outputs(), coinbase2.get_outputs());
+        assert_ne!(
+            coinbase1.get_transaction_id(),
+            coinbase2.get_transaction_id()
+        );
+        assert_eq!(
+            blockchain.get_balance(&wallet.get_address()).unwrap(),
+            dec!(100.0)
+        );
+
+        // the committed height must match the block index
+        let block = blockchain
+            .generate_new_block(
+                vec![(wallet.get_address(), dec!(50.0))],
+                "0.1v test".to_string(),
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs(),
+                0x207FFFFF_u32,
+                Some(vec![]),
+            )
+            .unwrap();
+        assert!(blockchain.verify_transactions(&block.data, 3, block.timestamp));
+        assert!(!blockchain.verify_transactions(&block.data, 4, block.timestamp));
+    }
+
+    #[test]
+    fn test_iter() {
+        let blockchain = Blockchain::deterministic_chain(5, 3);
+        assert_eq!(blockchain.len(), 6);
+        assert!(!blockchain.is_empty());
+        assert_eq!(blockchain.iter().count(), blockchain.len());
+
+        let difficulty_sum: u64 = blockchain.iter().map(|block| block.difficulty as u64).sum();
+        let manual_sum = blockchain
+            .blockchain
+            .iter()
+            .fold(0u64, |sum, block| sum + block.difficulty as u64);
+        assert_eq!(difficulty_sum, manual_sum);
+        assert_eq!(
+            blockchain
+                .iter()
+                .map(|block| block.index)
+                .collect::<Vec<_>>(),
+            (0..6).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_verify_chain() {
+        let mut blockchain = Blockchain::new("hello world");
+        mine_blocks(&mut blockchain, 1);
+        assert_eq!(blockchain.len(), 2);
+        assert!(Blockchain::verify_chain(&blockchain.blockchain));
+
+        // the genesis block must not point to a previous block
+        let mut chain = blockchain.blockchain.clone();
+        chain[0].prev_hash = HashValue::new([1u8; 32]);
+        chain[0].hash = chain[0].sha256();
+        assert!(!Blockchain::verify_chain(&chain));
+        assert!(!Blockchain::verify_chain(&[]));
+    }
+
+    #[test]
+    fn test_orphan_blocks() {
+        let mut blockchain = Blockchain::new("hello world");
+        mine_blocks(&mut blockchain, 1);
+        let mut source = blockchain.clone();
+        mine_blocks(&mut source, 2);
+        let parent = source.get_block(2).unwrap().clone();
+        let child = source.get_block(3).unwrap().clone();
+
+        // the child arrives first, it can't be connected yet
+        blockchain.add_orphan(child.clone());
+        blockchain.add_orphan(child.clone());
+        assert_eq!(blockchain.get_orphans(), std::slice::from_ref(&child));
+        assert_eq!(blockchain.try_connect_orphans(), 0);
+        assert_eq!(blockchain.len(), 2);
+
+        // then the parent
+        blockchain.add_orphan(parent.clone());
+        assert_eq!(blockchain.try_connect_orphans(), 2);
+        assert_eq!(blockchain.get_block(2), Some(&parent));
+        assert_eq!(blockchain.get_block(3), Some(&child));
+        assert!(blockchain.get_orphans().is_empty());
+
+        // a block already on the chain is not an orphan
+        blockchain.add_orphan(child);
+        assert!(blockchain.get_orphans().is_empty());
+
+        // the pool is bounded, the oldest orphans are dropped
+        for nonce in 0..=MAX_ORPHAN_POOL_SIZE {
+            let mut orphan = parent.clone();
+            orphan.index = 10;
+            orphan.nonce = nonce as i64;
+            orphan.hash = orphan.sha256();
+            blockchain.add_orphan(orphan);
+        }
+        assert_eq!(blockchain.get_orphans().len(), MAX_ORPHAN_POOL_SIZE);
+        assert_eq!(blockchain.get_orphans()[0].nonce, 1);
+
+        // an orphan mined at a lower difficulty than the chain requires is dropped
+        let mut blockchain = Blockchain::new_with_genesis(&GenesisConfig {
+            version: "0.1v test".to_string(),
+            timestamp: 1_700_000_000,
+            difficulty: 0x2000FFFF,
+            message: "hello world".to_string(),
+            chain_id: 0,
+            target_block_interval_secs: DEFAULT_TARGET_BLOCK_INTERVAL_SECS,
+            testnet: false,
+        });
+        let mut source = blockchain.clone();
+        mine_blocks(&mut source, 1);
+        let orphan = source.get_block(1).unwrap().clone();
+        assert!(orphan.difficulty != blockchain.next_difficulty());
+        blockchain.add_orphan(orphan);
+        assert_eq!(blockchain.try_connect_orphans(), 0);
+        assert_eq!(blockchain.len(), 1);
+        assert!(blockchain.get_orphans().is_empty());
+    }
+
+    #[test]
+    fn test_checkpoints() {
+        let mut blockchain = Blockchain::new("hello world");
+        mine_blocks(&mut blockchain, 3);
+        blockchain.set_checkpoints(BTreeMap::from([(2, blockchain.get_block(2).unwrap().hash)]));
+
+        // a longer chain rewriting the block at the checkpoint
+        let mut fork = Blockchain::from_vec(&blockchain.blockchain[..2]);
+        let tampered_block = fork
+            .generate_new_block(
+                vec![(Wallet::new().get_address(), dec!(50.0))],
+                "0.1v test".to_string(),
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs(),
+                0x207FFFFF_u32,
+                Some(vec![]),
+            )
+            .unwrap();
+        fork.add_block(tampered_block);
+        mine_blocks(&mut fork, 3);
+        assert!(Blockchain::verify_chain(&fork.blockchain));
+        assert!(!blockchain.verify_candidate_chain(&fork.blockchain));
+        assert!(!blockchain.matches_checkpoint(fork.get_block(2).unwrap()));
+        let tip = blockchain.get_last_block().unwrap().hash;
+        assert!(!blockchain.resolve_conflicts(&fork.blockchain));
+        assert_eq!(blockchain.get_last_block().unwrap().hash, tip);
+
+        // a longer chain building on the checkpoint is adopted
+        let mut extended = blockchain.clone();
+        mine_blocks(&mut extended, 2);
+        assert!(blockchain.resolve_conflicts(&extended.blockchain));
+        assert_eq!(blockchain.len(), 6);
+
+        // the signatures below the checkpoint are assumed valid, the chain still verifies
+        let chain = Blockchain::deterministic_chain(COINBASE_MATURITY + 3, 5);
+        let checkpoints = BTreeMap::from([(
+            COINBASE_MATURITY + 1,
+            chain.get_block(COINBASE_MATURITY + 1).unwrap().hash,
+        )]);
+        let mut verifier = Blockchain::from_vec(&chain.blockchain[..1]);
+        verifier.set_checkpoints(checkpoints);
+        assert!(verifier.verify_candidate_chain(&chain.blockchain));
+    }
+
+    #[test]
+    fn test_confirmations() {
+        let mut blockchain = Blockchain::new("hello world");
+        let mut wallet = Wallet::new();
+        blockchain.add_block(
+            blockchain
+                .generate_new_block(
+                    vec![(wallet.get_address(), dec!(50.0))],
+                    "0.1v test".to_string(),
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs(),
+                    0x207FFFFF_u32,
+                    Some(vec![]),
+                )
+                .unwrap(),
+        );
+        let coinbase_tx_id = blockchain.get_block(1).unwrap().data[0].get_transaction_id();
+        assert_eq!(blockchain.confirmations(coinbase_tx_id), Some(1));
+        mine_blocks(&mut blockchain, COINBASE_MATURITY);
+        assert_eq!(
+            blockchain.confirmations(coinbase_tx_id),
+            Some(COINBASE_MATURITY + 1)
+        );
+
+        wallet.update_unspent_tx_outputs(blockchain.get_utxos_for(&wallet.get_address(), 0));
+        let tx = wallet
+            .send(vec![(dec!(20.0), Wallet::new().get_address())])
+            .unwrap();
+        blockchain.add_to_pool(tx.clone()).unwrap();
+        // not confirmed while waiting in the pool
+        assert_eq!(blockchain.confirmations(tx.get_transaction_id()), None);
+
+        blockchain.add_block(
+            blockchain
+                .generate_new_block(
+                    vec![],
+                    "0.1v test".to_string(),
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs(),
+                    0x207FFFFF_u32,
+                    None,
+                )
+                .unwrap(),
+        );
+        assert_eq!(blockchain.confirmations(tx.get_transaction_id()), Some(1));
+        mine_blocks(&mut blockchain, 2);
+        assert_eq!(blockchain.confirmations(tx.get_transaction_id()), Some(3));
+        assert_eq!(blockchain.confirmations(HashValue::new([0u8; 32])), None);
+    }
+
+    #[test]
+    fn test_find_transaction() {
+        let mut blockchain = Blockchain::new("hello world");
+        let wallet = Wallet::new();
+        mine_blocks(&mut blockchain, 2);
+        blockchain.add_block(
+            blockchain
+                .generate_new_block(
+                    vec![(wallet.get_address(), dec!(50.0))],
+                    "0.1v test".to_string(),
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs(),
+                    0x207FFFFF_u32,
+                    Some(vec![]),
+                )
+                .unwrap(),
+        );
+        mine_blocks(&mut blockchain, 1);
+
+        let coinbase_tx = blockchain.get_block(3).unwrap().data[0].clone();
+        let (block_index, tx) = blockchain
+            .find_transaction(coinbase_tx.get_transaction_id())
+            .unwrap();
+        assert_eq!(block_index, 3);
+        assert_eq!(tx, &coinbase_tx);
+        assert_eq!(
+            blockchain
+                .find_output(coinbase_tx.get_transaction_id(), 0)
+                .unwrap()
+                .get_locking_script(),
+            &wallet.get_address().to_vec()
+        );
+        assert!(blockchain
+            .find_output(coinbase_tx.get_transaction_id(), 1)
+            .is_none());
+        assert!(blockchain
+            .find_transaction(HashValue::new([0u8; 32]))
+            .is_none());
+
+        // the index is rebuilt for a chain created from blocks
+        let rebuilt = Blockchain::from_vec(&blockchain.blockchain);
+        assert_eq!(rebuilt.tx_index, blockchain.tx_index);
+    }
+
+    #[test]
+    fn test_verify_block_logs_failure() {
+        captured_warnings();
+        let mut blockchain = Blockchain::new("hello world");
+        mine_blocks(&mut blockchain, 1);
+        let mut block = blockchain
+            .generate_new_block(
+                vec![],
+                "0.1v test".to_string(),
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs(),
+                0x207FFFFF_u32,
+                Some(vec![]),
+            )
+            .unwrap();
+        block.merkle_root = HashValue::new([7u8; 32]);
+
+        assert!(!blockchain.verify_block(&block, 0x207FFFFF_u32));
+        let expected = format!(
+            "invalid block 2: merkle root {} doesn't match the transactions",
+            block.merkle_root
+        );
+        assert!(captured_warnings()
+            .iter()
+            .any(|warning| warning.starts_with(&expected)));
+    }
+
+    #[test]
+    fn test_verify_block_detailed() {
+        let mut blockchain = Blockchain::new("hello world");
+        mine_blocks(&mut blockchain, 1);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let block = blockchain
+            .generate_new_block(
+                vec![],
+                "0.1v test".to_string(),
+                now,
+                0x207FFFFF_u32,
+                Some(vec![]),
+            )
+            .unwrap();
+        assert_eq!(
+            blockchain.verify_block_detailed(&block, 0x207FFFFF_u32),
+            Ok(())
+        );
+
+        let mut bad_merkle_root = block.clone();
+        bad_merkle_root.merkle_root = HashValue::new([7u8; 32]);
+        assert_eq!(
+            blockchain.verify_block_detailed(&bad_merkle_root, 0x207FFFFF_u32),
+            Err(BlockValidationError::MerkleRootMismatch {
+                merkle_root: HashValue::new([7u8; 32]),
+                expected: block.merkle_root,
+            })
+        );
+
+        // re-mined, so that only the tampered field is wrong
+        let mut bad_prev_hash = block.clone();
+        bad_prev_hash.prev_hash = HashValue::new([7u8; 32]);
+        bad_prev_hash.update_hash_and_nonce();
+        assert_eq!(
+            blockchain.verify_block_detailed(&bad_prev_hash, 0x207FFFFF_u32),
+            Err(BlockValidationError::PrevHashMismatch {
+                prev_hash: HashValue::new([7u8; 32])
+            })
+        );
+
+        let mut bad_timestamp = block.clone();
+        bad_timestamp.timestamp = now + 3600;
+        bad_timestamp.update_hash_and_nonce();
+        assert_eq!(
+            blockchain.verify_block_detailed(&bad_timestamp, 0x207FFFFF_u32),
+            Err(BlockValidationError::InvalidTimestamp {
+                timestamp: now + 3600
+            })
+        );
+
+        assert!(matches!(
+            blockchain.verify_block_detailed(&block, 0x1D00FFFF_u32),
+            Err(BlockValidationError::DifficultyMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_add_block_checked() {
+        let wallet = Wallet::new();
+        let mut blockchain = chain_with_mature_rewards(&wallet, 1);
+        let utxos = blockchain.get_utxos_for(&wallet.get_address(), 0);
+        let tx = wallet
+            .transfer_credits(utxos, vec![(dec!(40.0), Wallet::new().get_address())], None)
+            .unwrap();
+        blockchain.add_to_pool(tx.clone()).unwrap();
+        let block = blockchain
+            .generate_new_block(
+                vec![],
+                "0.1v test".to_string(),
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs(),
+                0x207FFFFF_u32,
+                Some(vec![tx.clone()]),
+            )
+            .unwrap();
+
+        // an invalid block changes nothing
+        let len = blockchain.len();
+        let commitment = blockchain.utxo_commitment();
+        let mut bad_merkle_root = block.clone();
+        bad_merkle_root.merkle_root = HashValue::new([7u8; 32]);
+        assert!(matches!(
+            blockchain.add_block_checked(bad_merkle_root, 0x207FFFFF_u32),
+            Err(BlockValidationError::MerkleRootMismatch { .. })
+        ));
+        assert_eq!(blockchain.len(), len);
+        assert_eq!(blockchain.utxo_commitment(), commitment);
+        assert_eq!(blockchain.get_tx_pool(), std::slice::from_ref(&tx));
+        assert_eq!(blockchain.find_transaction(tx.get_transaction_id()), None);
+
+        assert_eq!(
+            blockchain.add_block_checked(block.clone(), 0x207FFFFF_u32),
+            Ok(())
+        );
+        assert_eq!(blockchain.len(), len + 1);
+        assert_eq!(blockchain.get_last_block(), Some(&block));
+        assert_ne!(blockchain.utxo_commitment(), commitment);
+        assert!(blockchain.get_tx_pool().is_empty());
+        assert_eq!(blockchain.confirmations(tx.get_transaction_id()), Some(1));
+
+        // the same block no longer extends the chain
+        assert!(blockchain.add_block_checked(block, 0x207FFFFF_u32).is_err());
+        assert_eq!(blockchain.len(), len + 1);
+    }
+
+    #[test]
+    fn test_empty_block() {
+        let mut blockchain = Blockchain::new("hello world");
+        mine_blocks(&mut blockchain, 1);
+        let mut block = blockchain
+            .generate_new_block(
+                vec![],
+                "0.1v test".to_string(),
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs(),
+                0x207FFFFF_u32,
+                Some(vec![]),
+            )
+            .unwrap();
+
+        // not even the coinbase transaction, with the all-zero merkle root of no transaction
+        block.data_mut().clear();
+        block.merkle_root = block.calc_merkle_root();
+        assert_eq!(block.merkle_root, HashValue::new([0; 32]));
+        block.update_hash_and_nonce();
+        assert!(!blockchain.verify_merkle_root(&block));
+        assert_eq!(
+            blockchain.verify_block_detailed(&block, 0x207FFFFF_u32),
+            Err(BlockValidationError::EmptyBlock)
+        );
+        assert!(!blockchain.verify_block(&block, 0x207FFFFF_u32));
+
+        // the genesis block is exempt
+        let genesis_block = blockchain.get_block(0).unwrap();
+        assert!(blockchain.verify_merkle_root(genesis_block));
+    }
+
+    #[test]
+    fn test_new_with_genesis() {
+        let config = GenesisConfig {
+            version: "0.1v test".to_string(),
+            timestamp: 1_700_000_000,
+            difficulty: 0x207FFFFF,
+            message: "hello world".to_string(),
+            chain_id: 1,
+            target_block_interval_secs: DEFAULT_TARGET_BLOCK_INTERVAL_SECS,
+            testnet: false,
+        };
+        let blockchain1 = Blockchain::new_with_genesis(&config);
+        sleep(std::time::Duration::from_secs(1));
+        let blockchain2 = Blockchain::new_with_genesis(&config);
+
+        let genesis_block = blockchain1.get_block(0).unwrap();
+        assert_eq!(genesis_block.hash, blockchain2.get_block(0).unwrap().hash);
+        assert_eq!(genesis_block.timestamp, config.timestamp);
+        assert_eq!(genesis_block.difficulty, config.difficulty);
+        assert_eq!(
+            genesis_block.data[0].get_additional_data(),
+            Some(config.message.as_bytes())
+        );
+        assert!(Blockchain::verify_chain(&blockchain1.blockchain));
+
+        // another message, another network
+        let other = Blockchain::new_with_genesis(&GenesisConfig {
+            message: "another network".to_string(),
+            ..config.clone()
+        });
+        assert_ne!(genesis_block.hash, other.get_block(0).unwrap().hash);
+        let other = Blockchain::new_with_genesis(&GenesisConfig {
+            chain_id: 2,
+            ..config
+        });
+        assert_ne!(genesis_block.hash, other.get_block(0).unwrap().hash);
+        assert_eq!(blockchain1.get_chain_id(), 1);
+        assert_eq!(other.get_chain_id(), 2);
+    }
+
+    #[test]
+    fn test_chain_id_replay_protection() {
+        let mut blockchain = Blockchain::new_with_genesis(&GenesisConfig {
+            version: "0.1v test".to_string(),
+            timestamp: 1_700_000_000,
+            difficulty: DEFAULT_GENESIS_DIFFICULTY,
+            message: "hello world".to_string(),
+            chain_id: 1,
+            target_block_interval_secs: DEFAULT_TARGET_BLOCK_INTERVAL_SECS,
+            testnet: false,
+        });
+        let mut wallet = Wallet::new();
+        blockchain.add_block(
+            blockchain
+                .generate_new_block(
+                    vec![(wallet.get_address(), dec!(50.0))],
+                    "0.1v test".to_string(),
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs(),
+                    0x207FFFFF_u32,
+                    Some(vec![]),
+                )
+                .unwrap(),
+        );
+        mine_blocks(&mut blockchain, COINBASE_MATURITY);
+        assert_eq!(blockchain.get_block(1).unwrap().data[0].get_chain_id(), 1);
+        assert!(Blockchain::verify_chain(&blockchain.blockchain));
+
+        let coinbase = blockchain.get_block(1).unwrap().data[0].clone();
+        let transfer = |wallet: &Wallet| {
+            wallet
+                .transfer_credits(
+                    vec![UTXO::new(coinbase.clone(), 1, 0)],
+                    vec![(dec!(49.0), Wallet::new().get_address())],
+                    None,
+                )
+                .unwrap()
+        };
+        wallet.set_chain_id(1);
+        let tx = transfer(&wallet);
+        wallet.set_chain_id(2);
+        let foreign_tx = transfer(&wallet);
+
+        // the same UTXOs on another network, the transaction signed for chain 1 is rejected there
+        let mut other = blockchain.clone();
+        other.chain_id = 2;
+        assert!(matches!(
+            other.add_to_pool(tx.clone()),
+            Err(RustyCoinError::InvalidTransaction)
+        ));
+        assert!(other.add_to_pool(foreign_tx.clone()).is_ok());
+
+        // rebinding the signed transaction to chain 2 invalidates its signature
+        let mut replayed_tx = tx.clone();
+        replayed_tx.set_chain_id(2);
+        replayed_tx.update_digest();
+        assert!(matches!(
+            other.add_to_pool(replayed_tx),
+            Err(RustyCoinError::InvalidTransaction)
+        ));
+
+        assert!(matches!(
+            blockchain.add_to_pool(foreign_tx),
+            Err(RustyCoinError::InvalidTransaction)
+        ));
+        assert!(blockchain.add_to_pool(tx).is_ok());
+    }
+
+    #[test]
+    fn test_data_output() {
+        let mut blockchain = Blockchain::new("hello world");
+        let secret_key = SecretKey::from_slice(&[7u8; 32]).unwrap();
+        let wallet = Wallet::from_secret_key(secret_key);
+        let now = || {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+        };
+        blockchain.add_block(
+            blockchain
+                .generate_new_block(
+                    vec![(wallet.get_address(), dec!(50.0))],
+                    "0.1v test".to_string(),
+                    now(),
+                    0x207FFFFF_u32,
+                    Some(vec![]),
+                )
+                .unwrap(),
+        );
+        mine_blocks(&mut blockchain, COINBASE_MATURITY);
+
+        let coinbase = blockchain.get_block(1).unwrap().data[0].clone();
+        let sign = |tx: &Transaction, input_index: usize| {
+            Some(Input::generate_unlock_script(
+                tx,
+                input_index,
+                secret_key,
+                wallet.get_public_key(),
+            ))
+        };
+        let tx = TransactionBuilder::new()
+            .add_input(
+                Input::new(coinbase.get_transaction_id(), 1, 0, vec![]),
+                dec!(50.0),
+            )
+            .add_output(Output::new(dec!(49.0), wallet.get_address().to_vec()).unwrap())
+            .add_output(Output::new_data(&[7u8; 32]).unwrap())
+            .build_signed(sign)
+            .unwrap();
+        assert!(blockchain.add_to_pool(tx.clone()).is_ok());
+        let block = blockchain
+            .generate_new_block(vec![], "0.1v test".to_string(), now(), 0x207FFFFF_u32, None)
+            .unwrap();
+        assert!(blockchain.verify_block(&block, 0x207FFFFF_u32));
+        blockchain.add_block(block);
+
+        // the data output is on the chain, but never in the UTXO set
+        assert!(blockchain.find_output(tx.get_transaction_id(), 1).is_some());
+        assert!(!blockchain
+            .get_utxo_set()
+            .contains(&OutPoint::new(tx.get_transaction_id(), 1)));
+        assert!(blockchain
+            .get_utxo_set()
+            .contains(&OutPoint::new(tx.get_transaction_id(), 0)));
+
+        // so it can't be spent
+        mine_blocks(&mut blockchain, REGULAR_MATURITY);
+        let spending_tx = TransactionBuilder::new()
+            .add_input(
+                Input::new(
+                    tx.get_transaction_id(),
+                    blockchain.len() - 1 - REGULAR_MATURITY,
+                    1,
+                    vec![],
+                ),
+                Decimal::ZERO,
+            )
+            .build_signed(sign)
+            .unwrap();
+        assert!(matches!(
+            blockchain.add_to_pool(spending_tx),
+            Err(RustyCoinError::InvalidTransaction)
+        ));
+    }
+
+    #[test]
+    fn test_chain_metadata() {
+        let mut blockchain = Blockchain::new("hello world");
+        assert_eq!(
+            blockchain.genesis_message(),
+            Some("hello world".to_string())
+        );
+
+        mine_blocks(&mut blockchain, 2);
+        let metadata = blockchain.chain_metadata();
+        assert_eq!(metadata.height, 2);
+        assert_eq!(
+            metadata.total_work,
+            Blockchain::chain_work(&blockchain.blockchain)
+        );
+        assert_eq!(metadata.genesis_hash, blockchain.get_block(0).unwrap().hash);
+        assert_eq!(metadata.tip_hash, blockchain.get_last_block().unwrap().hash);
+        assert_eq!(metadata.chain_id, 0);
+
+        // the genesis transaction is gone once the chain is pruned
+        blockchain.prune(1);
+        assert_eq!(blockchain.genesis_message(), None);
+        assert_eq!(blockchain.chain_metadata(), metadata);
+    }
+
+    #[test]
+    fn test_explorer_snapshot() {
+        let wallet = Wallet::new();
+        let mut blockchain = chain_with_mature_rewards(&wallet, 1);
+        let utxos = blockchain.get_utxos_for(&wallet.get_address(), 0);
+        // pays a fee of 10 rusty coins
+        let tx = wallet
+            .transfer_credits(
+                utxos,
+                vec![
+                    (dec!(30.0), Wallet::new().get_address()),
+                    (dec!(10.0), wallet.get_address()),
+                ],
+                None,
+            )
+            .unwrap();
+        let txs = vec![tx];
+        let reward = blockchain.expected_coinbase_reward(&txs).unwrap();
+        blockchain.add_block(
+            blockchain
+                .generate_new_block(
+                    vec![(wallet.get_address(), reward)],
+                    "0.1v test".to_string(),
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs(),
+                    0x207FFFFF_u32,
+                    Some(txs),
+                )
+                .unwrap(),
+        );
+
+        let snapshot = blockchain.explorer_snapshot().unwrap();
+        assert_eq!(snapshot.metadata, blockchain.chain_metadata());
+        assert_eq!(snapshot.blocks.len(), blockchain.len());
+        for (summary, block) in snapshot.blocks.iter().zip(&blockchain.blockchain) {
+            assert_eq!(summary.height, block.index);
+            assert_eq!(summary.hash, block.hash);
+            assert_eq!(summary.timestamp, block.timestamp);
+            assert_eq!(summary.difficulty, block.difficulty);
         }
+        // the genesis transaction has no output
+        assert_eq!(snapshot.blocks[0].tx_count, 1);
+        assert_eq!(snapshot.blocks[0].total_output_value, dec!(0.0));
+        assert_eq!(snapshot.blocks[1].tx_count, 1);
+        assert_eq!(snapshot.blocks[1].total_output_value, dec!(50.0));
+        // the coinbase transaction plus the transfer
+        let last = snapshot.blocks.last().unwrap();
+        assert_eq!(last.tx_count, 2);
+        assert_eq!(last.total_output_value, reward + dec!(40.0));
 
-        true
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert_eq!(
+            serde_json::from_str::<ExplorerSnapshot>(&json).unwrap(),
+            snapshot
+        );
     }
 
-    /// reward rule of the coinbase transaction
-    /// - the reward of the coinbase transaction is a convergent infinite series
-    /// - the reward of the coinbase transaction is 50 rusty coin at the beginning
-    fn reward_algorithm(index: usize) -> Decimal {
-        let inverse_log = 18.0 * 1.0 / (index.to_f64().unwrap() + 1.0_f64).log10();
-        let inverse_log_floor = inverse_log.floor();
-        Decimal::from_f64(inverse_log_floor).unwrap()
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_spawn_miner() {
+        let chain = Arc::new(RwLock::new(Blockchain::new("hello world")));
+        let reward_address = Wallet::new().get_address();
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let miner = Blockchain::spawn_miner(chain.clone(), reward_address, shutdown.clone());
+
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        shutdown.store(true, atomic::Ordering::Relaxed);
+        miner.await.unwrap();
+
+        // the miner stopped, and the blocks it mined extend the chain
+        let blockchain = chain.read().await;
+        let height = blockchain.len();
+        assert!(height > 1);
+        assert!(Blockchain::verify_chain(&blockchain.blockchain));
+        assert_eq!(
+            blockchain.get_balance(&reward_address).unwrap(),
+            Blockchain::reward_algorithm(1) * Decimal::from(height - 1)
+        );
+        drop(blockchain);
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(chain.read().await.len(), height);
     }
 
-    /// create the first block of a blockchain, the genesis block
-    ///
-    /// # Arguments
-    /// * `init_msg: &str` - the message of the genesis transaction of the genesis block
-    fn create_genesis_block(init_msg: &str) -> Block {
-        let init_time = match SystemTime::now().duration_since(UNIX_EPOCH) {
-            Ok(duration) => duration.as_secs(),
-            Err(e) => {
-                panic!("SystemTimeError: {}", e);
-            }
-        };
+    #[test]
+    fn test_get_block_by_hash() {
+        let mut blockchain = Blockchain::new("hello world");
+        mine_blocks(&mut blockchain, 2);
 
-        let mut genesis_transaction = Transaction::new(
-            vec![],
+        for index in 0..3 {
+            let block = blockchain.get_block(index).unwrap();
+            assert_eq!(blockchain.get_block_by_hash(&block.hash), Some(block));
+        }
+        let block = blockchain.get_block(2).unwrap().clone();
+        assert_eq!(
+            blockchain.get_block_by_hash(&block.prev_hash),
+            blockchain.get_block(1)
+        );
+        assert!(blockchain
+            .get_block_by_hash(&HashValue::new([0u8; 32]))
+            .is_none());
+
+        // the index follows the chain when it is replaced
+        let mut other = Blockchain::from_vec(&blockchain.blockchain[..1]);
+        other.add_block(
+            other
+                .generate_new_block(
+                    vec![(Wallet::new().get_address(), dec!(50.0))],
+                    "0.1v test".to_string(),
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs(),
+                    0x207FFFFF_u32,
+                    Some(vec![]),
+                )
+                .unwrap(),
+        );
+        mine_blocks(&mut other, 2);
+        assert!(blockchain.resolve_conflicts(&other.blockchain));
+        assert!(blockchain.get_block_by_hash(&block.hash).is_none());
+        let last_block = other.get_last_block().unwrap();
+        assert_eq!(
+            blockchain.get_block_by_hash(&last_block.hash),
+            Some(last_block)
+        );
+    }
+
+    #[test]
+    fn test_compute_fee() {
+        let mut blockchain = Blockchain::new("hello world");
+        let wallet = Wallet::from_secret_key(SecretKey::from_slice(&[7u8; 32]).unwrap());
+        let receiver = Wallet::new();
+        blockchain.add_block(
+            blockchain
+                .generate_new_block(
+                    vec![(wallet.get_address(), dec!(50.0))],
+                    "0.1v test".to_string(),
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs(),
+                    0x207FFFFF_u32,
+                    Some(vec![]),
+                )
+                .unwrap(),
+        );
+        mine_blocks(&mut blockchain, COINBASE_MATURITY);
+
+        let utxo = blockchain.get_utxos_for(&wallet.get_address(), 0).remove(0);
+        let tx = wallet
+            .transfer_credits(
+                vec![utxo.clone()],
+                vec![(dec!(45.0), receiver.get_address())],
+                None,
+            )
+            .unwrap();
+        assert_eq!(tx.compute_fee(&blockchain).unwrap(), dec!(5.0));
+        assert!(blockchain.verify_regular_transaction(&tx, blockchain.len(), &BTreeSet::new()));
+
+        // a correctly signed transaction declaring a forged fee of 1 instead of 5
+        let mut forged_tx = Transaction::new(
+            vec![Input::new(
+                utxo.prev_tx_hash,
+                utxo.prev_block_index,
+                utxo.prev_output_index,
+                vec![],
+            )],
+            vec![Output::new(dec!(45.0), receiver.get_address().to_vec()).unwrap()],
+            HashValue::new([0u8; 32]),
+            dec!(1.0),
+            None,
+        );
+        let unlock_script = Input::generate_unlock_script(
+            &forged_tx,
+            0,
+            SecretKey::from_slice(&[7u8; 32]).unwrap(),
+            wallet.get_public_key(),
+        );
+        forged_tx.set_unlock_script(0, unlock_script);
+        forged_tx.update_digest();
+
+        assert_eq!(forged_tx.compute_fee(&blockchain).unwrap(), dec!(5.0));
+        assert!(!blockchain.verify_regular_transaction(
+            &forged_tx,
+            blockchain.len(),
+            &BTreeSet::new()
+        ));
+        assert!(blockchain.add_to_pool(forged_tx).is_err());
+
+        // spending more than the inputs, or an unknown output
+        let overspending_tx = Transaction::new(
+            vec![Input::new(
+                utxo.prev_tx_hash,
+                utxo.prev_block_index,
+                utxo.prev_output_index,
+                vec![],
+            )],
+            vec![Output::new(dec!(60.0), receiver.get_address().to_vec()).unwrap()],
+            HashValue::new([0u8; 32]),
+            dec!(0.0),
+            None,
+        );
+        assert!(matches!(
+            overspending_tx.compute_fee(&blockchain),
+            Err(RustyCoinError::InvalidInputFee)
+        ));
+        let unknown_input_tx = Transaction::new(
+            vec![Input::new(HashValue::new([1u8; 32]), 1, 0, vec![])],
             vec![],
             HashValue::new([0u8; 32]),
             dec!(0.0),
-            Some(init_msg.as_bytes().to_vec()),
+            None,
         );
+        assert!(matches!(
+            unknown_input_tx.compute_fee(&blockchain),
+            Err(RustyCoinError::InvalidOutputIndex)
+        ));
+    }
 
-        genesis_transaction.update_digest(); // update genesis transaction's digest (transaction_id, hash value of the transaction)
+    #[test]
+    fn test_max_block_size() {
+        let mut blockchain = Blockchain::new("hello world");
+        let wallet = Wallet::new();
+        let now = || {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+        };
+        mine_blocks(&mut blockchain, 1);
 
-        let mut genesis_block = Block {
-            version: "0.1v test".to_string(),
-            index: 0,
-            data: vec![genesis_transaction],
-            timestamp: init_time,
-            prev_hash: HashValue::new([0; 32]),
-            hash: HashValue::new([0; 32]),
-            merkle_root: HashValue::new([0; 32]),
-            difficulty: 0,
-            nonce: 0,
+        // 100 KB each, only 9 of them fit in a block
+        let unpacked_transactions = (0..20u8)
+            .map(|i| {
+                let mut tx = Transaction::new(
+                    vec![],
+                    vec![],
+                    HashValue::new([0u8; 32]),
+                    dec!(0.0),
+                    Some(vec![i; 100_000]),
+                );
+                tx.update_digest();
+                tx
+            })
+            .collect::<Vec<Transaction>>();
+        let block = blockchain
+            .generate_new_block(
+                vec![(wallet.get_address(), dec!(50.0))],
+                "0.1v test".to_string(),
+                now(),
+                0x207FFFFF_u32,
+                Some(unpacked_transactions.clone()),
+            )
+            .unwrap();
+        assert_eq!(block.data.len(), 1 + 9);
+        assert_eq!(&block.data[1..], &unpacked_transactions[..9]);
+        assert!(block.size() <= MAX_BLOCK_SIZE);
+
+        // a pool filling the block up to the brim, paying fees, leaves room for the coinbase transaction,
+        // so the reward of the selected transactions is claimed like `spawn_miner` does
+        let filler = |i: u8, len: usize| {
+            let mut tx = Transaction::new(
+                vec![],
+                vec![],
+                HashValue::new([0u8; 32]),
+                dec!(1.0),
+                Some(vec![i; len]),
+            );
+            tx.update_digest();
+            tx
         };
+        blockchain.tx_pool = (0..9u8).map(|i| filler(i, 100_000)).collect();
+        let pool_size = blockchain
+            .tx_pool
+            .iter()
+            .map(Transaction::size)
+            .sum::<usize>();
+        let overhead = filler(9, 0).size();
+        // 8 bytes for the length of the transactions of the block
+        let last_tx = filler(9, MAX_BLOCK_SIZE - 8 - pool_size - overhead);
+        blockchain.tx_pool.push(last_tx);
+        let txs = blockchain.select_transactions_for_block(usize::MAX);
+        assert_eq!(txs.len(), 9);
+        let reward = blockchain.expected_coinbase_reward(&txs).unwrap();
+        let block = blockchain
+            .generate_new_block(
+                vec![(wallet.get_address(), reward)],
+                "0.1v test".to_string(),
+                now(),
+                0x207FFFFF_u32,
+                Some(txs.clone()),
+            )
+            .unwrap();
+        assert_eq!(&block.data[1..], &txs[..]);
 
-        genesis_block.merkle_root = genesis_block.calc_merkle_root(); // update merkle root of the genesis block
-        genesis_block.hash = genesis_block.sha256(); // update hash value of the genesis block
-        genesis_block
+        // a block made of a coinbase transaction padded with an extra nonce
+        let padded_block = |padding: usize| {
+            let mut block = blockchain
+                .generate_new_block(
+                    vec![(wallet.get_address(), dec!(50.0))],
+                    "0.1v test".to_string(),
+                    now(),
+                    0x207FFFFF_u32,
+                    Some(vec![]),
+                )
+                .unwrap();
+            let mut coinbase = Transaction::new(
+                vec![],
+                block.data[0].get_outputs().clone(),
+                HashValue::new([0u8; 32]),
+                dec!(0.0),
+                Some(
+                    [
+                        Blockchain::coinbase_height_commitment(2),
+                        vec![0u8; padding],
+                    ]
+                    .concat(),
+                ),
+            );
+            coinbase.update_digest();
+            *block.data_mut() = vec![coinbase];
+            block.merkle_root = block.calc_merkle_root();
+            block.update_hash_and_nonce();
+            block
+        };
+        assert!(blockchain.verify_block(&padded_block(MAX_BLOCK_SIZE / 2), 0x207FFFFF_u32));
+        let oversized_block = padded_block(MAX_BLOCK_SIZE);
+        assert!(oversized_block.size() > MAX_BLOCK_SIZE);
+        assert!(!blockchain.verify_block(&oversized_block, 0x207FFFFF_u32));
     }
-}
 
-impl Default for Blockchain {
-    fn default() -> Self {
-        Self::new("Default Blockchain")
-    }
-}
+    #[test]
+    fn test_double_spend_within_a_block() {
+        let mut blockchain = Blockchain::new("hello world");
+        let wallet = Wallet::new();
+        let receiver = Wallet::new();
+        let now = || {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+        };
+        blockchain.add_block(
+            blockchain
+                .generate_new_block(
+                    vec![(wallet.get_address(), dec!(50.0))],
+                    "0.1v test".to_string(),
+                    now(),
+                    0x207FFFFF_u32,
+                    Some(vec![]),
+                )
+                .unwrap(),
+        );
+        mine_blocks(&mut blockchain, COINBASE_MATURITY);
 
-impl Display for Blockchain {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "[Blockchain]:")?;
-        for block in self.blockchain.iter() {
-            write!(f, "{}\n\n\n", block)?;
+        // two different transactions spending the same output
+        let utxos = || blockchain.get_utxos_for(&wallet.get_address(), 0);
+        let tx1 = wallet
+            .transfer_credits(utxos(), vec![(dec!(50.0), receiver.get_address())], None)
+            .unwrap();
+        let tx2 = wallet
+            .transfer_credits(utxos(), vec![(dec!(49.0), receiver.get_address())], None)
+            .unwrap();
+        assert_ne!(tx1.get_transaction_id(), tx2.get_transaction_id());
+
+        for tx in [&tx1, &tx2] {
+            let block = blockchain
+                .generate_new_block(
+                    vec![],
+                    "0.1v test".to_string(),
+                    now(),
+                    0x207FFFFF_u32,
+                    Some(vec![tx.clone()]),
+                )
+                .unwrap();
+            assert!(blockchain.verify_block(&block, 0x207FFFFF_u32));
         }
-        Ok(())
+
+        let block = blockchain
+            .generate_new_block(
+                vec![],
+                "0.1v test".to_string(),
+                now(),
+                0x207FFFFF_u32,
+                Some(vec![tx1, tx2]),
+            )
+            .unwrap();
+        assert!(!blockchain.verify_block(&block, 0x207FFFFF_u32));
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::thread::sleep;
+    #[test]
+    fn test_verify_block_signatures_batched() {
+        captured_warnings();
+        let mut blockchain = Blockchain::new("hello world");
+        let wallet = Wallet::new();
+        let receiver = Wallet::new();
+        let now = || {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+        };
+        // 50 outputs to the wallet, spent by a single transaction with 50 inputs
+        blockchain.add_block(
+            blockchain
+                .generate_new_block(
+                    vec![(wallet.get_address(), dec!(1.0)); 50],
+                    "0.1v test".to_string(),
+                    now(),
+                    0x207FFFFF_u32,
+                    Some(vec![]),
+                )
+                .unwrap(),
+        );
+        mine_blocks(&mut blockchain, COINBASE_MATURITY);
+        let mut tx = wallet
+            .transfer_credits(
+                blockchain.get_utxos_for(&wallet.get_address(), 0),
+                vec![(dec!(49.0), receiver.get_address())],
+                None,
+            )
+            .unwrap();
+        assert_eq!(tx.get_inputs().len(), 50);
+
+        let verify_one_by_one = |blockchain: &Blockchain, tx: &Transaction| {
+            tx.get_inputs()
+                .iter()
+                .enumerate()
+                .all(|(input_index, input)| {
+                    let outpoint =
+                        OutPoint::new(input.get_prev_tx_hash(), input.get_prev_output_index());
+                    Transaction::verify_scripts(
+                        tx,
+                        input_index,
+                        input.get_unlock_script(),
+                        blockchain
+                            .utxo_set
+                            .get(&outpoint)
+                            .unwrap()
+                            .output
+                            .get_locking_script(),
+                    )
+                })
+        };
+        let new_block = |blockchain: &Blockchain, tx: &Transaction| {
+            blockchain
+                .generate_new_block(
+                    vec![],
+                    "0.1v test".to_string(),
+                    now(),
+                    0x207FFFFF_u32,
+                    Some(vec![tx.clone()]),
+                )
+                .unwrap()
+        };
+
+        let block = new_block(&blockchain, &tx);
+        // the batch agrees with checking the signatures one by one
+        assert!(blockchain.verify_block_signatures_batched(&block));
+        assert!(verify_one_by_one(&blockchain, &tx));
+        assert!(blockchain.verify_block(&block, 0x207FFFFF_u32));
+
+        // a well-formed signature made for another input
+        let unlock_script = tx.get_inputs()[16].get_unlock_script().clone();
+        tx.set_unlock_script(17, unlock_script);
+        tx.update_digest();
+        let block = new_block(&blockchain, &tx);
+        assert!(!verify_one_by_one(&blockchain, &tx));
+        assert!(!blockchain.verify_block_signatures_batched(&block));
+        assert!(!blockchain.verify_block(&block, 0x207FFFFF_u32));
+        let expected = format!(
+            "invalid signature of input 17 of transaction {}",
+            tx.get_transaction_id()
+        );
+        assert!(captured_warnings().contains(&expected));
+    }
+
+    /// a toy proof-of-work hash (FNV-1a, stretched to 32 bytes),
+    /// counting how many times it is called
+    struct MockHasher {
+        calls: AtomicUsize,
+    }
+
+    impl Hasher for MockHasher {
+        fn digest(&self, data: &[u8]) -> HashValue {
+            const FNV_PRIME: u64 = 0x100000001b3;
+            self.calls.fetch_add(1, atomic::Ordering::Relaxed);
+            // every byte of the data, the nonce included, affects every byte of the hash
+            let mut state = data.iter().fold(0xcbf29ce484222325u64, |state, byte| {
+                (state ^ *byte as u64).wrapping_mul(FNV_PRIME)
+            });
+            let mut hash = [0u8; 32];
+            for chunk in hash.chunks_mut(8) {
+                state = (state ^ (state >> 29)).wrapping_mul(FNV_PRIME);
+                chunk.copy_from_slice(&state.to_be_bytes());
+            }
+            HashValue::new(hash)
+        }
+    }
 
     #[test]
-    fn test_new_blockchain() {
+    fn test_pluggable_hasher() {
         let blockchain = Blockchain::new("hello world");
-        println!("{:?}", blockchain);
+        let hasher = MockHasher {
+            calls: AtomicUsize::new(0),
+        };
+        let mut block = blockchain
+            .generate_new_block(
+                vec![],
+                "0.1v test".to_string(),
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs(),
+                0x207FFFFF_u32,
+                Some(vec![]),
+            )
+            .unwrap();
+        assert!(blockchain.verify_block(&block, 0x207FFFFF_u32));
+        assert_eq!(block.pow_hash(&Sha256dHasher), block.hash);
+
+        // mine the same block with the mock hasher, find a nonce it accepts
+        block.nonce = 0;
+        block.update_hash_and_nonce_with(&hasher);
+        assert_eq!(block.hash, block.pow_hash(&hasher));
+        assert!(blockchain.verify_block_with(&block, 0x207FFFFF_u32, &hasher));
+        assert!(hasher.calls.load(atomic::Ordering::Relaxed) > 0);
+
+        // on multiple threads as well, through a boxed hasher
+        let boxed: Box<dyn Hasher + Sync> = Box::new(MockHasher {
+            calls: AtomicUsize::new(0),
+        });
+        block.nonce = 0;
+        block.mine_parallel_with(4, boxed.as_ref());
+        assert!(blockchain.verify_block_with(&block, 0x207FFFFF_u32, boxed.as_ref()));
+
+        // the default verification only accepts double SHA256
+        assert_ne!(block.hash, block.pow_hash(&Sha256dHasher));
+        assert!(!blockchain.verify_block(&block, 0x207FFFFF_u32));
     }
 
     #[test]
-    fn test_reward_algorithm() {
-        let reward = Blockchain::reward_algorithm(1844674407370955161);
-        println!("{}", reward);
+    fn test_amount_overflow() {
+        let blockchain = Blockchain::new("hello world");
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let address = Wallet::new().get_address();
+
+        // the outputs of the coinbase transaction overflow when summed up
+        let coinbase = Blockchain::create_coinbase_transaction(
+            vec![(address, Decimal::MAX), (address, Decimal::MAX)],
+            blockchain.len(),
+            0,
+        )
+        .unwrap();
+        assert!(!blockchain.verify_transactions(&[coinbase], blockchain.len(), now));
+
+        // so do the fees of the packed transactions
+        let mut tx = Transaction::new(
+            vec![],
+            vec![],
+            HashValue::new([0u8; 32]),
+            Decimal::MAX,
+            None,
+        );
+        tx.update_digest();
+        assert!(matches!(
+            blockchain.expected_coinbase_reward(&[tx.clone(), tx]),
+            Err(RustyCoinError::AmountOverflow)
+        ));
+
+        // and the amounts sent by a wallet
+        assert!(matches!(
+            Wallet::new().send(vec![(Decimal::MAX, address), (Decimal::MAX, address)]),
+            Err(RustyCoinError::AmountOverflow)
+        ));
     }
 
     #[test]
-    fn test_generate_new_block() {
+    fn test_estimate_fee_rate() {
         let mut blockchain = Blockchain::new("hello world");
-        let latest_reward_fee = blockchain.get_latest_reward(&[]);
-        sleep(std::time::Duration::from_secs(1)); // simulate the time before creating a new block
-        let block = blockchain.generate_new_block(
-            vec![(HashValue::new([0u8; 32]), latest_reward_fee)],
-            "0.1v test".to_string(),
+        assert_eq!(blockchain.estimate_fee_rate(1), DEFAULT_MIN_RELAY_FEE_RATE);
+
+        let secret_key = SecretKey::from_slice(&[7u8; 32]).unwrap();
+        let wallet = Wallet::from_secret_key(secret_key);
+        let now = || {
             SystemTime::now()
                 .duration_since(UNIX_EPOCH)
                 .unwrap()
-                .as_secs(), // for the test, set the timestamp to 10 seconds later
-            0x1E123456_u32,
-            vec![],
+                .as_secs()
+        };
+        blockchain.add_block(
+            blockchain
+                .generate_new_block(
+                    vec![(wallet.get_address(), dec!(5.0)); 10],
+                    "0.1v test".to_string(),
+                    now(),
+                    0x207FFFFF_u32,
+                    Some(vec![]),
+                )
+                .unwrap(),
         );
-        sleep(std::time::Duration::from_secs(1)); //simulate the time gap between mining and verifying process
+        mine_blocks(&mut blockchain, COINBASE_MATURITY);
 
-        let verification_res = blockchain.verify_block(&block, 0x1E123456_u32);
+        // 10 transactions paying 0.1, 0.2, ..., 1.0, packed into 2 blocks
+        let coinbase = blockchain.get_block(1).unwrap().data[0].clone();
+        let txs = (0..10)
+            .map(|output_index| {
+                let fee = Decimal::new(output_index as i64 + 1, 1);
+                let input = Input::new(coinbase.get_transaction_id(), 1, output_index, vec![]);
+                TransactionBuilder::new()
+                    .add_input(input, dec!(5.0))
+                    .add_output(
+                        Output::new(dec!(5.0) - fee, wallet.get_address().to_vec()).unwrap(),
+                    )
+                    .build_signed(|tx, input_index| {
+                        Some(Input::generate_unlock_script(
+                            tx,
+                            input_index,
+                            secret_key,
+                            wallet.get_public_key(),
+                        ))
+                    })
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        for chunk in txs.chunks(5) {
+            let block = blockchain
+                .generate_new_block(
+                    vec![],
+                    "0.1v test".to_string(),
+                    now(),
+                    0x207FFFFF_u32,
+                    Some(chunk.to_vec()),
+                )
+                .unwrap();
+            assert!(blockchain.verify_block(&block, 0x207FFFFF_u32));
+            blockchain.add_block(block);
+        }
 
-        assert!(verification_res);
+        // the median for the next block, lower fee rates for a longer wait
+        let fee_rates = txs.iter().map(Transaction::fee_rate).collect::<Vec<_>>();
+        assert!(fee_rates.is_sorted());
+        assert_eq!(blockchain.estimate_fee_rate(1), fee_rates[4]);
+        assert_eq!(blockchain.estimate_fee_rate(0), fee_rates[4]);
+        assert_eq!(blockchain.estimate_fee_rate(4), fee_rates[1]);
+        assert_eq!(blockchain.estimate_fee_rate(100), fee_rates[0]);
+        for target_blocks in 1..20 {
+            let estimate = blockchain.estimate_fee_rate(target_blocks);
+            assert!(fee_rates[0] <= estimate && estimate <= fee_rates[4]);
+            assert!(estimate >= blockchain.estimate_fee_rate(target_blocks + 1));
+        }
 
-        blockchain.add_block(block);
-        println!("{}", blockchain);
+        // the fee rates fall out of the window after `FEE_ESTIMATE_WINDOW` blocks
+        mine_blocks(&mut blockchain, FEE_ESTIMATE_WINDOW);
+        blockchain.set_min_relay_fee_rate(dec!(0.001));
+        assert_eq!(blockchain.estimate_fee_rate(1), dec!(0.001));
     }
 
     #[test]
-    fn test_resolve_conflicts() {
+    fn test_prune() {
         let mut blockchain = Blockchain::new("hello world");
-        let mut blockchain_copied = blockchain.clone();
-
-        let tx1 = Transaction::new(
-            vec![],
-            vec![Output::new(dec!(50.0), HashValue::new([0u8; 32]).to_vec())],
-            HashValue::new([0_u8; 32]),
-            dec!(0.0),
-            Some("test fake tx 1".as_bytes().to_vec()),
-        );
-
-        let tx2 = Transaction::new(
-            vec![],
-            vec![Output::new(dec!(50.0), HashValue::new([0u8; 32]).to_vec())],
-            HashValue::new([1_u8; 32]),
-            dec!(0.0),
-            None,
+        let secret_key = SecretKey::from_slice(&[7u8; 32]).unwrap();
+        let wallet = Wallet::from_secret_key(secret_key);
+        let now = || {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+        };
+        blockchain.add_block(
+            blockchain
+                .generate_new_block(
+                    vec![(wallet.get_address(), dec!(50.0))],
+                    "0.1v test".to_string(),
+                    now(),
+                    0x207FFFFF_u32,
+                    Some(vec![]),
+                )
+                .unwrap(),
         );
+        mine_blocks(&mut blockchain, COINBASE_MATURITY);
+        let coinbase_id = blockchain.get_block(1).unwrap().data[0].get_transaction_id();
+        let headers = blockchain.iter().map(Block::header).collect::<Vec<_>>();
+        let utxo_commitment = blockchain.utxo_commitment();
 
-        blockchain.add_block(
-            blockchain.generate_new_block(
-                vec![(HashValue::new([0u8; 32]), dec!(50.0))],
-                "0.1v test".to_string(),
-                SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs(),
-                0x1E123456_u32,
-                vec![tx1],
-            ),
+        blockchain.prune(10);
+        assert_eq!(blockchain.get_pruned_height(), blockchain.len() - 10);
+        assert!(blockchain.get_block(1).unwrap().data.is_empty());
+        assert_eq!(blockchain.get_last_block().unwrap().data.len(), 1);
+        assert!(blockchain.find_transaction(coinbase_id).is_none());
+        // the headers and the UTXO set are kept
+        assert_eq!(
+            blockchain.iter().map(Block::header).collect::<Vec<_>>(),
+            headers
         );
+        assert_eq!(blockchain.utxo_commitment(), utxo_commitment);
+        assert!(!blockchain
+            .self_check()
+            .contains(&HealthIssue::UtxoSetMismatch));
 
-        blockchain_copied.add_block(
-            blockchain_copied.generate_new_block(
-                vec![(HashValue::new([0u8; 32]), dec!(50.0))],
+        // the output created in a pruned block is still found, from the UTXO set alone,
+        // and a new block spending it is still verified
+        let utxos = blockchain.get_utxos_for(&wallet.get_address(), 0);
+        assert_eq!(utxos.len(), 1);
+        assert_eq!(utxos[0].prev_tx_hash, coinbase_id);
+        assert_eq!(utxos[0].get_amount(), dec!(50.0));
+        let tx = wallet
+            .transfer_credits(utxos, vec![(dec!(49.0), wallet.get_address())], None)
+            .unwrap();
+        let block = blockchain
+            .generate_new_block(
+                vec![],
                 "0.1v test".to_string(),
-                SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs(),
-                0x1E123456_u32,
-                vec![tx2],
-            ),
+                now(),
+                0x207FFFFF_u32,
+                Some(vec![tx.clone()]),
+            )
+            .unwrap();
+        assert!(blockchain.verify_block(&block, 0x207FFFFF_u32));
+        blockchain.add_block(block);
+        assert_eq!(
+            blockchain.get_balance(&wallet.get_address()).unwrap(),
+            dec!(49.0)
         );
 
-        let before_resolve = blockchain.clone();
+        // pruning never goes backwards
+        blockchain.prune(usize::MAX);
+        assert_eq!(blockchain.get_pruned_height(), blockchain.len() - 11);
+    }
 
-        let res = blockchain.resolve_conflicts(&blockchain_copied.blockchain);
-        // [Blockchain]:
-        // Block[0]:
-        // 	version: 0.1v test
-        // 	timestamp: 1709725083
-        // 	prev_hash: 0x0000000000000000000000000000000000000000000000000000000000000000
-        // 	hash: 0xb3fa77116dffb01cd560a6d246fd6c82ebcfe27b18bed173986170c7ad2d2244
-        // 	merkle_root: 0xae058a070f5ce7e44d1d6a54f5011e69a6aead1993409e8a7d79ef53abbe5bf2
-        // 	difficulty: 0
-        // 	nonce: 0
-        // 	data: [
-        // 		Transaction ID: 0xae058a070f5ce7e44d1d6a54f5011e69a6aead1993409e8a7d79ef53abbe5bf2
-        // 		Transaction Fee: 0.0
-        // 		Inputs:
-        // 		Outputs:
-        // 		Additional Data: Some([104, 101, 108, 108, 111, 32, 119, 111, 114, 108, 100])
-        // 	]
-        //
-        //
-        //
-        // Block[1]:
-        // 	version: 0.1v test
-        // 	timestamp: 1709725083
-        // 	prev_hash: 0xb3fa77116dffb01cd560a6d246fd6c82ebcfe27b18bed173986170c7ad2d2244
-        // 	hash: 0x00000b5bb6e201e16fdb7a8a1d373da12496db001a3df865941acaa321b23525
-        // 	merkle_root: 0x8923412372d89abca3f2863712f46a2b488cbdf70aa535a2b9a1f8fae0bc756c
-        // 	difficulty: 504509526
-        // 	nonce: 925788
-        // 	data: [
-        // 		Transaction ID: 0xb0768ef4fb2d549a1aa450415481ea40862d460f038792e8e4c0af03a47b5a87
-        // 		Transaction Fee: 0.0
-        // 		Inputs:
-        // 		Outputs:
-        // 			Amount: 50.0
-        // 			Length of Locking Script: 32
-        // 			Locking Script: 0x0000000000000000000000000000000000000000000000000000000000000000
-        // 		Additional Data: None
-        // 		Transaction ID: 0x0000000000000000000000000000000000000000000000000000000000000000
-        // 		Transaction Fee: 0.0
-        // 		Inputs:
-        // 		Outputs:
-        // 			Amount: 50.0
-        // 			Length of Locking Script: 32
-        // 			Locking Script: 0x0000000000000000000000000000000000000000000000000000000000000000
-        // 		Additional Data: Some([116, 101, 115, 116, 32, 102, 97, 107, 101, 32, 116, 120, 32, 49])
-        // 	]
+    #[test]
+    fn test_try_from_json() {
+        let mut blockchain = Blockchain::new("hello world");
+        let secret_key = SecretKey::from_slice(&[7u8; 32]).unwrap();
+        let wallet = Wallet::from_secret_key(secret_key);
+        blockchain.add_block(
+            blockchain
+                .generate_new_block(
+                    vec![(wallet.get_address(), dec!(50.0))],
+                    "0.1v test".to_string(),
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs(),
+                    0x207FFFFF_u32,
+                    Some(vec![]),
+                )
+                .unwrap(),
+        );
+        mine_blocks(&mut blockchain, COINBASE_MATURITY);
+        let mut wallet = wallet;
+        wallet.update_unspent_tx_outputs(blockchain.get_utxos_for(&wallet.get_address(), 0));
+        let tx = wallet
+            .send(vec![(dec!(20.0), Wallet::new().get_address())])
+            .unwrap();
+        blockchain.add_to_pool(tx.clone()).unwrap();
 
-        println!("{}", blockchain);
+        let json = serde_json::to_string(&blockchain).unwrap();
+        assert_eq!(Blockchain::try_from_json(&json).unwrap(), blockchain);
 
-        assert_eq!(before_resolve.blockchain, blockchain.blockchain);
+        // the derived state and the pool are not trusted
+        let mut untrusted = blockchain.clone();
+        untrusted.utxo_set = UtxoSet::new();
+        untrusted.tx_index.clear();
+        let mut forged_tx = tx.clone();
+        forged_tx.set_unlock_script(0, vec![0u8; 98]);
+        untrusted.tx_pool.push(forged_tx);
+        let rebuilt =
+            Blockchain::try_from_json(&serde_json::to_string(&untrusted).unwrap()).unwrap();
+        assert_eq!(rebuilt, blockchain);
+        assert_eq!(rebuilt.get_tx_pool(), &[tx]);
 
-        assert!(!res);
+        // a tampered block rejects the whole chain
+        let mut tampered = blockchain.clone();
+        tampered.blockchain[1].data_mut()[0] = Blockchain::create_coinbase_transaction(
+            vec![(Wallet::new().get_address(), dec!(50.0))],
+            1,
+            0,
+        )
+        .unwrap();
+        assert!(matches!(
+            Blockchain::try_from_json(&serde_json::to_string(&tampered).unwrap()),
+            Err(RustyCoinError::InvalidChain)
+        ));
+        assert!(matches!(
+            Blockchain::try_from_json("{\"blockchain\": 1}"),
+            Err(RustyCoinError::InvalidChain)
+        ));
     }
 }
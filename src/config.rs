@@ -0,0 +1,62 @@
+//! configuration shared by all the nodes of a rusty coin network
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+/// the time between two blocks the difficulty is retargeted toward by default, in seconds
+pub const DEFAULT_TARGET_BLOCK_INTERVAL_SECS: u64 = 10;
+
+/// everything the genesis block is built from, see `Blockchain::new_with_genesis`.
+///
+/// nodes created from the same config get the same genesis block,
+/// so they belong to the same network and can resolve conflicts with each other.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GenesisConfig {
+    pub version: String, // protocol version of the genesis block
+    pub timestamp: u64,  // timestamp of the genesis block, in seconds since the Unix Epoch
+    pub difficulty: u32, // difficulty of the genesis block, in nBits format
+    pub message: String, // message of the genesis transaction
+    pub chain_id: u32, // mixed into the transaction hashes, so transactions can't be replayed on other networks
+    #[serde(default = "default_target_block_interval_secs")]
+    pub target_block_interval_secs: u64, // the time between two blocks the difficulty is retargeted toward
+    #[serde(default)]
+    pub testnet: bool, // a local test network, coins can be minted with `Blockchain::mint_to`
+}
+
+fn default_target_block_interval_secs() -> u64 {
+    DEFAULT_TARGET_BLOCK_INTERVAL_SECS
+}
+
+/// how the transaction fees of a block are split between its miner and the void,
+/// see `Blockchain::set_fee_policy`.
+///
+/// the coinbase transaction can claim the block reward plus `miner_share` of the fees,
+/// inflated by 3% if `inflate_fees` is set, the rest of the fees is burned.
+/// all the nodes of a network must agree on it, or they reject each other's blocks.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeePolicy {
+    pub miner_share: Decimal, // the fraction of the fees paid to the miner, between 0 and 1
+    pub inflate_fees: bool,   // whether the miner's share of the fees is inflated by 3%
+}
+
+impl FeePolicy {
+    /// burn `fraction` of the fees, the rest is paid to the miner without inflation,
+    /// `None` if `fraction` is not between 0 and 1
+    pub fn burn(fraction: Decimal) -> Option<Self> {
+        (Decimal::ZERO..=Decimal::ONE)
+            .contains(&fraction)
+            .then(|| FeePolicy {
+                miner_share: Decimal::ONE - fraction,
+                inflate_fees: false,
+            })
+    }
+}
+
+impl Default for FeePolicy {
+    /// all the fees are paid to the miner, inflated by 3%
+    fn default() -> Self {
+        FeePolicy {
+            miner_share: Decimal::ONE,
+            inflate_fees: true,
+        }
+    }
+}
@@ -8,26 +8,189 @@ pub enum RustyCoinError {
     InvalidBlockIndex,
     InvalidTransactionIndex,
     InvalidOutputAmount,
+    InvalidTransaction,
+    DuplicateTransaction,
+    DoubleSpend,
+    InsufficientFunds,
+    InvalidAddress,
+    InvalidMnemonic,
+    AmountOverflow,
+    InvalidSignature,
+    InvalidChain,
+    InvalidPrivateKey,
+    NoConflictingTransaction,
+    InsufficientFee,
+    InvalidHex { expected_len: usize },
+    MerkleRootMismatch,
+    MintingDisabled,
+    DuplicateInput,
+    AdditionalDataTooLarge { size: usize },
+    FeeRateTooLow,
+    NoOutputForSigHashSingle,
+    InvalidDataOutput { size: usize },
 }
 
+impl Display for RustyCoinError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RustyCoinError::InvalidOutputIndex => {
+                write!(
+                    f,
+                    "the output index does not exist in the previous transaction"
+                )
+            }
+            RustyCoinError::InvalidInputFee => {
+                write!(f, "the inputs can't cover the outputs of the transaction")
+            }
+            RustyCoinError::InvalidBlockIndex => write!(f, "the block index does not exist"),
+            RustyCoinError::InvalidTransactionIndex => {
+                write!(f, "the transaction index does not exist in the block")
+            }
+            RustyCoinError::InvalidOutputAmount => write!(f, "the output amount is negative"),
+            RustyCoinError::InvalidTransaction => write!(f, "the transaction is invalid"),
+            RustyCoinError::DuplicateTransaction => {
+                write!(f, "the transaction is already in the transaction pool")
+            }
+            RustyCoinError::DoubleSpend => write!(
+                f,
+                "the transaction spends an output already spent in the transaction pool"
+            ),
+            RustyCoinError::InsufficientFunds => {
+                write!(f, "the wallet does not have enough funds")
+            }
+            RustyCoinError::InvalidAddress => {
+                write!(f, "the address is malformed or its checksum does not match")
+            }
+            RustyCoinError::InvalidMnemonic => {
+                write!(f, "the mnemonic has an unknown word or a wrong checksum")
+            }
+            RustyCoinError::AmountOverflow => write!(f, "the sum of the amounts overflows"),
+            RustyCoinError::InvalidSignature => {
+                write!(f, "the signature must be exactly 65 bytes")
+            }
+            RustyCoinError::InvalidChain => write!(f, "the blockchain is malformed or invalid"),
+            RustyCoinError::InvalidPrivateKey => {
+                write!(
+                    f,
+                    "the private key is malformed or its checksum does not match"
+                )
+            }
+            RustyCoinError::NoConflictingTransaction => {
+                write!(
+                    f,
+                    "no transaction in the transaction pool spends the same outputs"
+                )
+            }
+            RustyCoinError::InsufficientFee => write!(
+                f,
+                "the transaction fee must be higher than the fees of the replaced transactions"
+            ),
+            RustyCoinError::InvalidHex { expected_len } => write!(
+                f,
+                "expected {expected_len} hex digits, with an optional 0x prefix"
+            ),
+            RustyCoinError::MerkleRootMismatch => write!(
+                f,
+                "the merkle root of the transactions does not match the block header"
+            ),
+            RustyCoinError::MintingDisabled => {
+                write!(f, "coins can only be minted on a testnet")
+            }
+            RustyCoinError::DuplicateInput => {
+                write!(f, "two inputs of the transaction spend the same output")
+            }
+            RustyCoinError::AdditionalDataTooLarge { size } => write!(
+                f,
+                "the additional data of {size} bytes exceeds the limit of the transaction"
+            ),
+            RustyCoinError::FeeRateTooLow => write!(
+                f,
+                "the fee rate of the transaction is below the minimum relay fee rate"
+            ),
+            RustyCoinError::NoOutputForSigHashSingle => write!(
+                f,
+                "the input is signed with SIGHASH_SINGLE, but there is no output at its index"
+            ),
+            RustyCoinError::InvalidDataOutput { size } => write!(
+                f,
+                "a data output can't carry {size} bytes, it would be too long or taken for an address"
+            ),
+        }
+    }
+}
+
+impl Error for RustyCoinError {}
+
 #[derive(Debug)]
-pub struct InvalidTransactionInput(String);
+pub struct InvalidTransactionInput(pub String);
 
 impl Display for InvalidTransactionInput {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Invalid transaction input")
+        write!(f, "Invalid transaction input: {}", self.0)
     }
 }
 
 impl Error for InvalidTransactionInput {}
 
 #[derive(Debug)]
-pub struct InvalidTransactionOutput(String);
+pub struct InvalidTransactionOutput(pub String);
 
 impl Display for InvalidTransactionOutput {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Invalid transaction input")
+        write!(f, "Invalid transaction output: {}", self.0)
     }
 }
 
 impl Error for InvalidTransactionOutput {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_messages() {
+        let errors = [
+            RustyCoinError::InvalidOutputIndex,
+            RustyCoinError::InvalidInputFee,
+            RustyCoinError::InvalidBlockIndex,
+            RustyCoinError::InvalidTransactionIndex,
+            RustyCoinError::InvalidOutputAmount,
+            RustyCoinError::InvalidTransaction,
+            RustyCoinError::DuplicateTransaction,
+            RustyCoinError::DoubleSpend,
+            RustyCoinError::InsufficientFunds,
+            RustyCoinError::InvalidAddress,
+            RustyCoinError::InvalidMnemonic,
+            RustyCoinError::AmountOverflow,
+            RustyCoinError::InvalidSignature,
+            RustyCoinError::InvalidChain,
+            RustyCoinError::InvalidPrivateKey,
+            RustyCoinError::NoConflictingTransaction,
+            RustyCoinError::InsufficientFee,
+            RustyCoinError::InvalidHex { expected_len: 64 },
+            RustyCoinError::MerkleRootMismatch,
+            RustyCoinError::MintingDisabled,
+            RustyCoinError::DuplicateInput,
+            RustyCoinError::AdditionalDataTooLarge { size: 2048 },
+            RustyCoinError::FeeRateTooLow,
+            RustyCoinError::NoOutputForSigHashSingle,
+            RustyCoinError::InvalidDataOutput { size: 30 },
+        ];
+        for error in errors {
+            assert!(!error.to_string().is_empty());
+        }
+
+        // bubble up into a `Box<dyn Error>`
+        let boxed: Box<dyn Error> = RustyCoinError::InsufficientFunds.into();
+        assert_eq!(boxed.to_string(), "the wallet does not have enough funds");
+
+        assert_eq!(
+            InvalidTransactionInput("unknown outpoint".to_string()).to_string(),
+            "Invalid transaction input: unknown outpoint"
+        );
+        assert_eq!(
+            InvalidTransactionOutput("negative amount".to_string()).to_string(),
+            "Invalid transaction output: negative amount"
+        );
+    }
+}
@@ -0,0 +1,33 @@
+use crate::types::HashValue;
+use sha2::{Digest, Sha256};
+
+/// The hash algorithm of the proof of work.
+///
+/// rusty coin mines with `Sha256dHasher`, other algorithms can be plugged into
+/// `Block::update_hash_and_nonce_with`, `Block::mine_parallel_with` and
+/// `Blockchain::verify_block_with` for experimentation.
+pub trait Hasher {
+    /// hash the given bytes
+    fn digest(&self, data: &[u8]) -> HashValue;
+}
+
+/// double SHA256, the default hash algorithm of the proof of work
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sha256dHasher;
+
+impl Hasher for Sha256dHasher {
+    fn digest(&self, data: &[u8]) -> HashValue {
+        HashValue::new(Sha256::digest(Sha256::digest(data)).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sha256d_hasher() {
+        let once = HashValue::new(Sha256::digest(b"hello world").into());
+        assert_eq!(Sha256dHasher.digest(b"hello world"), once.sha256());
+    }
+}
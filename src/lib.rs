@@ -1,7 +1,11 @@
 //! Implementation of a blockchain
 pub mod block;
 pub mod blockchain;
+pub mod config;
 pub mod errors;
+pub mod hasher;
+pub mod node;
 pub mod transaction;
 pub mod types;
+pub mod utxo_set;
 pub mod wallet;
@@ -1,28 +1,262 @@
 //! Implementation of a bitcoin-like system
-use axum::routing::get;
-use axum::Router;
+use rust_decimal::Decimal;
+use rusty_coin::block::Block;
+use rusty_coin::blockchain::Blockchain;
+use rusty_coin::config::{GenesisConfig, DEFAULT_TARGET_BLOCK_INTERVAL_SECS};
+use rusty_coin::node::{join_network, ping_peers, router, NodeState, MAX_BLOCKS_PER_REQUEST};
+use rusty_coin::transaction::Transaction;
+use rusty_coin::types::HashValue;
+use rusty_coin::wallet::Wallet;
 use std::net::SocketAddr;
 use std::option::Option;
+use std::process::ExitCode;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+const USAGE: &str = "usage:
+  rusty_coin run-node [--peer <addr>] [--port <n>] [--mine <address>]
+  rusty_coin new-wallet --out <path>
+  rusty_coin balance --wallet <path> --peer <addr>
+  rusty_coin send --wallet <path> --to <address> --amount <n> --peer <addr>
+  rusty_coin self-check <path>";
+
+/// the difficulty of the genesis block of `run-node`, in nBits format,
+/// the blocks are mined at it until the first retarget, see `Blockchain::next_difficulty`
+const MINING_DIFFICULTY: u32 = 0x1F00FFFF;
+
+/// the timestamp of the genesis block of `run-node`, fixed so that every node builds the same genesis block
+/// and can sync with the others
+const GENESIS_TIMESTAMP: u64 = 1_700_000_000;
 
 #[tokio::main]
-async fn main() {
-    const ROOT_NODE_ADDR: Option<String> = None;
+async fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().collect();
+    let flags = args.get(2..).unwrap_or_default();
 
-    match ROOT_NODE_ADDR {
-        Some(addr) => {
-            println!("Requesting {addr} for peers addresses");
+    match args.get(1).map(String::as_str) {
+        Some("self-check") => self_check(args.get(2)),
+        Some("run-node") => {
+            run_node(
+                flag(flags, "--peer"),
+                flag(flags, "--port"),
+                flag(flags, "--mine"),
+            )
+            .await
         }
-        None => {
-            println!("This node will run as the root node");
+        Some("new-wallet") => new_wallet(flag(flags, "--out")),
+        Some("balance") => balance(flag(flags, "--wallet"), flag(flags, "--peer")).await,
+        Some("send") => {
+            send(
+                flag(flags, "--wallet"),
+                flag(flags, "--to"),
+                flag(flags, "--amount"),
+                flag(flags, "--peer"),
+            )
+            .await
+        }
+        None => run_node(None, None, None).await,
+        Some(_) => {
+            eprintln!("{USAGE}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// the value following `name` in the flags, e.g. `flag(["--port", "8080"], "--port") == Some("8080")`
+fn flag<'a>(flags: &'a [String], name: &str) -> Option<&'a str> {
+    flags
+        .iter()
+        .position(|arg| arg == name)
+        .and_then(|position| flags.get(position + 1))
+        .map(String::as_str)
+}
+
+/// `new-wallet --out <path>`: create a new wallet and save its private key to a file
+fn new_wallet(out: Option<&str>) -> ExitCode {
+    let Some(out) = out else {
+        eprintln!("{USAGE}");
+        return ExitCode::FAILURE;
+    };
+
+    let wallet = Wallet::new();
+    if let Err(e) = wallet.save_private_key_to_file(out) {
+        eprintln!("Failed to write the key file {out}: {e}");
+        return ExitCode::FAILURE;
+    }
+    println!("{}", wallet.address_string());
+    ExitCode::SUCCESS
+}
+
+/// `balance --wallet <path> --peer <addr>`: the balance of a wallet on the chain of a node
+async fn balance(wallet: Option<&str>, peer: Option<&str>) -> ExitCode {
+    let (Some(wallet), Some(peer)) = (wallet, peer) else {
+        eprintln!("{USAGE}");
+        return ExitCode::FAILURE;
+    };
+
+    let (wallet, blockchain) = match load_wallet_and_chain(wallet, peer).await {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    match blockchain.get_balance(&wallet.get_address()) {
+        Ok(balance) => println!("{balance}"),
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+/// `send --wallet <path> --to <address> --amount <n> --peer <addr>`:
+/// pay an address from a wallet, and submit the transaction to a node
+async fn send(
+    wallet: Option<&str>,
+    to: Option<&str>,
+    amount: Option<&str>,
+    peer: Option<&str>,
+) -> ExitCode {
+    let (Some(wallet), Some(to), Some(amount), Some(peer)) = (wallet, to, amount, peer) else {
+        eprintln!("{USAGE}");
+        return ExitCode::FAILURE;
+    };
+    let Ok(to) = HashValue::from_address_string(to) else {
+        eprintln!("Invalid address: {to}");
+        return ExitCode::FAILURE;
+    };
+    let Ok(amount) = Decimal::from_str(amount) else {
+        eprintln!("Invalid amount: {amount}");
+        return ExitCode::FAILURE;
+    };
+
+    let (mut wallet, blockchain) = match load_wallet_and_chain(wallet, peer).await {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    wallet.scan_chain(&blockchain);
+    wallet.set_chain_id(blockchain.get_chain_id());
+    let tx = match wallet.send(vec![(amount, to)]) {
+        Ok(tx) => tx,
+        Err(e) => {
+            eprintln!("Failed to create the transaction: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match submit_transaction(peer, &tx).await {
+        Ok(()) => {
+            println!("{}", tx.get_transaction_id());
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("Failed to submit the transaction to {peer}: {e}");
+            ExitCode::FAILURE
         }
     }
+}
 
-    let app = Router::new().route("/ping", get(pong));
+/// read a wallet from its key file, and fetch the chain of a node from its `/chain` endpoint
+async fn load_wallet_and_chain(
+    path: &str,
+    peer: &str,
+) -> Result<(Wallet, Blockchain), Box<dyn std::error::Error>> {
+    let wallet = Wallet::build_from_private_key_file(path)
+        .map_err(|e| format!("Failed to read the key file {path}: {e}"))?;
 
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:0").await.unwrap();
+    let response = reqwest::get(format!("{peer}/chain"))
+        .await?
+        .error_for_status()?;
+    let chain: Vec<Block> = serde_json::from_str(&response.text().await?)?;
+    if !Blockchain::verify_chain(&chain) {
+        return Err(format!("{peer} responded with an invalid chain").into());
+    }
+    Ok((wallet, Blockchain::from_vec(&chain)))
+}
+
+/// post a transaction to the `/tx` endpoint of a node
+async fn submit_transaction(
+    peer: &str,
+    tx: &Transaction,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let response = reqwest::Client::new()
+        .post(format!("{peer}/tx"))
+        .header("content-type", "application/json")
+        .body(serde_json::to_string(tx)?)
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        return Err(response.text().await?.into());
+    }
+    Ok(())
+}
+
+/// `self-check <path>`: run the consistency checks over a blockchain saved by `Blockchain::save_to_file`
+fn self_check(path: Option<&String>) -> ExitCode {
+    let Some(path) = path else {
+        eprintln!("usage: rusty_coin self-check <path to blockchain file>");
+        return ExitCode::FAILURE;
+    };
+
+    let blockchain = match Blockchain::load_from_file(path) {
+        Ok(blockchain) => blockchain,
+        Err(e) => {
+            eprintln!("Failed to read blockchain from {path}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let issues = blockchain.self_check();
+    if issues.is_empty() {
+        println!("The blockchain is healthy");
+        return ExitCode::SUCCESS;
+    }
+
+    for issue in &issues {
+        println!("{issue}");
+    }
+    println!("{} issue(s) found", issues.len());
+    ExitCode::FAILURE
+}
+
+/// `run-node [--peer <addr>] [--port <n>] [--mine <address>]`: run a node on the port (a random one by default),
+/// pass the address of a root node (e.g. "http://127.0.0.1:8080") to join its network,
+/// and an address to mine blocks paying their rewards to it
+async fn run_node(peer: Option<&str>, port: Option<&str>, mine: Option<&str>) -> ExitCode {
+    let Ok(port) = port.map_or(Ok(0), u16::from_str) else {
+        eprintln!("Invalid port: {}", port.unwrap());
+        return ExitCode::FAILURE;
+    };
+    let Ok(reward_address) = mine.map(HashValue::from_address_string).transpose() else {
+        eprintln!("Invalid address: {}", mine.unwrap());
+        return ExitCode::FAILURE;
+    };
+
+    let state = NodeState::new(Blockchain::new_with_genesis(&GenesisConfig {
+        version: "0.1v test".to_string(),
+        timestamp: GENESIS_TIMESTAMP,
+        difficulty: MINING_DIFFICULTY,
+        message: "Default Blockchain".to_string(),
+        chain_id: 0,
+        target_block_interval_secs: DEFAULT_TARGET_BLOCK_INTERVAL_SECS,
+        testnet: false,
+    }));
+    let listener = match tokio::net::TcpListener::bind(("0.0.0.0", port)).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Failed to listen on port {port}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let port = listener.local_addr().unwrap().port();
 
     if let SocketAddr::V4(addr4) = listener.local_addr().unwrap() {
-        let port = addr4.port();
         let ip = addr4.ip();
         println!("Listening on http://{ip}:{port}");
         println!("Listening on localhost http://127.0.0.1:{port}");
@@ -30,24 +264,46 @@ async fn main() {
         println!("you should get a \"pong\" in response");
     }
 
-    let server = axum::serve(listener, app);
-
-    server.await.unwrap();
-}
+    match peer {
+        Some(addr) => {
+            println!("Requesting {addr} for peers addresses");
+            let own_addr = format!("http://127.0.0.1:{port}");
+            match join_network(&state, addr, &own_addr).await {
+                Ok(()) => println!("Known peers: {:?}", state.peers.read().await),
+                Err(e) => eprintln!("Failed to join the network through {addr}: {e}"),
+            }
+            // a dead peer is only reported, it is pruned once it keeps failing
+            let peers = state.peers.read().await.iter().cloned().collect();
+            let results = ping_peers(peers).await;
+            for (peer, result) in &results {
+                if let Err(e) = result {
+                    eprintln!("Failed to ping {peer}: {e}");
+                }
+            }
+            state.record_peer_results(&results).await;
+            // catch up with the chain of the root node
+            let mut blockchain = state.blockchain.write().await;
+            match blockchain.sync_range(addr, MAX_BLOCKS_PER_REQUEST).await {
+                Ok(changed) => println!("Synced from {addr}, chain changed: {changed}"),
+                Err(e) => eprintln!("Failed to sync the chain from {addr}: {e}"),
+            }
+        }
+        None => {
+            println!("This node will run as the root node");
+        }
+    }
 
-/// A simple ping-pong function
-async fn pong() -> &'static str {
-    "pong"
-}
+    let shutdown = Arc::new(AtomicBool::new(false));
+    if let Some(reward_address) = reward_address {
+        println!("Mining to {}", reward_address.to_address_string());
+        Blockchain::spawn_miner(state.blockchain.clone(), reward_address, shutdown.clone());
+    }
 
+    let app = router(state);
 
+    let server = axum::serve(listener, app);
 
-#[allow(dead_code)]
-async fn ping(addr: &str) -> Result<String, Box<dyn std::error::Error>> {
-    let url = format!("http://{addr}/ping");
-    let response = reqwest::get(url).await?.text().await?;
-    println!("{response}");
-    Ok(response)
+    server.await.unwrap();
+    shutdown.store(true, Ordering::Relaxed);
+    ExitCode::SUCCESS
 }
-
-
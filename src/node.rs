@@ -0,0 +1,397 @@
+//! HTTP interface of a rusty coin node
+use crate::block::{Block, BlockHeader};
+use crate::blockchain::Blockchain;
+use crate::transaction::Transaction;
+use crate::types::HashValue;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use log::{info, warn};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+/// the most headers served by one `/headers` request
+pub const MAX_HEADERS_PER_REQUEST: usize = 2000;
+/// the most blocks served by one `/blocks` request
+pub const MAX_BLOCKS_PER_REQUEST: usize = 100;
+/// the most consecutive failures to contact a peer before it is removed from the known peers
+pub const MAX_PEER_FAILURES: usize = 3;
+/// how long to wait for a peer to respond, so a dead peer doesn't hold up the others
+const PEER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// the state shared by all the handlers of a node
+#[derive(Clone)]
+pub struct NodeState {
+    pub blockchain: Arc<RwLock<Blockchain>>, // the local copy of the blockchain
+    pub peers: Arc<RwLock<BTreeSet<String>>>, // base urls of the known peers, e.g. "http://127.0.0.1:8080"
+    pub peer_failures: Arc<RwLock<BTreeMap<String, usize>>>, // base url -> consecutive failures to contact it
+}
+
+impl NodeState {
+    pub fn new(blockchain: Blockchain) -> Self {
+        NodeState {
+            blockchain: Arc::new(RwLock::new(blockchain)),
+            peers: Arc::new(RwLock::new(BTreeSet::new())),
+            peer_failures: Arc::new(RwLock::new(BTreeMap::new())),
+        }
+    }
+
+    /// add a peer by its base url, return false if it is already known
+    pub async fn add_peer(&self, addr: String) -> bool {
+        self.peers.write().await.insert(addr)
+    }
+
+    /// record the results of contacting peers, e.g. of `broadcast_block`:
+    /// a success resets the failures of the peer,
+    /// a peer failing `MAX_PEER_FAILURES` times in a row is removed from the known peers
+    pub async fn record_peer_results<T>(&self, results: &[(String, Result<T, reqwest::Error>)]) {
+        let mut peers = self.peers.write().await;
+        let mut peer_failures = self.peer_failures.write().await;
+        for (peer, result) in results {
+            if result.is_ok() {
+                peer_failures.remove(peer);
+                continue;
+            }
+            let failures = peer_failures.entry(peer.clone()).or_insert(0);
+            *failures += 1;
+            if *failures >= MAX_PEER_FAILURES {
+                warn!("removed the peer {peer}, it failed to respond {failures} times in a row");
+                peer_failures.remove(peer);
+                peers.remove(peer);
+            }
+        }
+    }
+}
+
+/// summary of the node's view of the blockchain, served at `/info`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeInfo {
+    pub height: usize,              // index of the latest block
+    pub last_block_hash: HashValue, // hash of the latest block
+    pub utxo_count: usize,          // number of unspent transaction outputs
+    pub utxo_commitment: HashValue, // commitment hash of the UTXO set
+}
+
+/// query of `/headers`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeadersQuery {
+    pub from: Option<usize>, // index of the first header, 0 by default
+}
+
+/// query of `/blocks`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlocksQuery {
+    pub from: Option<usize>,  // index of the first block, 0 by default
+    pub count: Option<usize>, // number of blocks, capped at `MAX_BLOCKS_PER_REQUEST`, the cap by default
+}
+
+/// build the router of a node with all its routes
+pub fn router(state: NodeState) -> Router {
+    Router::new()
+        .route("/ping", get(pong))
+        .route("/info", get(info))
+        .route("/height", get(height))
+        .route("/block/:index", get(block_by_index))
+        .route("/headers", get(headers))
+        .route("/blocks", get(blocks))
+        .route("/chain", get(chain))
+        .route("/estimatefee/:blocks", get(estimate_fee))
+        .route("/tx", post(submit_transaction))
+        .route("/block", post(submit_block))
+        .route("/peers", get(peers).post(register_peer))
+        .with_state(state)
+}
+
+/// A simple ping-pong function
+async fn pong() -> &'static str {
+    "pong"
+}
+
+async fn info(State(state): State<NodeState>) -> Json<NodeInfo> {
+    let blockchain = state.blockchain.read().await;
+    let last_block = blockchain.get_last_block().unwrap();
+
+    Json(NodeInfo {
+        height: last_block.index,
+        last_block_hash: last_block.hash,
+        utxo_count: blockchain.get_utxo_set().len(),
+        utxo_commitment: blockchain.utxo_commitment(),
+    })
+}
+
+/// number of blocks in the chain, including the genesis block
+async fn height(State(state): State<NodeState>) -> Json<usize> {
+    Json(state.blockchain.read().await.len())
+}
+
+/// the block at `index`, 404 if the index is out of range,
+/// 410 if the transactions of the block have been pruned, see `Blockchain::prune`
+async fn block_by_index(
+    State(state): State<NodeState>,
+    Path(index): Path<usize>,
+) -> Result<Json<Block>, StatusCode> {
+    let blockchain = state.blockchain.read().await;
+    if index < blockchain.get_pruned_height() {
+        return Err(StatusCode::GONE);
+    }
+    match blockchain.get_block(index) {
+        Some(block) => Ok(Json(block.clone())),
+        None => Err(StatusCode::NOT_FOUND),
+    }
+}
+
+/// all the blocks of the chain, from the genesis block on,
+/// for a new node to catch up with `Blockchain::sync_from_peer`.
+/// 410 if the node has pruned blocks, their transactions can't be served, see `Blockchain::prune`
+async fn chain(State(state): State<NodeState>) -> Result<Json<Vec<Block>>, StatusCode> {
+    let blockchain = state.blockchain.read().await;
+    if blockchain.get_pruned_height() > 0 {
+        return Err(StatusCode::GONE);
+    }
+    Ok(Json(blockchain.iter().cloned().collect()))
+}
+
+/// the blocks from `from` on, at most `count` and `MAX_BLOCKS_PER_REQUEST` of them,
+/// for a node to catch up range by range with `Blockchain::sync_range`.
+/// 410 if some of them have been pruned, see `Blockchain::prune`
+async fn blocks(
+    State(state): State<NodeState>,
+    Query(query): Query<BlocksQuery>,
+) -> Result<Json<Vec<Block>>, StatusCode> {
+    let blockchain = state.blockchain.read().await;
+    let from = query.from.unwrap_or(0);
+    let count = query
+        .count
+        .unwrap_or(MAX_BLOCKS_PER_REQUEST)
+        .min(MAX_BLOCKS_PER_REQUEST);
+    if count > 0 && from < blockchain.get_pruned_height() {
+        return Err(StatusCode::GONE);
+    }
+    let blocks = blockchain.iter().skip(from).take(count).cloned().collect();
+    Ok(Json(blocks))
+}
+
+/// the fee rate suggested to be confirmed within `blocks` blocks,
+/// see `Blockchain::estimate_fee_rate`
+async fn estimate_fee(State(state): State<NodeState>, Path(blocks): Path<usize>) -> Json<Decimal> {
+    Json(state.blockchain.read().await.estimate_fee_rate(blocks))
+}
+
+/// add a transaction to the transaction pool, then relay it to all the peers,
+/// 202 with the transaction ID if accepted, 400 with the reason if rejected
+async fn submit_transaction(
+    State(state): State<NodeState>,
+    Json(tx): Json<Transaction>,
+) -> Result<(StatusCode, Json<HashValue>), (StatusCode, String)> {
+    // a malformed transaction is rejected before waiting for the chain
+    tx.is_valid_structure()
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    let tx_id = tx.get_transaction_id();
+    if let Err(e) = state.blockchain.write().await.add_to_pool(tx.clone()) {
+        return Err((StatusCode::BAD_REQUEST, e.to_string()));
+    }
+
+    // a peer rejects a transaction already in its pool, so the relay stops there
+    let peers = state.peers.read().await.iter().cloned().collect();
+    tokio::spawn(async move {
+        let results = broadcast_transaction(peers, tx).await;
+        state.record_peer_results(&results).await;
+    });
+    Ok((StatusCode::ACCEPTED, Json(tx_id)))
+}
+
+/// the headers of the blocks from `from` on, at most `MAX_HEADERS_PER_REQUEST` of them,
+/// for headers-first sync without the transactions
+async fn headers(
+    State(state): State<NodeState>,
+    Query(query): Query<HeadersQuery>,
+) -> Json<Vec<BlockHeader>> {
+    let blockchain = state.blockchain.read().await;
+    let headers = blockchain
+        .iter()
+        .skip(query.from.unwrap_or(0))
+        .take(MAX_HEADERS_PER_REQUEST)
+        .map(Block::header)
+        .collect();
+    Json(headers)
+}
+
+/// add a block announced by a peer or a miner to the chain, then announce it to all the peers:
+/// - 202 with the block hash if the block extends the chain
+/// - 202 with the block hash if the block is beyond the tip, it is kept as an orphan
+///   until its parent arrives, see `Blockchain::add_orphan`, and not announced
+/// - 200 with the block hash if the block is already the tip, it is not announced again,
+///   so an announcement stops once every node has the block
+/// - 400 if the block is invalid or doesn't extend the chain
+async fn submit_block(
+    State(state): State<NodeState>,
+    Json(block): Json<Block>,
+) -> Result<(StatusCode, Json<HashValue>), (StatusCode, String)> {
+    let block_hash = block.hash;
+    // a malformed transaction is rejected before waiting for the chain
+    for tx in &block.data {
+        tx.is_valid_structure()
+            .map_err(|e| (StatusCode::BAD_REQUEST, format!("invalid block: {e}")))?;
+    }
+    {
+        let mut blockchain = state.blockchain.write().await;
+        if blockchain
+            .get_last_block()
+            .is_some_and(|last_block| last_block.header() == block.header())
+        {
+            return Ok((StatusCode::OK, Json(block_hash)));
+        }
+        if block.index > blockchain.len() {
+            // the parent may still be on its way, keep the block until it arrives
+            blockchain.add_orphan(block);
+            return Ok((StatusCode::ACCEPTED, Json(block_hash)));
+        }
+        if block.index != blockchain.len() {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "the block doesn't extend the chain".to_string(),
+            ));
+        }
+        // the block must be mined at the difficulty the chain requires, not the one it claims
+        let difficulty = blockchain.next_difficulty();
+        blockchain
+            .add_block_checked(block.clone(), difficulty)
+            .map_err(|e| (StatusCode::BAD_REQUEST, format!("invalid block: {e}")))?;
+        blockchain.try_connect_orphans();
+    }
+    info!("accepted block {}: {}", block.index, block_hash);
+
+    let peers = state.peers.read().await.iter().cloned().collect();
+    tokio::spawn(async move {
+        let results = broadcast_block(peers, block).await;
+        state.record_peer_results(&results).await;
+    });
+    Ok((StatusCode::ACCEPTED, Json(block_hash)))
+}
+
+/// announce a block to the peers at `POST /block`.
+///
+/// every peer is contacted even if some fail to respond, the failures are logged,
+/// returns the result of each peer, a peer rejecting the block still responded
+pub async fn broadcast_block(
+    peers: Vec<String>,
+    block: Block,
+) -> Vec<(String, Result<(), reqwest::Error>)> {
+    let body = serde_json::to_string(&block).unwrap();
+    let results = post_to_peers(peers, "block", body).await;
+    for (peer, result) in &results {
+        if let Err(e) = result {
+            warn!("failed to announce block {} to {peer}: {e}", block.index);
+        }
+    }
+    results
+}
+
+/// relay a transaction to the peers at `POST /tx`, see `broadcast_block`
+pub async fn broadcast_transaction(
+    peers: Vec<String>,
+    tx: Transaction,
+) -> Vec<(String, Result<(), reqwest::Error>)> {
+    let body = serde_json::to_string(&tx).unwrap();
+    let results = post_to_peers(peers, "tx", body).await;
+    for (peer, result) in &results {
+        if let Err(e) = result {
+            warn!(
+                "failed to relay transaction {} to {peer}: {e}",
+                tx.get_transaction_id()
+            );
+        }
+    }
+    results
+}
+
+/// ping the peers at `/ping`, returns the result of each peer, continuing past the failures
+pub async fn ping_peers(peers: Vec<String>) -> Vec<(String, Result<String, reqwest::Error>)> {
+    let client = peer_client();
+    let mut results = Vec::with_capacity(peers.len());
+    for peer in peers {
+        let response = match client.get(format!("{peer}/ping")).send().await {
+            Ok(response) => response.text().await,
+            Err(e) => Err(e),
+        };
+        results.push((peer, response));
+    }
+    results
+}
+
+/// post the same json body to `path` of every peer, continuing past the failures
+async fn post_to_peers(
+    peers: Vec<String>,
+    path: &str,
+    body: String,
+) -> Vec<(String, Result<(), reqwest::Error>)> {
+    let client = peer_client();
+    let mut results = Vec::with_capacity(peers.len());
+    for peer in peers {
+        let response = client
+            .post(format!("{peer}/{path}"))
+            .header("content-type", "application/json")
+            .body(body.clone())
+            .send()
+            .await;
+        results.push((peer, response.map(|_| ())));
+    }
+    results
+}
+
+/// an http client giving up on a peer after `PEER_TIMEOUT`
+fn peer_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(PEER_TIMEOUT)
+        .build()
+        .unwrap()
+}
+
+/// the base urls of the known peers
+async fn peers(State(state): State<NodeState>) -> Json<Vec<String>> {
+    Json(state.peers.read().await.iter().cloned().collect())
+}
+
+/// register a peer by its base url, 201 if it is new, 200 if it is already known
+async fn register_peer(State(state): State<NodeState>, Json(addr): Json<String>) -> StatusCode {
+    if state.add_peer(addr).await {
+        StatusCode::CREATED
+    } else {
+        StatusCode::OK
+    }
+}
+
+/// join the network through a root node:
+/// - ask the root node for its peers and add them, together with the root node, to the known peers
+/// - register this node at the root node with its own base url `own_addr`
+pub async fn join_network(
+    state: &NodeState,
+    root_addr: &str,
+    own_addr: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let client = reqwest::Client::new();
+    let response = client.get(format!("{root_addr}/peers")).send().await?;
+    let root_peers: Vec<String> = serde_json::from_str(&response.text().await?)?;
+
+    for peer in root_peers
+        .into_iter()
+        .chain([root_addr.to_string()])
+        .filter(|peer| peer != own_addr)
+    {
+        state.add_peer(peer).await;
+    }
+
+    client
+        .post(format!("{root_addr}/peers"))
+        .header("content-type", "application/json")
+        .body(serde_json::to_string(own_addr)?)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
@@ -1,11 +1,77 @@
+use crate::blockchain::Blockchain;
+use crate::errors::RustyCoinError;
 use crate::types::{bytes_vec_to_hex_string, HashValue};
+use crate::utxo_set::OutPoint;
 use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use secp256k1::constants::{COMPACT_SIGNATURE_SIZE, PUBLIC_KEY_SIZE};
 use secp256k1::{ecdsa::Signature, Message, PublicKey, SecretKey};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
 use std::fmt::Display;
 use std::hash::{Hash, Hasher};
 
+/// amounts are counted in the smallest unit, 10^-8 rusty coin,
+/// an amount with more decimal places doesn't correspond to any real denomination
+pub const AMOUNT_DECIMAL_PLACES: u32 = 8;
+/// the smallest amount an output created by `Output::new` can carry, smaller outputs are dust
+pub const MIN_OUTPUT_AMOUNT: Decimal = dec!(0.00000001);
+
+/// a lock time below it is a block index, otherwise it is a timestamp in seconds
+pub const LOCKTIME_THRESHOLD: u64 = 500_000_000;
+/// the sequence of an input by default, if all the inputs are final the lock time is ignored
+pub const SEQUENCE_FINAL: u32 = u32::MAX;
+/// the largest additional data a transaction can carry, in bytes, see `Transaction::is_valid_structure`
+pub const MAX_ADDITIONAL_DATA_SIZE: usize = 1024;
+
+/// which parts of a transaction a signature commits to, see `Transaction::sighash`.
+///
+/// its byte is appended to the compact signature in the unlocking script,
+/// so the verifier rebuilds the same signing digest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SigHashType {
+    #[default]
+    All = 0x01, // every input and every output
+    None = 0x02,   // every input but no output, anyone can change the outputs
+    Single = 0x03, // every input and only the output at the index of the signed input
+}
+
+impl SigHashType {
+    /// parse the byte appended to a signature, `None` if it is not a known sighash type
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0x01 => Some(SigHashType::All),
+            0x02 => Some(SigHashType::None),
+            0x03 => Some(SigHashType::Single),
+            _ => None,
+        }
+    }
+}
+
+/// size of a signature in an unlocking script, a compact signature followed by its `SigHashType`
+pub const SIGNATURE_SIZE: usize = COMPACT_SIGNATURE_SIZE + 1;
+
+/// split a signature of an unlocking script into the compact signature and its sighash type,
+/// `None` if the sighash type is missing or unknown
+fn split_sighash_type(signature: &[u8]) -> Option<(&[u8], SigHashType)> {
+    let (&byte, compact_signature) = signature.split_last()?;
+    Some((compact_signature, SigHashType::from_byte(byte)?))
+}
+
+/// check if an amount can be counted in the smallest unit, i.e. has at most `AMOUNT_DECIMAL_PLACES` decimal places
+pub fn is_valid_amount_precision(amount: Decimal) -> bool {
+    amount.round_dp(AMOUNT_DECIMAL_PLACES) == amount
+}
+
+/// sum up amounts, return `AmountOverflow` instead of panicking if the sum overflows `Decimal`
+pub fn sum_amounts(amounts: impl IntoIterator<Item = Decimal>) -> Result<Decimal, RustyCoinError> {
+    amounts
+        .into_iter()
+        .try_fold(Decimal::ZERO, |sum, amount| sum.checked_add(amount))
+        .ok_or(RustyCoinError::AmountOverflow)
+}
+
 /// Represents a transaction in the blockchain.
 /// Pay2PubKeyHash(P2PKH) is used as the locking script.
 #[derive(Debug, Eq, PartialOrd, PartialEq, Clone, Serialize, Deserialize)]
@@ -15,6 +81,8 @@ pub struct Transaction {
     transaction_id: HashValue,        // hash value of this transaction.
     transaction_fee: Decimal,         // difference between inputs and outputs.
     additional_data: Option<Vec<u8>>, // Any additional data associated with the transaction.
+    lock_time: u64, // the block index or timestamp the transaction is locked until, 0 if not locked
+    chain_id: u32,  // the network the transaction is valid on, see `GenesisConfig::chain_id`
 }
 
 impl Transaction {
@@ -40,6 +108,8 @@ impl Transaction {
             transaction_id,
             transaction_fee,
             additional_data,
+            lock_time: 0,
+            chain_id: 0,
         }
     }
     /// Calculates the SHA256 hash of the transaction.
@@ -56,6 +126,7 @@ impl Transaction {
             hasher.update(input.prev_output_index.to_be_bytes());
             hasher.update(input.length_of_unlock_script.to_be_bytes());
             hasher.update(&input.unlock_script);
+            hasher.update(input.sequence.to_be_bytes());
         }
 
         for output in &self.outputs {
@@ -65,13 +136,73 @@ impl Transaction {
         }
 
         hasher.update(serde_json::to_vec(&self.transaction_fee).unwrap());
-        if self.additional_data.is_some() {
-            hasher.update(&self.additional_data.clone().unwrap());
+        if let Some(additional_data) = &self.additional_data {
+            hasher.update(additional_data);
+        }
+        hasher.update(self.lock_time.to_be_bytes());
+        hasher.update(self.chain_id.to_be_bytes());
+        let result = hasher.finalize().into();
+        HashValue::new(result)
+    }
+
+    /// Calculates the digest that is signed by the unlocking script of an input.
+    ///
+    /// the digest commits to every input's outpoint and sequence, the additional data, the lock time,
+    /// the chain ID, the index of the input being signed and the sighash type,
+    /// so a signature can not be replayed on another network. the outputs committed to depend on
+    /// `sighash_type`:
+    /// - `All`: every output and the transaction fee, the funds can not be redirected
+    /// - `None`: no output, nor the fee, as it changes with the outputs
+    /// - `Single`: only the output at `input_index`, nor the fee,
+    ///   such a signature is invalid if the transaction has no output at that index,
+    ///   see `Transaction::can_sign_with`
+    ///
+    /// unlocking scripts are excluded, as they contain the signatures themselves,
+    /// so the digest is the same before and after any input is signed.
+    pub fn sighash(&self, input_index: usize, sighash_type: SigHashType) -> HashValue {
+        let mut hasher = Sha256::new();
+
+        for input in &self.inputs {
+            hasher.update(input.prev_transaction_hash);
+            hasher.update(input.prev_block_index.to_be_bytes());
+            hasher.update(input.prev_output_index.to_be_bytes());
+            hasher.update(input.sequence.to_be_bytes());
+        }
+
+        let outputs = match sighash_type {
+            SigHashType::All => &self.outputs[..],
+            SigHashType::None => &[],
+            SigHashType::Single => self
+                .outputs
+                .get(input_index..=input_index)
+                .unwrap_or_default(),
+        };
+        for output in outputs {
+            hasher.update(serde_json::to_vec(&output.amount).unwrap());
+            hasher.update(output.length_of_locking_script.to_be_bytes());
+            hasher.update(&output.locking_script);
+        }
+
+        if sighash_type == SigHashType::All {
+            hasher.update(serde_json::to_vec(&self.transaction_fee).unwrap());
         }
+        if let Some(additional_data) = &self.additional_data {
+            hasher.update(additional_data);
+        }
+        hasher.update(self.lock_time.to_be_bytes());
+        hasher.update(self.chain_id.to_be_bytes());
+        hasher.update(input_index.to_be_bytes());
+        hasher.update([sighash_type as u8]);
         let result = hasher.finalize().into();
         HashValue::new(result)
     }
 
+    /// check if the input at `input_index` can be signed with `sighash_type`:
+    /// a `Single` signature needs an output at the index of the input
+    pub fn can_sign_with(&self, input_index: usize, sighash_type: SigHashType) -> bool {
+        sighash_type != SigHashType::Single || input_index < self.outputs.len()
+    }
+
     /// update transaction ID of this transaction:
     /// * transaction id is the SHA256 of the transaction
     /// * calculate the SHA256 of this transaction, and assign it to the `transaction_id` field
@@ -86,12 +217,64 @@ impl Transaction {
     pub fn get_output_by_index(&self, index: usize) -> Option<&Output> {
         self.outputs.get(index)
     }
-    /// get the transaction fee of this transaction
+    /// get the additional data of this transaction, e.g. the height committed by a coinbase transaction
+    pub fn get_additional_data(&self) -> Option<&[u8]> {
+        self.additional_data.as_deref()
+    }
+    /// get the transaction fee of this transaction, as declared by its creator
     /// # Returns
     /// * `transaction_fee` - the transaction fee of this transaction
+    ///
+    /// the declared fee is not trustworthy until the transaction is verified,
+    /// use `compute_fee` to derive the real fee from the blockchain.
     pub fn get_transaction_fee(&self) -> Decimal {
         self.transaction_fee
     }
+    /// the size of the transaction in bytes, as serialized into a block (bincode)
+    pub fn size(&self) -> usize {
+        bincode::serialized_size(self).unwrap() as usize
+    }
+    /// the transaction fee paid per byte of the transaction,
+    /// miners prefer the transactions paying the highest fee rate as block space is limited
+    pub fn fee_rate(&self) -> Decimal {
+        self.transaction_fee / Decimal::from(self.size())
+    }
+    /// derive the transaction fee from the blockchain:
+    /// the sum of the amounts of the unspent outputs spent by the inputs, minus the sum of the outputs.
+    ///
+    /// # Errors
+    /// * `InvalidOutputIndex` - an input does not refer to an output in the UTXO set
+    /// * `InvalidOutputAmount` - an output amount is negative
+    /// * `InvalidInputFee` - the inputs can't cover the outputs
+    /// * `AmountOverflow` - the sum of the amounts overflows
+    pub fn compute_fee(&self, blockchain: &Blockchain) -> Result<Decimal, RustyCoinError> {
+        let mut fee = Decimal::ZERO;
+        for input in &self.inputs {
+            let outpoint = OutPoint::new(input.prev_transaction_hash, input.prev_output_index);
+            match blockchain.get_utxo_set().get(&outpoint) {
+                Some(entry) if entry.block_index == input.prev_block_index => {
+                    fee = fee
+                        .checked_add(entry.output.get_amount())
+                        .ok_or(RustyCoinError::AmountOverflow)?
+                }
+                _ => return Err(RustyCoinError::InvalidOutputIndex),
+            }
+        }
+
+        for output in &self.outputs {
+            if output.get_amount() < Decimal::ZERO {
+                return Err(RustyCoinError::InvalidOutputAmount);
+            }
+            fee = fee
+                .checked_sub(output.get_amount())
+                .ok_or(RustyCoinError::AmountOverflow)?;
+        }
+
+        if fee < Decimal::ZERO {
+            return Err(RustyCoinError::InvalidInputFee);
+        }
+        Ok(fee)
+    }
     pub fn get_inputs(&self) -> &Vec<Input> {
         &self.inputs
     }
@@ -101,45 +284,485 @@ impl Transaction {
     pub fn get_transaction_id(&self) -> HashValue {
         self.transaction_id
     }
+    /// set the unlocking script of one input, used when signing a drafted transaction.
+    ///
+    /// the transaction ID is not updated, call `update_digest` after all the inputs are signed.
+    pub fn set_unlock_script(&mut self, input_index: usize, unlock_script: Vec<u8>) {
+        if let Some(input) = self.inputs.get_mut(input_index) {
+            input.length_of_unlock_script = unlock_script.len();
+            input.unlock_script = unlock_script;
+        }
+    }
+    /// check if the output amounts and the declared fee can be counted in the smallest unit,
+    /// see `is_valid_amount_precision`
+    pub fn has_valid_amount_precision(&self) -> bool {
+        is_valid_amount_precision(self.transaction_fee)
+            && self
+                .outputs
+                .iter()
+                .all(|output| is_valid_amount_precision(output.amount))
+    }
+
+    /// a cheap sanity check of the transaction on its own, before looking up the chain or the UTXO set,
+    /// e.g. for a transaction received from the network:
+    /// - `InvalidTransaction` - the transaction ID is not the hash of the transaction
+    /// - `AdditionalDataTooLarge` - the additional data exceeds `MAX_ADDITIONAL_DATA_SIZE`
+    /// - `DuplicateInput` - two inputs spend the same output
+    /// - `InvalidOutputAmount` - an output amount is negative
+    pub fn is_valid_structure(&self) -> Result<(), RustyCoinError> {
+        if self.transaction_id != self.sha256() {
+            return Err(RustyCoinError::InvalidTransaction);
+        }
+        if let Some(additional_data) = &self.additional_data {
+            if additional_data.len() > MAX_ADDITIONAL_DATA_SIZE {
+                return Err(RustyCoinError::AdditionalDataTooLarge {
+                    size: additional_data.len(),
+                });
+            }
+        }
+        let mut outpoints = BTreeSet::new();
+        if !self.inputs.iter().all(|input| {
+            outpoints.insert(OutPoint::new(
+                input.prev_transaction_hash,
+                input.prev_output_index,
+            ))
+        }) {
+            return Err(RustyCoinError::DuplicateInput);
+        }
+        if self.outputs.iter().any(|output| output.amount < dec!(0.0)) {
+            return Err(RustyCoinError::InvalidOutputAmount);
+        }
+        Ok(())
+    }
+
+    /// get the lock time: a block index if below `LOCKTIME_THRESHOLD`, otherwise a timestamp in seconds
+    pub fn get_lock_time(&self) -> u64 {
+        self.lock_time
+    }
+
+    /// lock the transaction until a block index or a timestamp, see `Transaction::is_final`.
+    /// the transaction ID and the signatures commit to the lock time, so set it before signing.
+    pub fn set_lock_time(&mut self, lock_time: u64) {
+        self.lock_time = lock_time;
+    }
+
+    /// get the ID of the network the transaction is valid on, see `GenesisConfig::chain_id`
+    pub fn get_chain_id(&self) -> u32 {
+        self.chain_id
+    }
+
+    /// bind the transaction to the network with the chain ID `chain_id`, so it can't be replayed on another one.
+    /// the transaction ID and the signatures commit to the chain ID, so set it before signing.
+    pub fn set_chain_id(&mut self, chain_id: u32) {
+        self.chain_id = chain_id;
+    }
+
+    /// check if the transaction can be packed into the block at `block_index` mined at `block_time`:
+    /// - a lock time of 0 never locks the transaction
+    /// - a lock time below `LOCKTIME_THRESHOLD` is reached by the blocks with a larger index
+    /// - otherwise it is a timestamp, reached by the blocks with a later timestamp
+    /// - if all the inputs have the sequence `SEQUENCE_FINAL`, the lock time is ignored
+    pub fn is_final(&self, block_index: usize, block_time: u64) -> bool {
+        if self.lock_time == 0 {
+            return true;
+        }
+        let reached = if self.lock_time < LOCKTIME_THRESHOLD {
+            block_index as u64 > self.lock_time
+        } else {
+            block_time > self.lock_time
+        };
+        reached
+            || self
+                .inputs
+                .iter()
+                .all(|input| input.sequence == SEQUENCE_FINAL)
+    }
+
     /// if the inputs of this transaction is empty, then it is a coinbase transaction
     pub fn is_coinbase_transaction(tx: &Transaction) -> bool {
         tx.get_inputs().is_empty()
     }
+    /// verify the unlocking script
+    /// one must provide the unlocking script and the corresponding locking script.
+    /// the spending transaction and the index of the input need to be provided,
+    /// as the signatures are made over the signing digest of that input.
+    ///
+    /// the unlocking script is run first, it may only push data onto the stack,
+    /// then the locking script is run on the same stack,
+    /// the scripts are valid if the top of the stack is true at the end.
+    ///
+    /// a 32 bytes locking script is a P2PKH address, it stands for the template
+    /// `OP_DUP OP_HASH256 <address> OP_EQUALVERIFY OP_CHECKSIG`,
+    /// whose unlocking script is a 64 bytes compact signature and its `SigHashType` byte,
+    /// followed by a 33 bytes compressed public key.
+    ///
+    /// malformed scripts never panic, they are simply rejected.
     pub fn verify_scripts(
-        prev_transaction: &Transaction,
+        spending_transaction: &Transaction,
+        input_index: usize,
+        unlocking_script: &[u8],
+        locking_script: &[u8],
+    ) -> bool {
+        Self::verify_scripts_with(
+            spending_transaction,
+            input_index,
+            unlocking_script,
+            locking_script,
+            false,
+        )
+    }
+
+    /// same as `verify_scripts`, but if `signature_verified` is true, the P2PKH signature
+    /// of the input has already been checked by `SignatureCheck::verify` and is not checked again
+    pub(crate) fn verify_scripts_with(
+        spending_transaction: &Transaction,
+        input_index: usize,
         unlocking_script: &[u8],
         locking_script: &[u8],
+        signature_verified: bool,
     ) -> bool {
-        let (signature, public_key) = unlocking_script.split_at(64);
+        let mut interpreter = ScriptInterpreter::new(spending_transaction, input_index);
 
-        // verify public key
-        let mut hasher = Sha256::new();
-        hasher.update(public_key);
-        let result: [u8; 32] = hasher.finalize().into();
+        let locking_script = if locking_script.len() == P2PKH_ADDRESS_SIZE {
+            let p2pkh_unlocking_script_size = SIGNATURE_SIZE + PUBLIC_KEY_SIZE;
+            if unlocking_script.len() == p2pkh_unlocking_script_size {
+                let (signature, public_key) = unlocking_script.split_at(SIGNATURE_SIZE);
+                if signature_verified {
+                    interpreter.verified_signature = Some((signature, public_key));
+                }
+                interpreter.stack.push(signature.to_vec());
+                interpreter.stack.push(public_key.to_vec());
+            } else if interpreter.run(unlocking_script, true).is_none() {
+                return false;
+            }
+            Output::generate_p2pkh_script(locking_script)
+        } else {
+            if interpreter.run(unlocking_script, true).is_none() {
+                return false;
+            }
+            locking_script.to_vec()
+        };
 
-        // check if the hash of public key is the same as the locking script
-        if result.to_vec() != *locking_script {
-            return false;
+        interpreter.run(&locking_script, false).is_some()
+            && interpreter.stack.last().is_some_and(|top| is_true(top))
+    }
+
+    /// extract the signature check of a P2PKH input, so that it can be verified apart
+    /// from the script interpreter, e.g. together with the other signatures of a block.
+    ///
+    /// returns `None` if the locking script is not a P2PKH address, or the unlocking script
+    /// of the input is not a well-formed signature followed by a public key,
+    /// such inputs are left to `verify_scripts`.
+    pub fn p2pkh_signature_check(
+        &self,
+        input_index: usize,
+        locking_script: &[u8],
+    ) -> Option<SignatureCheck> {
+        let unlocking_script = self.inputs.get(input_index)?.get_unlock_script();
+        if locking_script.len() != P2PKH_ADDRESS_SIZE
+            || unlocking_script.len() != SIGNATURE_SIZE + PUBLIC_KEY_SIZE
+        {
+            return None;
+        }
+        let (signature, public_key) = unlocking_script.split_at(SIGNATURE_SIZE);
+        let (signature, sighash_type) = split_sighash_type(signature)?;
+        if !self.can_sign_with(input_index, sighash_type) {
+            return None;
         }
+        Some(SignatureCheck {
+            msg: Message::from_digest(*self.sighash(input_index, sighash_type)),
+            signature: Signature::from_compact(signature).ok()?,
+            public_key: PublicKey::from_slice(public_key).ok()?,
+        })
+    }
+}
 
-        // verify signature
-        let msg = Message::from_digest(*prev_transaction.sha256());
-        let signature = Signature::from_compact(signature).unwrap();
+/// a signature to be checked over the signing digest of an input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SignatureCheck {
+    pub msg: Message,          // the signing digest of the input
+    pub signature: Signature,  // the compact signature
+    pub public_key: PublicKey, // the public key the signature is checked against
+}
 
-        // deserialize public key
-        let public_key = match PublicKey::from_slice(public_key) {
-            Ok(public_key) => public_key,
-            Err(e) => {
-                println!("Error: {}", e);
-                return false;
+impl SignatureCheck {
+    pub fn verify(&self) -> bool {
+        self.signature.verify(&self.msg, &self.public_key).is_ok()
+    }
+}
+
+/// size of a P2PKH address, the SHA256 of a public key
+const P2PKH_ADDRESS_SIZE: usize = 32;
+
+// the opcodes of the script, a minimal subset of bitcoin's script.
+// besides them, the bytes 0x01..=0x4b push the next 1..=75 bytes onto the stack.
+pub const OP_0: u8 = 0x00; // push an empty item, i.e. false or the number 0
+pub const OP_1: u8 = 0x51; // OP_1..=OP_16 push the numbers 1..=16
+pub const OP_16: u8 = 0x60;
+pub const OP_RETURN: u8 = 0x6a; // fail the script, marks a provably unspendable data output
+pub const OP_DUP: u8 = 0x76; // duplicate the top item
+pub const OP_EQUALVERIFY: u8 = 0x88; // pop two items, fail if they are not equal
+pub const OP_HASH256: u8 = 0xaa; // replace the top item with its SHA256, the hash of P2PKH addresses
+pub const OP_CHECKSIG: u8 = 0xac; // pop a public key and a signature, push if the signature is valid
+pub const OP_CHECKMULTISIG: u8 = 0xae; // pop n, n public keys, m, m signatures, push if all the signatures are valid
+/// the longest data a single push opcode can push
+pub const MAX_PUSH_SIZE: usize = 0x4b;
+
+/// a stack-based interpreter running the scripts of one input of a spending transaction
+struct ScriptInterpreter<'a> {
+    spending_transaction: &'a Transaction, // the transaction the signatures are made over
+    input_index: usize,                    // the input being unlocked
+    stack: Vec<Vec<u8>>,                   // the data stack
+    verified_signature: Option<(&'a [u8], &'a [u8])>, // a signature and public key already checked
+}
+
+impl<'a> ScriptInterpreter<'a> {
+    fn new(spending_transaction: &'a Transaction, input_index: usize) -> Self {
+        ScriptInterpreter {
+            spending_transaction,
+            input_index,
+            stack: vec![],
+            verified_signature: None,
+        }
+    }
+
+    /// run a script on the stack, return `None` if the script fails.
+    ///
+    /// a `push_only` script may only contain push opcodes
+    fn run(&mut self, script: &[u8], push_only: bool) -> Option<()> {
+        let mut pc = 0;
+        while pc < script.len() {
+            let opcode = script[pc];
+            pc += 1;
+            match opcode {
+                OP_0 => self.stack.push(vec![]),
+                0x01..=0x4b => {
+                    let data = script.get(pc..pc + opcode as usize)?;
+                    self.stack.push(data.to_vec());
+                    pc += opcode as usize;
+                }
+                OP_1..=OP_16 => self.stack.push(vec![opcode - OP_1 + 1]),
+                _ if push_only => return None,
+                OP_RETURN => return None,
+                OP_DUP => {
+                    let top = self.stack.last()?.clone();
+                    self.stack.push(top);
+                }
+                OP_HASH256 => {
+                    let top = self.stack.pop()?;
+                    self.stack.push(Sha256::digest(top).to_vec());
+                }
+                OP_EQUALVERIFY => {
+                    let a = self.stack.pop()?;
+                    let b = self.stack.pop()?;
+                    if !bytes_eq(&a, &b) {
+                        return None;
+                    }
+                }
+                OP_CHECKSIG => {
+                    let public_key = self.stack.pop()?;
+                    let signature = self.stack.pop()?;
+                    let valid = self.check_signature(&signature, &public_key);
+                    self.stack.push(vec![valid as u8]);
+                }
+                OP_CHECKMULTISIG => {
+                    let n = self.pop_number()?;
+                    let public_keys = self.pop_items(n)?;
+                    let m = self.pop_number()?;
+                    if m > n {
+                        return None;
+                    }
+                    let signatures = self.pop_items(m)?;
+
+                    // the signatures must be in the same order as the public keys
+                    let mut public_keys = public_keys.iter();
+                    let valid = signatures.iter().all(|signature| {
+                        public_keys.any(|public_key| self.check_signature(signature, public_key))
+                    });
+                    self.stack.push(vec![valid as u8]);
+                }
+                _ => return None, // unknown opcode
             }
-        };
+        }
+
+        Some(())
+    }
+
+    /// pop a small number pushed by OP_0..=OP_16
+    fn pop_number(&mut self) -> Option<usize> {
+        match self.stack.pop()?.as_slice() {
+            [] => Some(0),
+            [number] => Some(*number as usize),
+            _ => None,
+        }
+    }
+
+    /// pop `count` items, return them in the order they were pushed
+    fn pop_items(&mut self, count: usize) -> Option<Vec<Vec<u8>>> {
+        let start = self.stack.len().checked_sub(count)?;
+        Some(self.stack.split_off(start))
+    }
 
-        if signature.verify(&msg, &public_key).is_err() {
+    /// check a signature and its sighash type over the signing digest of the input
+    fn check_signature(&self, signature: &[u8], public_key: &[u8]) -> bool {
+        if self
+            .verified_signature
+            .is_some_and(|verified| verified == (signature, public_key))
+        {
+            return true;
+        }
+        let Some((signature, sighash_type)) = split_sighash_type(signature) else {
+            return false;
+        };
+        if !self
+            .spending_transaction
+            .can_sign_with(self.input_index, sighash_type)
+        {
+            return false;
+        }
+        let msg = Message::from_digest(
+            *self
+                .spending_transaction
+                .sighash(self.input_index, sighash_type),
+        );
+        let (Ok(signature), Ok(public_key)) = (
+            Signature::from_compact(signature),
+            PublicKey::from_slice(public_key),
+        ) else {
             return false;
+        };
+
+        signature.verify(&msg, &public_key).is_ok()
+    }
+}
+
+/// compare two stack items, hashes are compared in constant time
+fn bytes_eq(a: &[u8], b: &[u8]) -> bool {
+    match (<[u8; 32]>::try_from(a), <[u8; 32]>::try_from(b)) {
+        (Ok(a), Ok(b)) => HashValue::new(a).ct_eq(&HashValue::new(b)),
+        _ => a == b,
+    }
+}
+
+/// an item is true if it has a non-zero byte
+fn is_true(item: &[u8]) -> bool {
+    item.iter().any(|byte| *byte != 0)
+}
+
+/// the script pushing `data` onto the stack
+///
+/// # Panics
+/// if `data` is longer than `MAX_PUSH_SIZE`
+pub fn push_data_script(data: &[u8]) -> Vec<u8> {
+    match data.len() {
+        0 => vec![OP_0],
+        len if len <= MAX_PUSH_SIZE => [vec![len as u8], data.to_vec()].concat(),
+        _ => panic!("Data too long to push"),
+    }
+}
+
+/// Builds a transaction step by step:
+/// the fee is computed from the amounts of the inputs, and the transaction ID is set by `build`.
+#[derive(Debug, Clone, Default)]
+pub struct TransactionBuilder {
+    inputs: Vec<Input>,               // The inputs for the transaction.
+    input_amounts: Vec<Decimal>,      // amounts of the outputs spent by the inputs
+    outputs: Vec<Output>,             // The outputs for the transaction.
+    additional_data: Option<Vec<u8>>, // Any additional data associated with the transaction.
+    lock_time: u64, // the block index or timestamp the transaction is locked until
+    chain_id: u32,  // the network the transaction is valid on
+}
+
+impl TransactionBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// add an input, together with the amount of the output it spends
+    pub fn add_input(mut self, input: Input, amount: Decimal) -> Self {
+        self.inputs.push(input);
+        self.input_amounts.push(amount);
+        self
+    }
+
+    pub fn add_output(mut self, output: Output) -> Self {
+        self.outputs.push(output);
+        self
+    }
+
+    pub fn additional_data(mut self, additional_data: Vec<u8>) -> Self {
+        self.additional_data = Some(additional_data);
+        self
+    }
+
+    /// lock the transaction until a block index or a timestamp, see `Transaction::is_final`
+    pub fn lock_time(mut self, lock_time: u64) -> Self {
+        self.lock_time = lock_time;
+        self
+    }
+
+    /// bind the transaction to a network, see `Transaction::set_chain_id`
+    pub fn chain_id(mut self, chain_id: u32) -> Self {
+        self.chain_id = chain_id;
+        self
+    }
+
+    /// build the transaction, the fee is the amount of the inputs minus the amount of the outputs.
+    ///
+    /// # Errors
+    /// * `InvalidOutputAmount` - an output amount is negative
+    /// * `InvalidInputFee` - the inputs can't cover the outputs
+    /// * `AmountOverflow` - the sum of the amounts overflows
+    pub fn build(self) -> Result<Transaction, RustyCoinError> {
+        self.build_signed(|_, _| None)
+    }
+
+    /// build the transaction and sign its inputs:
+    /// `sign` is called with the drafted transaction and the index of every input,
+    /// and returns the unlock script of the input, or `None` to leave it as it is.
+    ///
+    /// # Errors
+    /// * `InvalidOutputAmount` - an output amount is negative
+    /// * `InvalidInputFee` - the inputs can't cover the outputs
+    /// * `AmountOverflow` - the sum of the amounts overflows
+    pub fn build_signed<F>(self, sign: F) -> Result<Transaction, RustyCoinError>
+    where
+        F: Fn(&Transaction, usize) -> Option<Vec<u8>>,
+    {
+        if self
+            .outputs
+            .iter()
+            .any(|output| output.get_amount() < Decimal::ZERO)
+        {
+            return Err(RustyCoinError::InvalidOutputAmount);
+        }
+        let input_amount = sum_amounts(self.input_amounts.iter().copied())?;
+        let output_amount = sum_amounts(self.outputs.iter().map(Output::get_amount))?;
+        let transaction_fee = input_amount
+            .checked_sub(output_amount)
+            .ok_or(RustyCoinError::AmountOverflow)?;
+        if transaction_fee < Decimal::ZERO {
+            return Err(RustyCoinError::InvalidInputFee);
+        }
+
+        let mut tx = Transaction::new(
+            self.inputs,
+            self.outputs,
+            HashValue::new([0u8; 32]),
+            transaction_fee,
+            self.additional_data,
+        );
+        tx.set_lock_time(self.lock_time);
+        tx.set_chain_id(self.chain_id);
+        // the signatures commit to the drafted transaction
+        for input_index in 0..tx.inputs.len() {
+            if let Some(unlock_script) = sign(&tx, input_index) {
+                tx.set_unlock_script(input_index, unlock_script);
+            }
         }
+        tx.update_digest();
 
-        true
+        Ok(tx)
     }
 }
 
@@ -166,6 +789,7 @@ impl Display for Transaction {
                 "\tUnlock Script: {}",
                 bytes_vec_to_hex_string(&input.unlock_script)
             )?;
+            writeln!(f, "\tSequence: {}", input.sequence)?;
         }
         writeln!(f, "Outputs:")?;
         for output in &self.outputs {
@@ -182,6 +806,8 @@ impl Display for Transaction {
             )?;
         }
         writeln!(f, "Additional Data: {:?}", self.additional_data)?;
+        writeln!(f, "Lock Time: {}", self.lock_time)?;
+        writeln!(f, "Chain ID: {}", self.chain_id)?;
         Ok(())
     }
 }
@@ -193,10 +819,6 @@ impl Hash for Transaction {
     }
 }
 
-/// verify the unlocking script
-/// one must provide the unlocking script and the corresponding locking script.
-/// previous transaction need to be provided as the transaction hash is needed.
-
 /// Represents an input for a transaction.
 #[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Clone, Serialize, Deserialize)]
 pub struct Input {
@@ -205,6 +827,7 @@ pub struct Input {
     prev_output_index: usize,         // The index of the output in the previous transaction.
     length_of_unlock_script: usize,   // The length of the unlock script.
     unlock_script: Vec<u8>,           // The unlock script: a signature and a public key.
+    sequence: u32, // `SEQUENCE_FINAL` unless the lock time of the transaction applies
 }
 
 impl Input {
@@ -229,6 +852,7 @@ impl Input {
             prev_output_index: output_index,
             length_of_unlock_script,
             unlock_script,
+            sequence: SEQUENCE_FINAL,
         }
     }
 
@@ -244,28 +868,128 @@ impl Input {
     pub fn get_prev_output_index(&self) -> usize {
         self.prev_output_index
     }
+    pub fn get_sequence(&self) -> u32 {
+        self.sequence
+    }
+
+    /// set the sequence of the input, any sequence other than `SEQUENCE_FINAL`
+    /// makes the lock time of the transaction apply, see `Transaction::is_final`
+    pub fn set_sequence(&mut self, sequence: u32) {
+        self.sequence = sequence;
+    }
 
     /// Generates the unlock script for the input.
-    /// an unlock script is a signature of previous transaction and a public key of sender,
-    /// here is separated with a 31 bytes long 0s vector `[0u8;31]`.
+    /// an unlock script is a signature of the spending transaction and a public key of sender.
     /// # Arguments
-    /// * `previous_transaction` - The previous transaction.
+    /// * `spending_transaction` - The drafted transaction that spends this input.
+    /// * `input_index` - The index of this input in the spending transaction.
     /// * `private_key` - The private key of the sender.
     /// * `public_key` - The public key of the sender.
     pub fn generate_unlock_script(
-        previous_transaction: &Transaction,
+        spending_transaction: &Transaction,
+        input_index: usize,
+        private_key: SecretKey,
+        public_key: PublicKey,
+    ) -> Vec<u8> {
+        [
+            Self::generate_signature(spending_transaction, input_index, private_key), // signature
+            public_key.serialize().to_vec(),                                          // public key
+        ]
+        .concat()
+    }
+
+    /// same as `generate_unlock_script`, the signature commits to the parts of the spending
+    /// transaction chosen by `sighash_type`
+    ///
+    /// return `NoOutputForSigHashSingle` if the input is signed with `SigHashType::Single`,
+    /// but the spending transaction has no output at its index
+    pub fn generate_unlock_script_with(
+        spending_transaction: &Transaction,
+        input_index: usize,
         private_key: SecretKey,
         public_key: PublicKey,
+        sighash_type: SigHashType,
+    ) -> Result<Vec<u8>, RustyCoinError> {
+        Ok([
+            Self::generate_signature_with(
+                spending_transaction,
+                input_index,
+                private_key,
+                sighash_type,
+            )?, // signature
+            public_key.serialize().to_vec(), // public key
+        ]
+        .concat())
+    }
+
+    /// sign one input of the spending transaction with `SigHashType::All`,
+    /// return the 64 bytes compact signature followed by the sighash type
+    ///
+    /// the nonce is derived from the private key and the signing digest (RFC 6979, as libsecp256k1 does),
+    /// so signing the same input of the same transaction twice yields the same signature,
+    /// and a retransmitted transaction keeps its transaction ID.
+    /// # Arguments
+    /// * `spending_transaction` - The drafted transaction that spends this input.
+    /// * `input_index` - The index of this input in the spending transaction.
+    /// * `private_key` - The private key of the signer.
+    pub fn generate_signature(
+        spending_transaction: &Transaction,
+        input_index: usize,
+        private_key: SecretKey,
     ) -> Vec<u8> {
-        let msg = Message::from_digest(*previous_transaction.sha256());
-        let signature = private_key.sign_ecdsa(msg);
+        Self::sign(
+            spending_transaction,
+            input_index,
+            private_key,
+            SigHashType::All,
+        )
+    }
+
+    /// same as `generate_signature`, the signature commits to the parts of the spending
+    /// transaction chosen by `sighash_type`, see `Transaction::sighash`
+    ///
+    /// return `NoOutputForSigHashSingle` if the input is signed with `SigHashType::Single`,
+    /// but the spending transaction has no output at its index
+    pub fn generate_signature_with(
+        spending_transaction: &Transaction,
+        input_index: usize,
+        private_key: SecretKey,
+        sighash_type: SigHashType,
+    ) -> Result<Vec<u8>, RustyCoinError> {
+        if !spending_transaction.can_sign_with(input_index, sighash_type) {
+            return Err(RustyCoinError::NoOutputForSigHashSingle);
+        }
+        Ok(Self::sign(
+            spending_transaction,
+            input_index,
+            private_key,
+            sighash_type,
+        ))
+    }
 
+    /// sign the digest of an input chosen by `sighash_type`, followed by the sighash type
+    fn sign(
+        spending_transaction: &Transaction,
+        input_index: usize,
+        private_key: SecretKey,
+        sighash_type: SigHashType,
+    ) -> Vec<u8> {
+        let msg = Message::from_digest(*spending_transaction.sighash(input_index, sighash_type));
         [
-            signature.serialize_compact().to_vec(), // signature
-            public_key.serialize().to_vec(),        // public key
+            private_key.sign_ecdsa(msg).serialize_compact().to_vec(),
+            vec![sighash_type as u8],
         ]
         .concat()
     }
+
+    /// generates the unlock script of a multisig output: the signatures pushed in order,
+    /// they must be in the same order as their public keys in the locking script
+    pub fn generate_multisig_unlock_script(signatures: &[Vec<u8>]) -> Vec<u8> {
+        signatures
+            .iter()
+            .flat_map(|signature| push_data_script(signature))
+            .collect()
+    }
 }
 
 /// Represents an output for a transaction.
@@ -281,15 +1005,61 @@ impl Output {
     ///
     /// # Arguments
     ///
-    /// * `amount` - The amount of the output.
+    /// * `amount` - The amount of the output, at least `MIN_OUTPUT_AMOUNT`,
+    ///   with at most `AMOUNT_DECIMAL_PLACES` decimal places.
     /// * `locking_script` - The locking script. This is a public key hash.
-    pub fn new(amount: Decimal, locking_script: Vec<u8>) -> Self {
+    ///
+    /// returns `InvalidOutputAmount` if the amount is negative, dust or too precise
+    pub fn new(amount: Decimal, locking_script: Vec<u8>) -> Result<Self, RustyCoinError> {
+        Self::new_with_dust_threshold(amount, locking_script, MIN_OUTPUT_AMOUNT)
+    }
+
+    /// Creates a new output, rejecting amounts below `dust_threshold` as dust.
+    ///
+    /// returns `InvalidOutputAmount` if the amount is negative, below `dust_threshold` or too precise
+    pub fn new_with_dust_threshold(
+        amount: Decimal,
+        locking_script: Vec<u8>,
+        dust_threshold: Decimal,
+    ) -> Result<Self, RustyCoinError> {
+        if amount < Decimal::ZERO || amount < dust_threshold || !is_valid_amount_precision(amount) {
+            return Err(RustyCoinError::InvalidOutputAmount);
+        }
+
         let length_of_locking_script = locking_script.len();
-        Self {
-            amount,
+        Ok(Self {
+            // normalize the scale, e.g. 1.000000000 is stored as 1.00000000
+            amount: amount.round_dp(AMOUNT_DECIMAL_PLACES),
             length_of_locking_script,
             locking_script,
+        })
+    }
+
+    /// Creates a data output: it carries no amount, and its locking script
+    /// `OP_RETURN <data>` fails whatever the unlocking script is,
+    /// so it can never be spent and is not added to the UTXO set.
+    ///
+    /// returns `InvalidDataOutput` if `data` is longer than `MAX_PUSH_SIZE`, or is exactly 30 bytes,
+    /// as a 32 bytes locking script stands for a P2PKH address
+    pub fn new_data(data: &[u8]) -> Result<Self, RustyCoinError> {
+        if data.len() > MAX_PUSH_SIZE {
+            return Err(RustyCoinError::InvalidDataOutput { size: data.len() });
+        }
+        let locking_script = [vec![OP_RETURN], push_data_script(data)].concat();
+        if locking_script.len() == P2PKH_ADDRESS_SIZE {
+            return Err(RustyCoinError::InvalidDataOutput { size: data.len() });
         }
+        Ok(Self {
+            amount: Decimal::ZERO,
+            length_of_locking_script: locking_script.len(),
+            locking_script,
+        })
+    }
+
+    /// check if the output is a data output created by `new_data`, which can never be spent
+    pub fn is_unspendable(&self) -> bool {
+        self.locking_script.len() != P2PKH_ADDRESS_SIZE
+            && self.locking_script.first() == Some(&OP_RETURN)
     }
 
     pub fn get_amount(&self) -> Decimal {
@@ -307,18 +1077,52 @@ impl Output {
         let result: [u8; 32] = hasher.finalize().into();
         result.to_vec()
     }
+
+    /// the P2PKH script a 32 bytes address stands for:
+    ///
+    /// `OP_DUP OP_HASH256 <address> OP_EQUALVERIFY OP_CHECKSIG`
+    pub fn generate_p2pkh_script(address: &[u8]) -> Vec<u8> {
+        [
+            vec![OP_DUP, OP_HASH256],
+            push_data_script(address),
+            vec![OP_EQUALVERIFY, OP_CHECKSIG],
+        ]
+        .concat()
+    }
+
+    /// generates an m-of-n multisig locking script:
+    ///
+    /// `OP_m <public key 1> ... <public key n> OP_n OP_CHECKMULTISIG`
+    ///
+    /// # Panics
+    /// if `m` is 0, or `m` is larger than n, or n is larger than 16
+    pub fn generate_multisig_script(m: usize, public_keys: &[PublicKey]) -> Vec<u8> {
+        let n = public_keys.len();
+        if m == 0 || m > n || n > 16 {
+            panic!("Invalid multisig parameters");
+        }
+
+        [
+            vec![OP_1 + m as u8 - 1],
+            public_keys
+                .iter()
+                .flat_map(|public_key| push_data_script(&public_key.serialize()))
+                .collect(),
+            vec![OP_1 + n as u8 - 1, OP_CHECKMULTISIG],
+        ]
+        .concat()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use rust_decimal_macros::dec;
     use secp256k1::generate_keypair;
 
     fn create_default_transaction() -> Transaction {
         let mut transaction = Transaction::new(
             vec![Input::new(HashValue::new([0u8; 32]), 0, 0, vec![0u8; 32])],
-            vec![Output::new(dec!(0.0), vec![0u8; 32])],
+            vec![Output::new(dec!(1.0), vec![0u8; 32]).unwrap()],
             HashValue::new([0u8; 32]),
             dec!(0.0),
             None,
@@ -329,6 +1133,46 @@ mod tests {
         transaction
     }
 
+    #[test]
+    fn test_valid_structure() {
+        let transaction = create_default_transaction();
+        assert!(transaction.is_valid_structure().is_ok());
+
+        // the transaction ID must match the content
+        let mut tampered = transaction.clone();
+        tampered.outputs[0].amount = dec!(2.0);
+        assert!(matches!(
+            tampered.is_valid_structure(),
+            Err(RustyCoinError::InvalidTransaction)
+        ));
+
+        let mut large_data = transaction.clone();
+        large_data.additional_data = Some(vec![0u8; MAX_ADDITIONAL_DATA_SIZE + 1]);
+        large_data.update_digest();
+        assert!(matches!(
+            large_data.is_valid_structure(),
+            Err(RustyCoinError::AdditionalDataTooLarge { size }) if size == MAX_ADDITIONAL_DATA_SIZE + 1
+        ));
+
+        let mut duplicate_input = transaction.clone();
+        duplicate_input
+            .inputs
+            .push(duplicate_input.inputs[0].clone());
+        duplicate_input.update_digest();
+        assert!(matches!(
+            duplicate_input.is_valid_structure(),
+            Err(RustyCoinError::DuplicateInput)
+        ));
+
+        let mut negative_output = transaction.clone();
+        negative_output.outputs[0].amount = dec!(-1.0);
+        negative_output.update_digest();
+        assert!(matches!(
+            negative_output.is_valid_structure(),
+            Err(RustyCoinError::InvalidOutputAmount)
+        ));
+    }
+
     #[test]
     fn test_hasher() {
         let transaction = create_default_transaction();
@@ -340,14 +1184,661 @@ mod tests {
         println!("{}", hash);
     }
 
+    #[test]
+    fn test_fee_rate() {
+        let small_tx = Transaction::new(
+            vec![Input::new(HashValue::new([0u8; 32]), 0, 0, vec![0u8; 32])],
+            vec![Output::new(dec!(1.0), vec![0u8; 32]).unwrap()],
+            HashValue::new([0u8; 32]),
+            dec!(0.1),
+            None,
+        );
+        let large_tx = Transaction::new(
+            vec![Input::new(HashValue::new([0u8; 32]), 0, 0, vec![0u8; 32])],
+            vec![Output::new(dec!(1.0), vec![0u8; 32]).unwrap()],
+            HashValue::new([0u8; 32]),
+            dec!(0.1),
+            Some(vec![0u8; 1000]),
+        );
+        assert_eq!(
+            small_tx.size(),
+            bincode::serialize(&small_tx).unwrap().len()
+        );
+        assert!(large_tx.size() > small_tx.size() + 1000);
+        assert_eq!(
+            small_tx.fee_rate(),
+            dec!(0.1) / Decimal::from(small_tx.size())
+        );
+
+        // the same fee, the smaller transaction pays more per byte
+        assert!(small_tx.fee_rate() > large_tx.fee_rate());
+    }
+
     #[test]
     fn test_scripts() {
         let transaction = create_default_transaction();
 
         let (private_key, public_key) = generate_keypair(&mut rand::thread_rng());
-        let unlocking_script = Input::generate_unlock_script(&transaction, private_key, public_key);
+        let unlocking_script =
+            Input::generate_unlock_script(&transaction, 0, private_key, public_key);
         let locking_script = Output::generate_locking_script(public_key);
-        let res = Transaction::verify_scripts(&transaction, &unlocking_script, &locking_script);
+        let res = Transaction::verify_scripts(&transaction, 0, &unlocking_script, &locking_script);
         assert!(res);
     }
+
+    #[test]
+    fn test_tampered_output_invalidates_signature() {
+        let mut transaction = create_default_transaction();
+
+        let (private_key, public_key) = generate_keypair(&mut rand::thread_rng());
+        let unlocking_script =
+            Input::generate_unlock_script(&transaction, 0, private_key, public_key);
+        transaction.set_unlock_script(0, unlocking_script.clone());
+        let locking_script = Output::generate_locking_script(public_key);
+        assert!(Transaction::verify_scripts(
+            &transaction,
+            0,
+            &unlocking_script,
+            &locking_script
+        ));
+
+        // redirect the output to another receiver after signing
+        transaction.outputs[0] = Output::new(dec!(1.0), vec![1u8; 32]).unwrap();
+        assert!(!Transaction::verify_scripts(
+            &transaction,
+            0,
+            &unlocking_script,
+            &locking_script
+        ));
+    }
+
+    #[test]
+    fn test_signature_bound_to_chain_id() {
+        let mut transaction = create_default_transaction();
+        transaction.set_chain_id(1);
+
+        let (private_key, public_key) = generate_keypair(&mut rand::thread_rng());
+        let unlocking_script =
+            Input::generate_unlock_script(&transaction, 0, private_key, public_key);
+        transaction.set_unlock_script(0, unlocking_script.clone());
+        let locking_script = Output::generate_locking_script(public_key);
+        assert!(Transaction::verify_scripts(
+            &transaction,
+            0,
+            &unlocking_script,
+            &locking_script
+        ));
+
+        // replay the transaction on the network with chain ID 2
+        let id = transaction.sha256();
+        transaction.set_chain_id(2);
+        assert_ne!(transaction.sha256(), id);
+        assert!(!Transaction::verify_scripts(
+            &transaction,
+            0,
+            &unlocking_script,
+            &locking_script
+        ));
+    }
+
+    #[test]
+    fn test_deterministic_signature() {
+        let transaction = create_default_transaction();
+        let (private_key, public_key) = generate_keypair(&mut rand::thread_rng());
+
+        let unlocking_script =
+            Input::generate_unlock_script(&transaction, 0, private_key, public_key);
+        assert_eq!(
+            Input::generate_unlock_script(&transaction, 0, private_key, public_key),
+            unlocking_script
+        );
+
+        // another key signs the same digest with another nonce
+        let (other_key, _) = generate_keypair(&mut rand::thread_rng());
+        assert_ne!(
+            Input::generate_signature(&transaction, 0, other_key),
+            Input::generate_signature(&transaction, 0, private_key)
+        );
+    }
+
+    #[test]
+    fn test_sighash_ignores_unlock_scripts() {
+        let mut transaction = create_default_transaction();
+        transaction.inputs.push(transaction.inputs[0].clone());
+        let sighashes = [
+            transaction.sighash(0, SigHashType::All),
+            transaction.sighash(1, SigHashType::All),
+        ];
+        let id = transaction.sha256();
+
+        // signing an input leaves the sighash of every input unchanged
+        let (private_key, public_key) = generate_keypair(&mut rand::thread_rng());
+        let locking_script = Output::generate_locking_script(public_key);
+        for input_index in 0..2 {
+            let unlocking_script =
+                Input::generate_unlock_script(&transaction, input_index, private_key, public_key);
+            transaction.set_unlock_script(input_index, unlocking_script);
+            assert_eq!(
+                [
+                    transaction.sighash(0, SigHashType::All),
+                    transaction.sighash(1, SigHashType::All),
+                ],
+                sighashes
+            );
+        }
+        assert_ne!(sighashes[0], sighashes[1]);
+        // the transaction ID commits to the signatures
+        assert_ne!(transaction.sha256(), id);
+
+        // so the signature of the first input is still valid once the second one is signed
+        for input_index in 0..2 {
+            assert!(Transaction::verify_scripts(
+                &transaction,
+                input_index,
+                transaction.inputs[input_index].get_unlock_script(),
+                &locking_script
+            ));
+        }
+    }
+
+    #[test]
+    fn test_signature_bound_to_input_index() {
+        let mut transaction = create_default_transaction();
+        transaction.inputs.push(transaction.inputs[0].clone());
+
+        let (private_key, public_key) = generate_keypair(&mut rand::thread_rng());
+        let unlocking_script =
+            Input::generate_unlock_script(&transaction, 0, private_key, public_key);
+        let locking_script = Output::generate_locking_script(public_key);
+
+        assert!(Transaction::verify_scripts(
+            &transaction,
+            0,
+            &unlocking_script,
+            &locking_script
+        ));
+        assert!(!Transaction::verify_scripts(
+            &transaction,
+            1,
+            &unlocking_script,
+            &locking_script
+        ));
+    }
+
+    #[test]
+    fn test_malformed_unlock_scripts() {
+        let transaction = create_default_transaction();
+
+        let (private_key, public_key) = generate_keypair(&mut rand::thread_rng());
+        let unlocking_script =
+            Input::generate_unlock_script(&transaction, 0, private_key, public_key);
+        let locking_script = Output::generate_locking_script(public_key);
+
+        // empty and truncated unlock scripts
+        for length in [0, 32, 63, 64, unlocking_script.len() - 1] {
+            assert!(!Transaction::verify_scripts(
+                &transaction,
+                0,
+                &unlocking_script[..length],
+                &locking_script
+            ));
+        }
+
+        // oversized unlock script
+        let oversized = [unlocking_script.clone(), vec![0u8; 8]].concat();
+        assert!(!Transaction::verify_scripts(
+            &transaction,
+            0,
+            &oversized,
+            &locking_script
+        ));
+
+        // an invalid signature, all bits set
+        let invalid_signature = [
+            vec![0xffu8; 64],
+            vec![SigHashType::All as u8],
+            public_key.serialize().to_vec(),
+        ]
+        .concat();
+        assert!(!Transaction::verify_scripts(
+            &transaction,
+            0,
+            &invalid_signature,
+            &locking_script
+        ));
+    }
+
+    #[test]
+    fn test_sighash_types() {
+        let transaction = Transaction::new(
+            vec![
+                Input::new(HashValue::new([0u8; 32]), 0, 0, vec![]),
+                Input::new(HashValue::new([1u8; 32]), 0, 1, vec![]),
+            ],
+            vec![
+                Output::new(dec!(1.0), vec![0u8; 32]).unwrap(),
+                Output::new(dec!(2.0), vec![1u8; 32]).unwrap(),
+            ],
+            HashValue::new([0u8; 32]),
+            dec!(0.1),
+            None,
+        );
+        let (private_key, public_key) = generate_keypair(&mut rand::thread_rng());
+        let locking_script = Output::generate_locking_script(public_key);
+
+        // (mutation, still valid under All, None, Single)
+        type Mutation = fn(&mut Transaction);
+        let mutations: [(Mutation, [bool; 3]); 6] = [
+            (|_tx| {}, [true, true, true]),
+            (|tx| tx.outputs[0].amount = dec!(1.5), [false, true, false]),
+            (|tx| tx.outputs[1].amount = dec!(2.5), [false, true, true]),
+            (|tx| tx.transaction_fee = dec!(0.2), [false, true, true]),
+            (|tx| tx.inputs[1].sequence = 0, [false, false, false]),
+            (|tx| tx.chain_id = 1, [false, false, false]),
+        ];
+        let sighash_types = [SigHashType::All, SigHashType::None, SigHashType::Single];
+        for (sighash_type, type_index) in sighash_types.into_iter().zip(0..) {
+            let unlocking_script = Input::generate_unlock_script_with(
+                &transaction,
+                0,
+                private_key,
+                public_key,
+                sighash_type,
+            )
+            .unwrap();
+            assert_eq!(unlocking_script[SIGNATURE_SIZE - 1], sighash_type as u8);
+            for (mutate, valid) in mutations {
+                let mut mutated = transaction.clone();
+                mutate(&mut mutated);
+                assert_eq!(
+                    Transaction::verify_scripts(&mutated, 0, &unlocking_script, &locking_script),
+                    valid[type_index],
+                    "{sighash_type:?}"
+                );
+                // the batched check rebuilds the same digest
+                mutated.set_unlock_script(0, unlocking_script.clone());
+                assert_eq!(
+                    mutated
+                        .p2pkh_signature_check(0, &locking_script)
+                        .unwrap()
+                        .verify(),
+                    valid[type_index]
+                );
+            }
+
+            // the sighash type is signed too, it can't be swapped for a weaker one
+            let mut swapped = unlocking_script.clone();
+            swapped[SIGNATURE_SIZE - 1] = SigHashType::None as u8;
+            assert_eq!(
+                Transaction::verify_scripts(&transaction, 0, &swapped, &locking_script),
+                sighash_type == SigHashType::None
+            );
+        }
+
+        // SIGHASH_SINGLE needs an output at the index of the input
+        let mut single_input = transaction.clone();
+        single_input.outputs.truncate(1);
+        assert!(matches!(
+            Input::generate_unlock_script_with(
+                &single_input,
+                1,
+                private_key,
+                public_key,
+                SigHashType::Single
+            ),
+            Err(RustyCoinError::NoOutputForSigHashSingle)
+        ));
+        let mut forged = Input::sign(&single_input, 1, private_key, SigHashType::Single);
+        forged.extend(public_key.serialize());
+        assert!(!Transaction::verify_scripts(
+            &single_input,
+            1,
+            &forged,
+            &locking_script
+        ));
+        single_input.set_unlock_script(1, forged);
+        assert!(single_input
+            .p2pkh_signature_check(1, &locking_script)
+            .is_none());
+
+        // an unknown sighash type
+        let mut unknown = Input::generate_unlock_script(&transaction, 0, private_key, public_key);
+        unknown[SIGNATURE_SIZE - 1] = 0x04;
+        assert_eq!(SigHashType::from_byte(0x04), None);
+        assert!(!Transaction::verify_scripts(
+            &transaction,
+            0,
+            &unknown,
+            &locking_script
+        ));
+    }
+
+    #[test]
+    fn test_multisig_scripts() {
+        let transaction = create_default_transaction();
+        let keys = (0..3)
+            .map(|_| generate_keypair(&mut rand::thread_rng()))
+            .collect::<Vec<_>>();
+        let public_keys = keys.iter().map(|(_, pk)| *pk).collect::<Vec<_>>();
+        let locking_script = Output::generate_multisig_script(2, &public_keys);
+        let sign = |signer: usize| Input::generate_signature(&transaction, 0, keys[signer].0);
+
+        // any 2 of the 3, in the order of the public keys
+        for signers in [[0, 1], [0, 2], [1, 2]] {
+            let unlocking_script =
+                Input::generate_multisig_unlock_script(&[sign(signers[0]), sign(signers[1])]);
+            assert!(Transaction::verify_scripts(
+                &transaction,
+                0,
+                &unlocking_script,
+                &locking_script
+            ));
+        }
+
+        // out of order, too few, duplicated, or from an outsider
+        let (outsider_key, _) = generate_keypair(&mut rand::thread_rng());
+        let failing_signatures = [
+            vec![sign(2), sign(0)],
+            vec![sign(1)],
+            vec![sign(1), sign(1)],
+            vec![
+                sign(0),
+                Input::generate_signature(&transaction, 0, outsider_key),
+            ],
+        ];
+        for signatures in failing_signatures {
+            let unlocking_script = Input::generate_multisig_unlock_script(&signatures);
+            assert!(!Transaction::verify_scripts(
+                &transaction,
+                0,
+                &unlocking_script,
+                &locking_script
+            ));
+        }
+
+        // the unlocking script may only push data
+        let unlocking_script = [
+            Input::generate_multisig_unlock_script(&[sign(0), sign(1)]),
+            vec![OP_DUP],
+        ]
+        .concat();
+        assert!(!Transaction::verify_scripts(
+            &transaction,
+            0,
+            &unlocking_script,
+            &locking_script
+        ));
+    }
+
+    #[test]
+    fn test_p2pkh_template() {
+        let transaction = create_default_transaction();
+        let (private_key, public_key) = generate_keypair(&mut rand::thread_rng());
+        let address = Output::generate_locking_script(public_key);
+        let signature = Input::generate_signature(&transaction, 0, private_key);
+
+        // the unlocking script may also be written as two pushes
+        let unlocking_script = [
+            push_data_script(&signature),
+            push_data_script(&public_key.serialize()),
+        ]
+        .concat();
+        assert!(Transaction::verify_scripts(
+            &transaction,
+            0,
+            &unlocking_script,
+            &address
+        ));
+        // the full P2PKH script is equivalent to the bare address
+        assert!(Transaction::verify_scripts(
+            &transaction,
+            0,
+            &unlocking_script,
+            &Output::generate_p2pkh_script(&address)
+        ));
+    }
+
+    #[test]
+    fn test_data_output() {
+        let output = Output::new_data(b"hello world").unwrap();
+        assert!(output.is_unspendable());
+        assert_eq!(output.get_amount(), Decimal::ZERO);
+        assert_eq!(
+            output.get_locking_script(),
+            &[vec![OP_RETURN], push_data_script(b"hello world")].concat()
+        );
+        assert!(!Output::new(dec!(1.0), vec![OP_RETURN; 32])
+            .unwrap()
+            .is_unspendable());
+
+        // no unlocking script can spend it
+        let transaction = create_default_transaction();
+        for unlocking_script in [vec![], vec![OP_1], push_data_script(b"hello world")] {
+            assert!(!Transaction::verify_scripts(
+                &transaction,
+                0,
+                &unlocking_script,
+                output.get_locking_script()
+            ));
+        }
+
+        // the data is as long as a push allows, and never as long as an address
+        assert!(Output::new_data(&[7u8; MAX_PUSH_SIZE]).is_ok());
+        assert!(matches!(
+            Output::new_data(&[7u8; MAX_PUSH_SIZE + 1]),
+            Err(RustyCoinError::InvalidDataOutput { size: 76 })
+        ));
+        assert!(matches!(
+            Output::new_data(&[7u8; P2PKH_ADDRESS_SIZE - 2]),
+            Err(RustyCoinError::InvalidDataOutput { size: 30 })
+        ));
+    }
+
+    #[test]
+    fn test_transaction_builder() {
+        let input = Input::new(HashValue::new([1u8; 32]), 3, 0, vec![]);
+        let output = Output::new(dec!(49.0), vec![2u8; 32]).unwrap();
+
+        let mut manual_tx = Transaction::new(
+            vec![input.clone()],
+            vec![output.clone()],
+            HashValue::new([0u8; 32]),
+            dec!(1.0),
+            Some(b"memo".to_vec()),
+        );
+        manual_tx.update_digest();
+
+        let built_tx = TransactionBuilder::new()
+            .add_input(input.clone(), dec!(50.0))
+            .add_output(output.clone())
+            .additional_data(b"memo".to_vec())
+            .build()
+            .unwrap();
+        assert_eq!(built_tx, manual_tx);
+        assert_eq!(built_tx.get_transaction_id(), built_tx.sha256());
+
+        // signed with the same key, the same transaction as the manual path
+        let (private_key, public_key) = generate_keypair(&mut rand::thread_rng());
+        let signed_tx = TransactionBuilder::new()
+            .add_input(input.clone(), dec!(50.0))
+            .add_output(output.clone())
+            .build_signed(|tx, input_index| {
+                Some(Input::generate_unlock_script(
+                    tx,
+                    input_index,
+                    private_key,
+                    public_key,
+                ))
+            })
+            .unwrap();
+        let mut manual_tx = Transaction::new(
+            vec![input.clone()],
+            vec![output.clone()],
+            HashValue::new([0u8; 32]),
+            dec!(1.0),
+            None,
+        );
+        let unlock_script = Input::generate_unlock_script(&manual_tx, 0, private_key, public_key);
+        manual_tx.set_unlock_script(0, unlock_script);
+        manual_tx.update_digest();
+        assert_eq!(signed_tx, manual_tx);
+
+        // the inputs must cover the outputs, and the outputs must not be negative
+        assert!(matches!(
+            TransactionBuilder::new()
+                .add_input(input.clone(), dec!(1.0))
+                .add_output(output)
+                .build(),
+            Err(RustyCoinError::InvalidInputFee)
+        ));
+        assert!(matches!(
+            TransactionBuilder::new()
+                .add_input(input, dec!(1.0))
+                .add_output(Output {
+                    amount: dec!(-1.0),
+                    length_of_locking_script: 32,
+                    locking_script: vec![2u8; 32],
+                })
+                .build(),
+            Err(RustyCoinError::InvalidOutputAmount)
+        ));
+    }
+
+    #[test]
+    fn test_output_amount() {
+        assert!(Output::new(dec!(1.0), vec![0u8; 32]).is_ok());
+        assert!(Output::new(MIN_OUTPUT_AMOUNT, vec![0u8; 32]).is_ok());
+        assert!(matches!(
+            Output::new(dec!(-1.0), vec![0u8; 32]),
+            Err(RustyCoinError::InvalidOutputAmount)
+        ));
+        assert!(matches!(
+            Output::new(dec!(0.0), vec![0u8; 32]),
+            Err(RustyCoinError::InvalidOutputAmount)
+        ));
+
+        // a custom dust threshold
+        assert!(Output::new_with_dust_threshold(dec!(0.01), vec![0u8; 32], dec!(0.01)).is_ok());
+        assert!(matches!(
+            Output::new_with_dust_threshold(dec!(0.009), vec![0u8; 32], dec!(0.01)),
+            Err(RustyCoinError::InvalidOutputAmount)
+        ));
+        assert!(matches!(
+            Output::new_with_dust_threshold(dec!(-1.0), vec![0u8; 32], dec!(-2.0)),
+            Err(RustyCoinError::InvalidOutputAmount)
+        ));
+    }
+
+    #[test]
+    fn test_amount_overflow() {
+        let input = Input::new(HashValue::new([1u8; 32]), 1, 0, vec![]);
+        let output = Output::new(Decimal::MAX, vec![0u8; 32]).unwrap();
+        assert!(matches!(
+            TransactionBuilder::new()
+                .add_input(input.clone(), Decimal::MAX)
+                .add_output(output.clone())
+                .add_output(output.clone())
+                .build(),
+            Err(RustyCoinError::AmountOverflow)
+        ));
+        assert!(matches!(
+            TransactionBuilder::new()
+                .add_input(input.clone(), Decimal::MAX)
+                .add_input(input, Decimal::MAX)
+                .add_output(output)
+                .build(),
+            Err(RustyCoinError::AmountOverflow)
+        ));
+        assert!(matches!(
+            sum_amounts([Decimal::MAX, MIN_OUTPUT_AMOUNT, Decimal::MAX]),
+            Err(RustyCoinError::AmountOverflow)
+        ));
+        assert_eq!(sum_amounts([dec!(1.5), dec!(2.5)]).unwrap(), dec!(4.0));
+    }
+
+    #[test]
+    fn test_amount_precision() {
+        assert!(is_valid_amount_precision(dec!(1.00000001)));
+        assert!(is_valid_amount_precision(dec!(1.000000000)));
+        assert!(!is_valid_amount_precision(dec!(1.000000001)));
+        assert!(!is_valid_amount_precision(dec!(0.00000000000000001)));
+
+        assert!(matches!(
+            Output::new(dec!(1.000000001), vec![0u8; 32]),
+            Err(RustyCoinError::InvalidOutputAmount)
+        ));
+        let output = Output::new(dec!(1.000000000), vec![0u8; 32]).unwrap();
+        assert_eq!(output.get_amount().scale(), AMOUNT_DECIMAL_PLACES);
+
+        let over_precise_output = Output {
+            amount: dec!(0.00000000000000001),
+            length_of_locking_script: 32,
+            locking_script: vec![0u8; 32],
+        };
+        let tx = Transaction::new(
+            vec![],
+            vec![over_precise_output],
+            HashValue::new([0u8; 32]),
+            dec!(0.0),
+            None,
+        );
+        assert!(!tx.has_valid_amount_precision());
+
+        let tx = Transaction::new(
+            vec![],
+            vec![output],
+            HashValue::new([0u8; 32]),
+            dec!(0.000000001),
+            None,
+        );
+        assert!(!tx.has_valid_amount_precision());
+        assert!(create_default_transaction().has_valid_amount_precision());
+    }
+
+    #[test]
+    fn test_lock_time() {
+        let mut input = Input::new(HashValue::new([1u8; 32]), 3, 0, vec![]);
+        let tx = TransactionBuilder::new()
+            .add_input(input.clone(), dec!(50.0))
+            .lock_time(10)
+            .build()
+            .unwrap();
+        // all the inputs are final, the lock time is ignored
+        assert!(tx.is_final(0, 0));
+
+        input.set_sequence(0);
+        let tx = TransactionBuilder::new()
+            .add_input(input.clone(), dec!(50.0))
+            .lock_time(10)
+            .build()
+            .unwrap();
+        assert_eq!(tx.get_lock_time(), 10);
+        assert!(!tx.is_final(10, u64::MAX));
+        assert!(tx.is_final(11, 0));
+
+        // a lock time above the threshold is a timestamp
+        let tx = TransactionBuilder::new()
+            .add_input(input.clone(), dec!(50.0))
+            .lock_time(1_700_000_000)
+            .build()
+            .unwrap();
+        assert!(!tx.is_final(usize::MAX, 1_700_000_000));
+        assert!(tx.is_final(0, 1_700_000_001));
+
+        // the transaction ID and the signatures commit to the lock time and the sequences
+        let mut relocked_tx = tx.clone();
+        relocked_tx.set_lock_time(0);
+        assert_ne!(relocked_tx.sha256(), tx.sha256());
+        assert_ne!(
+            relocked_tx.sighash(0, SigHashType::All),
+            tx.sighash(0, SigHashType::All)
+        );
+        let mut resequenced_tx = tx.clone();
+        resequenced_tx.inputs[0].set_sequence(SEQUENCE_FINAL);
+        assert_ne!(resequenced_tx.sha256(), tx.sha256());
+        assert_ne!(
+            resequenced_tx.sighash(0, SigHashType::All),
+            tx.sighash(0, SigHashType::All)
+        );
+    }
 }
@@ -1,8 +1,12 @@
+use std::array::TryFromSliceError;
 use std::fmt;
 use std::fmt::Formatter;
 use std::ops::Deref;
 
-use serde::{Deserialize, Serialize};
+use crate::errors::RustyCoinError;
+use serde::de::{Error, SeqAccess, Visitor};
+use serde::ser::SerializeTuple;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use sha2::{Digest, Sha256};
 
 pub fn bytes_vec_to_hex_string(bytes: &[u8]) -> String {
@@ -14,9 +18,16 @@ pub fn bytes_vec_to_hex_string(bytes: &[u8]) -> String {
 
 pub type HashValue = Bytes<32>;
 
+/// version byte prepended to the payload of a Base58Check address
+pub const ADDRESS_VERSION: u8 = 0x00;
+
 impl HashValue {
-    pub fn new(bytes: [u8; 32]) -> Self {
-        Self(bytes)
+    /// check if the hash is a valid proof-of-work for `target`, i.e. at most the target.
+    ///
+    /// both are 256-bit big-endian numbers, so comparing their bytes lexicographically
+    /// compares the numbers, the most significant byte first
+    pub fn meets_target(&self, target: &HashValue) -> bool {
+        self <= target
     }
 
     pub fn sha256(&self) -> Self {
@@ -25,28 +36,221 @@ impl HashValue {
         let result = hasher.finalize().into();
         Self::new(result)
     }
+
+    /// encode the hash as a Base58Check address:
+    /// version byte + 32 bytes payload + first 4 bytes of the double SHA256 of the two
+    pub fn to_address_string(&self) -> String {
+        let mut bytes = vec![ADDRESS_VERSION];
+        bytes.extend_from_slice(&self.0);
+        bytes.extend_from_slice(&address_checksum(&bytes));
+        bs58::encode(bytes).into_string()
+    }
+
+    /// decode a Base58Check address produced by `to_address_string`,
+    /// return `InvalidAddress` if it is not valid Base58, has a wrong length, version or checksum
+    pub fn from_address_string(s: &str) -> Result<HashValue, RustyCoinError> {
+        let bytes = bs58::decode(s)
+            .into_vec()
+            .map_err(|_| RustyCoinError::InvalidAddress)?;
+        if bytes.len() != 1 + 32 + 4 || bytes[0] != ADDRESS_VERSION {
+            return Err(RustyCoinError::InvalidAddress);
+        }
+
+        let (versioned_payload, checksum) = bytes.split_at(1 + 32);
+        if address_checksum(versioned_payload) != checksum {
+            return Err(RustyCoinError::InvalidAddress);
+        }
+
+        let mut payload = [0u8; 32];
+        payload.copy_from_slice(&versioned_payload[1..]);
+        Ok(HashValue::new(payload))
+    }
+}
+
+/// the nBits of the target of difficulty 1, bitcoin's genesis target.
+///
+/// the genesis block of rusty coin has a zero target, so it can't be the reference
+pub const DIFFICULTY_ONE_NBITS: u32 = 0x1D00FFFF;
+
+/// calculate the 256-bit target from the compact nBits `b_1b_2b_3b_4`:
+///
+/// $ target = b_2b_3b_4 \times 2^{8(b_1 - 3)} $
+///
+/// - if the exponent is smaller than 3, the low bytes of the mantissa are shifted out
+/// - if the target doesn't fit in 32 bytes, the maximum target (0xFF...FF) is returned
+pub fn nbits_to_target(nbits: u32) -> HashValue {
+    let n_bit_bytes: [u8; 4] = nbits.to_be_bytes();
+    let exp = n_bit_bytes[0] as usize;
+    let mut target = [0u8; 32];
+
+    // the mantissa b_2b_3b_4 starts at byte `32 - exp` of the big-endian target
+    for (k, &byte) in n_bit_bytes[1..].iter().enumerate() {
+        let position = (32 + k).checked_sub(exp);
+        match position {
+            Some(position) if position < 32 => target[position] = byte,
+            Some(_) => {} // shifted out to the right
+            None if byte == 0 => {}
+            None => return HashValue::MAX, // overflow to the left
+        }
+    }
+
+    HashValue::new(target)
+}
+
+/// encode a target as compact nBits, keeping its 3 most significant bytes.
+///
+/// the mantissa is kept below 0x800000, as bitcoin treats its top bit as a sign,
+/// so `target_to_nbits(nbits_to_target(nbits)) == nbits` for every such normalized nBits
+/// (a non-zero leading mantissa byte, or a zero one followed by a byte >= 0x80).
+/// a zero target is encoded as 0.
+pub fn target_to_nbits(target: &HashValue) -> u32 {
+    let Some(first) = target.iter().position(|&byte| byte != 0) else {
+        return 0;
+    };
+    let mut size = (32 - first) as u32;
+    let mut mantissa = (0..3).fold(0u32, |mantissa, k| {
+        mantissa << 8 | target.get(first + k).copied().unwrap_or(0) as u32
+    });
+    if mantissa & 0x0080_0000 != 0 {
+        mantissa >>= 8;
+        size += 1;
+    }
+    size << 24 | mantissa
+}
+
+/// scale a target by `numerator / denominator`, rounding down,
+/// e.g. to retarget the difficulty by the ratio of the actual to the expected timespan.
+///
+/// - if the result doesn't fit in 32 bytes, the maximum target (0xFF...FF) is returned
+/// - panics if `denominator` is 0
+pub fn scale_target(target: &HashValue, numerator: u64, denominator: u64) -> HashValue {
+    assert_ne!(denominator, 0, "can't scale a target by a zero denominator");
+
+    // big-endian 32-bit limbs, the product needs 64 more bits than the target
+    let mut limbs = [0u32; 10];
+    let mut carry = 0u128;
+    for k in (0..8).rev() {
+        let limb = u32::from_be_bytes(target[4 * k..4 * k + 4].try_into().unwrap());
+        let product = limb as u128 * numerator as u128 + carry;
+        limbs[k + 2] = product as u32;
+        carry = product >> 32;
+    }
+    limbs[1] = carry as u32;
+    limbs[0] = (carry >> 32) as u32;
+
+    let mut remainder = 0u128;
+    for limb in limbs.iter_mut() {
+        let dividend = remainder << 32 | *limb as u128;
+        *limb = (dividend / denominator as u128) as u32;
+        remainder = dividend % denominator as u128;
+    }
+    if limbs[0] != 0 || limbs[1] != 0 {
+        return HashValue::MAX;
+    }
+
+    let mut scaled = [0u8; 32];
+    for (k, limb) in limbs[2..].iter().enumerate() {
+        scaled[4 * k..4 * k + 4].copy_from_slice(&limb.to_be_bytes());
+    }
+    HashValue::new(scaled)
+}
+
+/// how many times harder the nBits is than difficulty 1, see `DIFFICULTY_ONE_NBITS`
+///
+/// a zero target is infinitely hard
+pub fn difficulty_ratio(nbits: u32) -> f64 {
+    let to_f64 = |target: HashValue| {
+        target
+            .iter()
+            .fold(0.0, |value, &byte| value * 256.0 + byte as f64)
+    };
+    to_f64(nbits_to_target(DIFFICULTY_ONE_NBITS)) / to_f64(nbits_to_target(nbits))
+}
+
+/// a recoverable ECDSA signature: the recovery id followed by the 64 bytes compact signature
+pub type Signature = Bytes<65>;
+
+impl Signature {
+    /// build a signature from raw bytes, return `InvalidSignature` if it is not exactly 65 bytes
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, RustyCoinError> {
+        Self::try_from(bytes).map_err(|_| RustyCoinError::InvalidSignature)
+    }
+}
+
+/// the checksum of Base58Check: the first 4 bytes of the double SHA256 of the bytes
+pub(crate) fn address_checksum(bytes: &[u8]) -> [u8; 4] {
+    let hash = Sha256::digest(Sha256::digest(bytes));
+    let mut checksum = [0u8; 4];
+    checksum.copy_from_slice(&hash[..4]);
+    checksum
 }
 
 //to store the hash value on stack, facilitate compute process
-#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Ord, PartialOrd)]
-#[serde(try_from = "String", into = "String")]
+#[derive(Clone, Copy, PartialEq, Eq, Ord, PartialOrd)]
 pub struct Bytes<const T: usize>([u8; T]);
 
+/// human-readable formats (like JSON) get a "0x..." hex string,
+/// binary formats (like bincode) get the `T` raw bytes
+impl<const T: usize> Serialize for Bytes<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&String::from(*self))
+        } else {
+            let mut tuple = serializer.serialize_tuple(T)?;
+            for byte in self.0.iter() {
+                tuple.serialize_element(byte)?;
+            }
+            tuple.end()
+        }
+    }
+}
+
+impl<'de, const T: usize> Deserialize<'de> for Bytes<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            let value = String::deserialize(deserializer)?;
+            Bytes::try_from(value).map_err(D::Error::custom)
+        } else {
+            deserializer.deserialize_tuple(T, BytesVisitor)
+        }
+    }
+}
+
+/// read the `T` raw bytes written by a binary format
+struct BytesVisitor<const T: usize>;
+
+impl<'de, const T: usize> Visitor<'de> for BytesVisitor<T> {
+    type Value = Bytes<T>;
+
+    fn expecting(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} bytes", T)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut arr = [0u8; T];
+        for (i, byte) in arr.iter_mut().enumerate() {
+            *byte = seq
+                .next_element()?
+                .ok_or_else(|| A::Error::invalid_length(i, &self))?;
+        }
+        Ok(Bytes(arr))
+    }
+}
+
 impl<const T: usize> TryFrom<String> for Bytes<T> {
     type Error = Box<dyn std::error::Error>;
 
     fn try_from(value: String) -> Result<Self, Self::Error> {
-        let val = if let Some(val) = value.strip_prefix("0x") {
-            val
-        } else {
-            &value
-        };
+        Ok(Self::from_hex(&value)?)
+    }
+}
 
-        Ok(hex::decode(val).map(|bytes| {
-            let mut arr = [0u8; T];
-            arr.copy_from_slice(&bytes);
-            Bytes(arr)
-        })?)
+/// build from raw bytes, e.g. read off a socket, the length must be exactly `T`
+impl<const T: usize> TryFrom<&[u8]> for Bytes<T> {
+    type Error = TryFromSliceError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        Ok(Bytes(value.try_into()?))
     }
 }
 
@@ -57,6 +261,46 @@ impl<const T: usize> From<Bytes<T>> for String {
 }
 
 impl<const T: usize> Bytes<T> {
+    /// all bytes 0x00, the smallest value, e.g. the easiest hash to meet a target
+    pub const MIN: Self = Self([0x00; T]);
+    /// all bytes 0xFF, the largest value, e.g. the easiest target
+    pub const MAX: Self = Self([0xFF; T]);
+
+    pub fn new(bytes: [u8; T]) -> Self {
+        Self(bytes)
+    }
+
+    /// parse the hex string typed by a user, e.g. a transaction ID or a block hash:
+    /// the "0x" prefix is optional, and the digits may be uppercase.
+    ///
+    /// returns `InvalidHex` if it is not exactly `T * 2` hex digits
+    pub fn from_hex(s: &str) -> Result<Self, RustyCoinError> {
+        let digits = s
+            .strip_prefix("0x")
+            .or_else(|| s.strip_prefix("0X"))
+            .unwrap_or(s);
+        let invalid_hex = RustyCoinError::InvalidHex {
+            expected_len: T * 2,
+        };
+        if digits.len() != T * 2 {
+            return Err(invalid_hex);
+        }
+
+        let mut arr = [0u8; T];
+        hex::decode_to_slice(digits, &mut arr).map_err(|_| invalid_hex)?;
+        Ok(Bytes(arr))
+    }
+
+    /// compare in constant time, the time taken does not depend on where the bytes differ
+    pub fn ct_eq(&self, other: &Self) -> bool {
+        let diff = self
+            .0
+            .iter()
+            .zip(other.0.iter())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b));
+        std::hint::black_box(diff) == 0
+    }
+
     fn fmt_as_hex(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "0x")?;
 
@@ -94,7 +338,12 @@ impl<const T: usize> AsRef<[u8]> for Bytes<T> {
 
 #[cfg(test)]
 mod tests {
-    use crate::types::{Bytes, HashValue};
+    use crate::errors::RustyCoinError;
+    use crate::types::{
+        difficulty_ratio, nbits_to_target, scale_target, target_to_nbits, Bytes, HashValue,
+        Signature, DIFFICULTY_ONE_NBITS,
+    };
+    use rand::Rng;
     use sha2::{Digest, Sha256};
 
     #[test]
@@ -106,4 +355,220 @@ mod tests {
             "0xb94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9"
         );
     }
+
+    #[test]
+    fn bytes_try_from_wrong_length() {
+        let too_short = format!("\"0x{}\"", "ab".repeat(31));
+        let too_long = format!("\"0x{}\"", "ab".repeat(33));
+        let just_right = format!("\"0x{}\"", "ab".repeat(32));
+
+        assert!(serde_json::from_str::<HashValue>(&too_short).is_err());
+        assert!(serde_json::from_str::<HashValue>(&too_long).is_err());
+        assert!(serde_json::from_str::<HashValue>("\"0x\"").is_err());
+        assert_eq!(
+            serde_json::from_str::<HashValue>(&just_right).unwrap(),
+            HashValue::new([0xab; 32])
+        );
+    }
+
+    #[test]
+    fn bytes_from_hex() {
+        let lowercase = "ab".repeat(32);
+        let hash = HashValue::new([0xab; 32]);
+        assert_eq!(HashValue::from_hex(&lowercase).unwrap(), hash);
+        assert_eq!(
+            HashValue::from_hex(&format!("0x{lowercase}")).unwrap(),
+            hash
+        );
+        assert_eq!(HashValue::from_hex(&"AB".repeat(32)).unwrap(), hash);
+        assert_eq!(
+            HashValue::from_hex(&format!("0X{}", "Ab".repeat(32))).unwrap(),
+            hash
+        );
+
+        // odd length, wrong length, not hex
+        for invalid in [
+            "a".repeat(63),
+            "a".repeat(65),
+            "ab".repeat(31),
+            "ab".repeat(33),
+            String::new(),
+            "0x".to_string(),
+            "xy".repeat(32),
+        ] {
+            assert!(matches!(
+                HashValue::from_hex(&invalid),
+                Err(RustyCoinError::InvalidHex { expected_len: 64 })
+            ));
+        }
+        assert_eq!(
+            Bytes::<4>::from_hex("0x1").unwrap_err().to_string(),
+            "expected 8 hex digits, with an optional 0x prefix"
+        );
+    }
+
+    #[test]
+    fn bytes_try_from_slice() {
+        let bytes = [0xabu8; 33];
+        assert_eq!(
+            HashValue::try_from(&bytes[..32]).unwrap(),
+            HashValue::new([0xab; 32])
+        );
+        assert_eq!(
+            Bytes::<4>::try_from(&bytes[..4]).unwrap(),
+            Bytes::<4>::new([0xab; 4])
+        );
+        assert!(HashValue::try_from(&bytes[..31]).is_err());
+        assert!(HashValue::try_from(&bytes[..]).is_err());
+        assert!(HashValue::try_from(&[][..]).is_err());
+    }
+
+    #[test]
+    fn signature_from_slice() {
+        let mut bytes = [0u8; 66];
+        bytes[0] = 1;
+        bytes[64] = 0xcd;
+        let signature = Signature::from_slice(&bytes[..65]).unwrap();
+        assert_eq!(signature, Signature::new(bytes[..65].try_into().unwrap()));
+        assert_eq!(signature[0], 1);
+        assert_eq!(signature[64], 0xcd);
+
+        for len in [0, 64, 66] {
+            assert!(matches!(
+                Signature::from_slice(&bytes[..len]),
+                Err(RustyCoinError::InvalidSignature)
+            ));
+        }
+    }
+
+    #[test]
+    fn bytes_ct_eq() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..1000 {
+            let a = HashValue::new(rng.gen());
+            let b = HashValue::new(rng.gen());
+            assert_eq!(a.ct_eq(&b), a == b);
+            assert!(a.ct_eq(&a));
+
+            // differ in a single bit
+            let mut flipped = *a;
+            flipped[rng.gen_range(0..32)] ^= 1 << rng.gen_range(0..8);
+            let flipped = HashValue::new(flipped);
+            assert!(!a.ct_eq(&flipped));
+            assert_eq!(a.ct_eq(&flipped), a == flipped);
+        }
+    }
+
+    #[test]
+    fn address_string_round_trip() {
+        let hash = HashValue::new(Sha256::digest(b"hello world").into());
+        let address = hash.to_address_string();
+        assert_eq!(HashValue::from_address_string(&address).unwrap(), hash);
+
+        // corrupt the checksum, the last character of the address
+        let mut corrupted = address.clone();
+        let last = corrupted.pop().unwrap();
+        corrupted.push(if last == '1' { '2' } else { '1' });
+        assert!(HashValue::from_address_string(&corrupted).is_err());
+
+        // not base58, or too short
+        assert!(HashValue::from_address_string("0OIl").is_err());
+        assert!(HashValue::from_address_string(&address[..20]).is_err());
+    }
+
+    #[test]
+    fn hash_ordering() {
+        let mut one = [0u8; 32];
+        one[31] = 1;
+        let one = HashValue::new(one);
+        assert!(HashValue::MIN < one && one < HashValue::MAX);
+        assert_eq!(HashValue::MIN, HashValue::new([0x00; 32]));
+        assert_eq!(HashValue::MAX, HashValue::new([0xFF; 32]));
+
+        // the bytes are big-endian, the first byte is the most significant one
+        let mut high = [0u8; 32];
+        high[0] = 1;
+        let high = HashValue::new(high);
+        let mut low = [0xFFu8; 32];
+        low[0] = 0;
+        let low = HashValue::new(low);
+        assert!(low < high);
+
+        assert!(one.meets_target(&one));
+        assert!(one.meets_target(&HashValue::MAX));
+        assert!(HashValue::MIN.meets_target(&one));
+        assert!(!one.meets_target(&HashValue::MIN));
+        assert!(low.meets_target(&high));
+        assert!(!high.meets_target(&low));
+        assert!(!HashValue::MAX.meets_target(&nbits_to_target(0x207FFFFF)));
+    }
+
+    #[test]
+    fn nbits_target_round_trip() {
+        for nbits in [
+            0x03123456, 0x1B0404CB, 0x1C7FFFFF, 0x1D00FFFF, 0x1E123456, 0x2000FFFF, 0x207FFFFF,
+        ] {
+            assert_eq!(target_to_nbits(&nbits_to_target(nbits)), nbits);
+        }
+        assert_eq!(target_to_nbits(&HashValue::new([0; 32])), 0);
+
+        // a target with its top bit set moves the mantissa down a byte, only 2 bytes are kept
+        let mut target = [0u8; 32];
+        target[4..8].copy_from_slice(&[0xFF, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(target_to_nbits(&HashValue::new(target)), 0x1D00FFFF);
+
+        // any target is encoded as nBits whose target is at most the original
+        let mut rng = rand::thread_rng();
+        for _ in 0..1000 {
+            let target = HashValue::new(rng.gen());
+            let nbits = target_to_nbits(&target);
+            assert!(nbits_to_target(nbits) <= target);
+            assert_eq!(target_to_nbits(&nbits_to_target(nbits)), nbits);
+        }
+    }
+
+    #[test]
+    fn known_difficulty_ratios() {
+        let close = |a: f64, b: f64| ((a - b) / b).abs() < 1e-12;
+        assert_eq!(difficulty_ratio(DIFFICULTY_ONE_NBITS), 1.0);
+        assert!(close(difficulty_ratio(0x1B0404CB), 16307.420938523983));
+        assert!(close(difficulty_ratio(0x1C7FFFFF), 1.9999697208368445));
+        assert!(close(difficulty_ratio(0x207FFFFF), 4.656542373906925e-10));
+        assert_eq!(difficulty_ratio(0), f64::INFINITY);
+    }
+
+    #[test]
+    fn scale_targets() {
+        let one = nbits_to_target(DIFFICULTY_ONE_NBITS);
+        assert_eq!(scale_target(&one, 1, 1), one);
+        assert_eq!(target_to_nbits(&scale_target(&one, 2, 1)), 0x1D01FFFE);
+        assert_eq!(target_to_nbits(&scale_target(&one, 1, 4)), 0x1C3FFFC0);
+        assert_eq!(scale_target(&one, 3, 3), one);
+        // rounded down
+        assert_eq!(
+            scale_target(&HashValue::new([0xFF; 32]), 1, 2),
+            HashValue::from_hex(&format!("7f{}", "ff".repeat(31))).unwrap()
+        );
+        assert_eq!(scale_target(&HashValue::MIN, u64::MAX, 1), HashValue::MIN);
+        // overflow
+        assert_eq!(scale_target(&one, u64::MAX, 1), HashValue::MAX);
+        assert_eq!(scale_target(&HashValue::MAX, 2, 1), HashValue::MAX);
+    }
+
+    #[test]
+    fn bytes_serde() {
+        let hash = HashValue::new(Sha256::digest(b"hello world").into());
+
+        let json = serde_json::to_string(&hash).unwrap();
+        assert_eq!(
+            json,
+            "\"0xb94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9\""
+        );
+        assert_eq!(serde_json::from_str::<HashValue>(&json).unwrap(), hash);
+
+        let binary = bincode::serialize(&hash).unwrap();
+        assert_eq!(binary, hash.to_vec());
+        assert_eq!(bincode::deserialize::<HashValue>(&binary).unwrap(), hash);
+        assert!(bincode::deserialize::<HashValue>(&binary[..31]).is_err());
+    }
 }
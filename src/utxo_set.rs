@@ -0,0 +1,193 @@
+use crate::block::{merkle_root, Block};
+use crate::transaction::{Output, Transaction};
+use crate::types::HashValue;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// reference to a single output of a transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct OutPoint {
+    pub tx_id: HashValue,    // id of the transaction that created the output
+    pub output_index: usize, // index of the output in the transaction's outputs
+}
+
+impl OutPoint {
+    pub fn new(tx_id: HashValue, output_index: usize) -> Self {
+        OutPoint {
+            tx_id,
+            output_index,
+        }
+    }
+}
+
+/// an unspent output together with where it was created
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UtxoEntry {
+    pub outpoint: OutPoint, // the transaction id and output index
+    pub block_index: usize, // the index of the block that contains the transaction
+    pub is_coinbase: bool,  // whether the output was created by a coinbase transaction
+    pub output: Output,     // the output itself, amount and locking script
+}
+
+impl UtxoEntry {
+    /// calculate the hash value of the entry, covering every field
+    pub fn sha256(&self) -> HashValue {
+        let mut hasher = Sha256::new();
+        hasher.update(self.outpoint.tx_id);
+        hasher.update(self.outpoint.output_index.to_be_bytes());
+        hasher.update(self.block_index.to_be_bytes());
+        hasher.update([self.is_coinbase as u8]);
+        hasher.update(serde_json::to_vec(&self.output.get_amount()).unwrap());
+        hasher.update(self.output.get_locking_script());
+        let result = hasher.finalize().into();
+        HashValue::new(result)
+    }
+}
+
+/// The set of all unspent transaction outputs of a blockchain.
+///
+/// the set is updated block by block:
+/// * outputs spent by the inputs of a block are removed
+/// * outputs created by the transactions of a block are added
+///
+/// entries are indexed by their `OutPoint`, and additionally grouped by locking script,
+/// so that the unspent outputs of an address can be found without scanning the chain.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(from = "Vec<UtxoEntry>", into = "Vec<UtxoEntry>")]
+pub struct UtxoSet {
+    entries: BTreeMap<OutPoint, UtxoEntry>, // all the unspent outputs
+    by_locking_script: BTreeMap<Vec<u8>, BTreeSet<OutPoint>>, // outpoints grouped by locking script
+}
+
+impl UtxoSet {
+    /// create an empty UTXO set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// build the UTXO set by applying all the blocks one by one
+    ///
+    /// the blocks must be arranged in the correct order:
+    ///
+    /// genesis block -> block 1 -> block 2 -> ... -> block n
+    pub fn from_blocks(blocks: &[Block]) -> Self {
+        let mut utxo_set = Self::new();
+        for block in blocks {
+            utxo_set.apply_block(block);
+        }
+        utxo_set
+    }
+
+    /// update the set with a new block:
+    /// remove the outputs spent by its inputs and add the outputs it creates,
+    /// except the data outputs, which can never be spent, see `Output::new_data`
+    pub fn apply_block(&mut self, block: &Block) {
+        for tx in &block.data {
+            self.apply_transaction(tx, block.index);
+        }
+    }
+
+    fn apply_transaction(&mut self, tx: &Transaction, block_index: usize) {
+        for input in tx.get_inputs() {
+            self.remove(&OutPoint::new(
+                input.get_prev_tx_hash(),
+                input.get_prev_output_index(),
+            ));
+        }
+
+        let is_coinbase = Transaction::is_coinbase_transaction(tx);
+        for (output_index, output) in tx.get_outputs().iter().enumerate() {
+            if output.is_unspendable() {
+                continue;
+            }
+            self.insert(UtxoEntry {
+                outpoint: OutPoint::new(tx.get_transaction_id(), output_index),
+                block_index,
+                is_coinbase,
+                output: output.clone(),
+            });
+        }
+    }
+
+    fn insert(&mut self, entry: UtxoEntry) {
+        self.by_locking_script
+            .entry(entry.output.get_locking_script().clone())
+            .or_default()
+            .insert(entry.outpoint);
+        self.entries.insert(entry.outpoint, entry);
+    }
+
+    fn remove(&mut self, outpoint: &OutPoint) -> Option<UtxoEntry> {
+        let entry = self.entries.remove(outpoint)?;
+        let locking_script = entry.output.get_locking_script();
+        if let Some(outpoints) = self.by_locking_script.get_mut(locking_script) {
+            outpoints.remove(outpoint);
+            if outpoints.is_empty() {
+                self.by_locking_script.remove(locking_script);
+            }
+        }
+        Some(entry)
+    }
+
+    /// get an unspent output by its outpoint,
+    /// return `None` if the output does not exist or has been spent
+    pub fn get(&self, outpoint: &OutPoint) -> Option<&UtxoEntry> {
+        self.entries.get(outpoint)
+    }
+
+    /// check if an output is still unspent
+    pub fn contains(&self, outpoint: &OutPoint) -> bool {
+        self.entries.contains_key(outpoint)
+    }
+
+    /// get all the unspent outputs locked by the given locking script
+    pub fn get_by_locking_script(&self, locking_script: &[u8]) -> Vec<&UtxoEntry> {
+        match self.by_locking_script.get(locking_script) {
+            Some(outpoints) => outpoints
+                .iter()
+                .filter_map(|outpoint| self.entries.get(outpoint))
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    /// calculate a commitment to the whole set:
+    /// the merkle root of the hashes of all the entries, ordered by outpoint.
+    ///
+    /// two UTXO sets are identical if and only if their commitments are equal,
+    /// so nodes can compare their sets in one comparison.
+    pub fn commitment(&self) -> HashValue {
+        merkle_root(self.entries.values().map(UtxoEntry::sha256).collect())
+    }
+
+    /// iterate over all the unspent outputs, ordered by outpoint
+    pub fn iter(&self) -> impl Iterator<Item = &UtxoEntry> {
+        self.entries.values()
+    }
+
+    /// number of unspent outputs in the set
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl From<Vec<UtxoEntry>> for UtxoSet {
+    fn from(entries: Vec<UtxoEntry>) -> Self {
+        let mut utxo_set = Self::new();
+        for entry in entries {
+            utxo_set.insert(entry);
+        }
+        utxo_set
+    }
+}
+
+impl From<UtxoSet> for Vec<UtxoEntry> {
+    fn from(utxo_set: UtxoSet) -> Self {
+        utxo_set.entries.into_values().collect()
+    }
+}
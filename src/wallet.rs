@@ -1,21 +1,52 @@
+use crate::blockchain::{Blockchain, REGULAR_MATURITY};
 use crate::errors::RustyCoinError;
-use crate::errors::RustyCoinError::{InvalidInputFee, InvalidOutputIndex};
-use crate::transaction::{Input, Output, Transaction};
-use crate::types::HashValue;
+use crate::errors::RustyCoinError::{InsufficientFunds, InvalidOutputIndex};
+use crate::transaction::{
+    sum_amounts, Input, Output, SigHashType, Transaction, TransactionBuilder,
+};
+use crate::types::{address_checksum, HashValue, Signature};
+use crate::utxo_set::UtxoEntry;
+use bip39::Mnemonic;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use rand::RngCore;
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
-use secp256k1::{generate_keypair, PublicKey, Secp256k1, SecretKey};
+use secp256k1::constants::SECRET_KEY_SIZE;
+use secp256k1::ecdsa::{RecoverableSignature, RecoveryId};
+use secp256k1::{generate_keypair, Message, PublicKey, Scalar, Secp256k1, SecretKey, SECP256K1};
 use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
 use std::fs;
 use std::fs::File;
 use std::io::Write;
 
-#[derive(Debug, PartialEq)]
+/// PBKDF2 rounds deriving the encryption key of a key file from the passphrase
+const KEY_DERIVATION_ROUNDS: u32 = 100_000;
+const SALT_SIZE: usize = 16;
+const NONCE_SIZE: usize = 12;
+/// version byte prepended to the private key in Wallet Import Format
+const WIF_VERSION: u8 = 0x80;
+/// suffix of a WIF private key whose public key is serialized in compressed form
+const WIF_COMPRESSED_FLAG: u8 = 0x01;
+/// prefix of the messages signed by `Wallet::sign_message`,
+/// so that a signed message can never be mistaken for a transaction
+const MESSAGE_PREFIX: &[u8] = b"Rusty Coin Signed Message:\n";
+
+/// the transaction fee paid by the transactions built with `Wallet::send`
+pub const DEFAULT_TX_FEE: Decimal = dec!(0.01);
+/// change smaller than this is not worth an output, it is left to the miner as transaction fee
+pub const DUST_THRESHOLD: Decimal = dec!(0.01);
+/// how many unused change addresses in a row `Wallet::scan_chain` derives
+/// before it assumes no later change address has been used
+pub const CHANGE_ADDRESS_GAP_LIMIT: usize = 20;
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct UTXO {
-    pub prev_tx: Transaction,     // the output
-    pub prev_block_index: usize,  // the index of the block that contains the previous transaction
+    pub prev_output: Option<Output>, // the output, None if the output index is out of range of the previous transaction
+    pub prev_block_index: usize, // the index of the block that contains the previous transaction
     pub prev_output_index: usize, // the index of the output in the transaction's outputs
-    pub prev_tx_hash: HashValue,  // Hash of previous transaction
+    pub prev_tx_hash: HashValue, // Hash of previous transaction
 }
 
 impl UTXO {
@@ -23,12 +54,56 @@ impl UTXO {
         let prev_tx_hash = prev_tx.sha256();
 
         UTXO {
-            prev_tx,
+            prev_output: prev_tx.get_output_by_index(prev_output_index).cloned(),
             prev_block_index,
             prev_output_index,
             prev_tx_hash,
         }
     }
+
+    /// build the unspent output from an entry of the UTXO set,
+    /// without the previous transaction, e.g. its block has been pruned
+    pub fn from_entry(entry: &UtxoEntry) -> Self {
+        UTXO {
+            prev_output: Some(entry.output.clone()),
+            prev_block_index: entry.block_index,
+            prev_output_index: entry.outpoint.output_index,
+            prev_tx_hash: entry.outpoint.tx_id,
+        }
+    }
+
+    /// get the amount of the output, 0 if the output index is invalid
+    pub fn get_amount(&self) -> Decimal {
+        self.amount().unwrap_or(dec!(0.0))
+    }
+
+    /// get the amount of the output, `None` if the output index is invalid
+    pub fn amount(&self) -> Option<Decimal> {
+        self.prev_output.as_ref().map(Output::get_amount)
+    }
+
+    /// get the locking script of the output, `None` if the output index is invalid
+    pub fn locking_script(&self) -> Option<&Vec<u8>> {
+        self.prev_output.as_ref().map(Output::get_locking_script)
+    }
+}
+
+/// whether a transaction moved coins into or out of a wallet, see `Wallet::transaction_history`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxDirection {
+    Incoming, // the wallet received more than it spent
+    Outgoing, // the wallet spent more than it received, e.g. a payment or only the fee of a self-transfer
+}
+
+/// an on-chain transaction that credited or debited a wallet, see `Wallet::transaction_history`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TxHistoryEntry {
+    pub tx_id: HashValue,             // the ID of the transaction
+    pub direction: TxDirection,       // whether the balance of the wallet went up or down
+    pub amount: Decimal, // how much the balance changed, the transaction fee included for an outgoing transaction
+    pub counterparts: Vec<HashValue>, // the senders of an incoming transaction, the receivers of an outgoing one
+    pub block_index: usize,           // the height of the block containing the transaction
+    pub confirmations: usize,         // see `Blockchain::confirmations`
 }
 
 /// a wallet contains a public key, a secret key, a list of unspent transaction outputs and an address.
@@ -42,7 +117,9 @@ pub struct Wallet {
     public_key: PublicKey,
     secret_key: SecretKey,
     unspent_tx_outputs: Vec<UTXO>,
-    address: HashValue, // SHA 256 hash of public key
+    address: HashValue,                       // SHA 256 hash of public key
+    change_keys: Vec<(SecretKey, PublicKey)>, // keys of the change addresses derived so far
+    chain_id: u32,                            // the network the transactions are built for
 }
 
 impl Wallet {
@@ -58,6 +135,8 @@ impl Wallet {
             secret_key,
             unspent_tx_outputs: Vec::new(),
             address,
+            change_keys: Vec::new(),
+            chain_id: 0,
         }
     }
     /// transfer credit to another wallet / other wallets.
@@ -73,63 +152,383 @@ impl Wallet {
         receivers: Vec<(Decimal, HashValue)>,
         extra_info: Option<Vec<u8>>,
     ) -> Result<Transaction, RustyCoinError> {
-        let mut input_fee = dec!(0.0);
-        let inputs: Result<Vec<Input>, RustyCoinError> = utxos
+        // in P2PKH, the locking script is the address of the receiver
+        let receivers = receivers
             .into_iter()
-            .map(|utxo| {
-                // create corresponding unlocking script of each input
-                let unlocking_script =
-                    Input::generate_unlock_script(&utxo.prev_tx, self.secret_key, self.public_key);
-
-                //if the previous transaction do not have enough outputs, return error
-                match utxo.prev_tx.get_output_by_index(utxo.prev_output_index) {
-                    // sum the input fee
-                    Some(output) => input_fee += output.get_amount(),
-                    None => return Err(InvalidOutputIndex),
-                };
+            .map(|(amount, address)| (amount, address.to_vec()))
+            .collect();
 
-                // create input
-                let input = Input::new(
-                    utxo.prev_tx_hash,
-                    utxo.prev_block_index,
-                    utxo.prev_output_index,
-                    unlocking_script,
-                );
+        self.transfer_to_scripts(utxos, receivers, extra_info)
+    }
 
-                Ok(input)
-            })
-            .collect();
+    /// transfer credit to arbitrary locking scripts, e.g. multisig or P2SH outputs.
+    /// create a signed transaction and sign it with the secret key.
+    /// # Arguments
+    /// * `utxos`: a vector of UTXO in your wallet
+    /// * `receivers`: a vector of tuple, the tuple is consist of
+    ///     - a `amount: Decimal` in rust_decimal, the amount that transfer to target
+    ///     - a `locking_script: Vec<u8>`, the script that locks the output
+    pub fn transfer_to_scripts(
+        &self,
+        utxos: Vec<UTXO>,
+        receivers: Vec<(Decimal, Vec<u8>)>,
+        extra_info: Option<Vec<u8>>,
+    ) -> Result<Transaction, RustyCoinError> {
+        let mut builder = TransactionBuilder::new();
+        let mut locking_scripts = Vec::with_capacity(utxos.len());
+        for utxo in utxos {
+            //if the previous transaction do not have enough outputs, return error
+            let (Some(amount), Some(locking_script)) = (utxo.amount(), utxo.locking_script())
+            else {
+                return Err(InvalidOutputIndex);
+            };
+            locking_scripts.push(locking_script.clone());
 
-        let inputs = inputs?; // Unwrap the Result, return Error if exists
+            // create input, the unlocking script is generated after the transaction is drafted
+            let input = Input::new(
+                utxo.prev_tx_hash,
+                utxo.prev_block_index,
+                utxo.prev_output_index,
+                vec![],
+            );
+            builder = builder.add_input(input, amount);
+        }
 
         // create outputs
-        let outputs: Vec<Output> = receivers
-            .into_iter()
-            .map(|(amount, address)| Output::new(amount, address.to_vec()))
+        for (amount, locking_script) in receivers {
+            builder = builder.add_output(Output::new(amount, locking_script)?);
+        }
+
+        if let Some(extra_info) = extra_info {
+            builder = builder.additional_data(extra_info);
+        }
+        builder = builder.chain_id(self.chain_id);
+
+        // sign every input with the key of the address it spends from,
+        // the signature commits to the inputs and outputs of this transaction
+        builder.build_signed(|tx, input_index| {
+            let (secret_key, public_key) =
+                self.key_for_locking_script(&locking_scripts[input_index]);
+            Some(Input::generate_unlock_script(
+                tx,
+                input_index,
+                secret_key,
+                public_key,
+            ))
+        })
+    }
+
+    /// select UTXOs from the wallet to cover `target` plus `DEFAULT_TX_FEE`.
+    ///
+    /// - if a single UTXO covers it with less than `DUST_THRESHOLD` left over, the smallest such UTXO is used
+    /// - otherwise UTXOs are picked largest-first until the amount is covered,
+    ///   then the smallest picked ones that turn out to be unnecessary are dropped
+    ///
+    /// returns `InsufficientFunds` if all the UTXOs in the wallet can't cover it,
+    /// `AmountOverflow` if the amount plus the fee, or the picked UTXOs, sum up beyond `Decimal`
+    pub fn select_utxos(&self, target: Decimal) -> Result<Vec<UTXO>, RustyCoinError> {
+        let needed = target
+            .checked_add(DEFAULT_TX_FEE)
+            .ok_or(RustyCoinError::AmountOverflow)?;
+
+        // an exact-ish match, no change output needed
+        if let Some(utxo) = self
+            .unspent_tx_outputs
+            .iter()
+            .filter(|utxo| {
+                utxo.get_amount() >= needed && utxo.get_amount() - needed < DUST_THRESHOLD
+            })
+            .min_by_key(|utxo| utxo.get_amount())
+        {
+            return Ok(vec![utxo.clone()]);
+        }
+
+        // largest first
+        let mut candidates = self.unspent_tx_outputs.iter().collect::<Vec<&UTXO>>();
+        candidates.sort_by_key(|utxo| std::cmp::Reverse(utxo.get_amount()));
+
+        let mut selected = vec![];
+        let mut selected_sum = dec!(0.0);
+        for utxo in candidates {
+            if selected_sum >= needed {
+                break;
+            }
+            selected_sum = selected_sum
+                .checked_add(utxo.get_amount())
+                .ok_or(RustyCoinError::AmountOverflow)?;
+            selected.push(utxo);
+        }
+        if selected_sum < needed {
+            return Err(InsufficientFunds);
+        }
+
+        // drop the smallest UTXOs if the rest still cover the amount
+        while let Some(smallest) = selected.last() {
+            if selected_sum - smallest.get_amount() < needed {
+                break;
+            }
+            selected_sum -= smallest.get_amount();
+            selected.pop();
+        }
+
+        Ok(selected.into_iter().cloned().collect())
+    }
+
+    /// transfer credit to other wallets, selecting the UTXOs from this wallet.
+    ///
+    /// `DEFAULT_TX_FEE` is paid as transaction fee, the change goes back to a fresh change address
+    /// of this wallet (see `new_change_address`),
+    /// unless it is smaller than `DUST_THRESHOLD`, then it is added to the transaction fee.
+    /// # Arguments
+    /// * `receivers`: a vector of tuple, the tuple is consist of
+    ///     - a `amount: Decimal` in rust_decimal, the amount that transfer to target
+    ///     - a `address: HashValue`, the address of the receiver (or public key hash of receiver)
+    pub fn send(
+        &mut self,
+        receivers: Vec<(Decimal, HashValue)>,
+    ) -> Result<Transaction, RustyCoinError> {
+        let target = sum_amounts(receivers.iter().map(|(amount, _address)| *amount))?;
+        if receivers
+            .iter()
+            .any(|(amount, _address)| *amount < dec!(0.0))
+        {
+            return Err(RustyCoinError::InvalidOutputAmount);
+        }
+
+        let utxos = self.select_utxos(target)?;
+        let change = sum_amounts(utxos.iter().map(UTXO::get_amount))? - target - DEFAULT_TX_FEE;
+
+        let mut receivers = receivers;
+        if change >= DUST_THRESHOLD {
+            receivers.push((change, self.new_change_address()));
+        }
+
+        self.transfer_credits(utxos, receivers, None)
+    }
+
+    /// derive the next change address of the wallet and remember its key,
+    /// so that the change sent to it can be spent later.
+    ///
+    /// the n-th change key is the secret key tweaked by SHA256(public key || n),
+    /// a wallet restored from the same secret key derives the same change addresses again.
+    pub fn new_change_address(&mut self) -> HashValue {
+        let mut hasher = Sha256::new();
+        hasher.update(self.public_key.serialize());
+        hasher.update((self.change_keys.len() as u64).to_be_bytes());
+        // the tweak is out of range or cancels the secret key with negligible probability
+        let tweak = Scalar::from_be_bytes(hasher.finalize().into()).unwrap();
+        let secret_key = self.secret_key.add_tweak(&tweak).unwrap();
+        let public_key = PublicKey::from_secret_key(SECP256K1, &secret_key);
+
+        self.change_keys.push((secret_key, public_key));
+        public_key_to_hash(public_key)
+    }
+
+    /// derive the next change keys until the wallet has `count` of them, see `new_change_address`
+    fn derive_change_keys(&mut self, count: usize) {
+        while self.change_keys.len() < count {
+            self.new_change_address();
+        }
+    }
+
+    /// get all the addresses of the wallet: the main address followed by the change addresses,
+    /// query the UTXOs of every one of them to get the whole balance of the wallet
+    pub fn get_addresses(&self) -> Vec<HashValue> {
+        std::iter::once(self.address)
+            .chain(
+                self.change_keys
+                    .iter()
+                    .map(|(_secret_key, public_key)| public_key_to_hash(*public_key)),
+            )
+            .collect()
+    }
+
+    /// check if an address belongs to the wallet, the main address or one of the change addresses
+    pub fn is_own_address(&self, address: &HashValue) -> bool {
+        self.get_addresses().contains(address)
+    }
+
+    /// the key pair that unlocks a P2PKH locking script of the wallet,
+    /// the main key pair if the script is not locked to a change address
+    fn key_for_locking_script(&self, locking_script: &[u8]) -> (SecretKey, PublicKey) {
+        self.change_keys
+            .iter()
+            .find(|(_secret_key, public_key)| {
+                public_key_to_hash(*public_key).to_vec() == locking_script
+            })
+            .copied()
+            .unwrap_or((self.secret_key, self.public_key))
+    }
+
+    /// replace the UTXOs known by the wallet, e.g. with `Blockchain::get_utxos_for`
+    pub fn update_unspent_tx_outputs(&mut self, utxos: Vec<UTXO>) {
+        self.unspent_tx_outputs = utxos;
+    }
+
+    /// rescan `chain` for the spendable outputs locked to the addresses of the wallet,
+    /// those with `REGULAR_MATURITY` confirmations (and mature if created by a coinbase transaction),
+    /// and replace the UTXOs known by the wallet with them,
+    /// e.g. to learn the balance of a wallet restored from a key file or a mnemonic.
+    ///
+    /// the change addresses are derived again until `CHANGE_ADDRESS_GAP_LIMIT` of them in a row
+    /// have never received an output on `chain`, the wallet keeps those up to the last used one
+    pub fn scan_chain(&mut self, chain: &Blockchain) {
+        let used_locking_scripts = chain
+            .iter()
+            .flat_map(|block| block.data.iter())
+            .flat_map(Transaction::get_outputs)
+            .map(Output::get_locking_script)
+            .collect::<BTreeSet<&Vec<u8>>>();
+        let is_used = |public_key: &PublicKey| {
+            used_locking_scripts.contains(&public_key_to_hash(*public_key).to_vec())
+        };
+
+        let known_change_keys = self.change_keys.len();
+        let mut gap = self
+            .change_keys
+            .iter()
+            .rev()
+            .take_while(|(_secret_key, public_key)| !is_used(public_key))
+            .count();
+        while gap < CHANGE_ADDRESS_GAP_LIMIT {
+            self.new_change_address();
+            let (_secret_key, public_key) = self.change_keys.last().unwrap();
+            gap = if is_used(public_key) { 0 } else { gap + 1 };
+        }
+        // the unused change keys are derived again when they are needed
+        let used_change_keys = self.change_keys.len() - gap;
+        self.change_keys
+            .truncate(used_change_keys.max(known_change_keys));
+
+        self.unspent_tx_outputs = self
+            .get_addresses()
+            .iter()
+            .flat_map(|address| chain.get_utxos_for(address, REGULAR_MATURITY))
             .collect();
+    }
 
-        // sum the output fee
-        let output_fee: Decimal = outputs.iter().map(|output| output.get_amount()).sum();
+    /// list the transactions on `chain` that credited or debited the addresses of the wallet,
+    /// newest first.
+    ///
+    /// a transaction credits the wallet with its outputs locked to the wallet,
+    /// and debits it with the outputs of the wallet that its inputs spend.
+    /// the counterparts are the other addresses involved, none for a coinbase transaction.
+    ///
+    /// returns `AmountOverflow` if the outputs of a transaction sum up beyond `Decimal`
+    pub fn transaction_history(
+        &self,
+        chain: &Blockchain,
+    ) -> Result<Vec<TxHistoryEntry>, RustyCoinError> {
+        let addresses = self.get_addresses();
+        let address_of = |locking_script: &Vec<u8>| HashValue::try_from(&locking_script[..]).ok();
+        let is_own = |locking_script: &Vec<u8>| {
+            address_of(locking_script).is_some_and(|address| addresses.contains(&address))
+        };
+
+        let mut history = Vec::new();
+        for block in chain.iter().rev() {
+            for tx in block.data.iter().rev() {
+                let spent_outputs: Vec<&Output> = tx
+                    .get_inputs()
+                    .iter()
+                    .filter_map(|input| {
+                        chain.find_output(input.get_prev_tx_hash(), input.get_prev_output_index())
+                    })
+                    .collect();
+                let (own_spent, other_spent): (Vec<&Output>, Vec<&Output>) = spent_outputs
+                    .into_iter()
+                    .partition(|output| is_own(output.get_locking_script()));
+                let (own_received, other_received): (Vec<&Output>, Vec<&Output>) = tx
+                    .get_outputs()
+                    .iter()
+                    .partition(|output| is_own(output.get_locking_script()));
+                if own_spent.is_empty() && own_received.is_empty() {
+                    continue;
+                }
 
-        let tx_fee = input_fee - output_fee;
+                let total = |outputs: &[&Output]| {
+                    sum_amounts(outputs.iter().map(|output| output.get_amount()))
+                };
+                let net = total(&own_received)? - total(&own_spent)?;
+                let (direction, others) = if net > dec!(0.0) {
+                    (TxDirection::Incoming, other_spent)
+                } else {
+                    (TxDirection::Outgoing, other_received)
+                };
+                let mut counterparts = Vec::new();
+                for address in others
+                    .iter()
+                    .filter_map(|output| address_of(output.get_locking_script()))
+                {
+                    if !counterparts.contains(&address) {
+                        counterparts.push(address);
+                    }
+                }
 
-        if tx_fee < dec!(0.0) {
-            return Err(InvalidInputFee);
+                history.push(TxHistoryEntry {
+                    tx_id: tx.get_transaction_id(),
+                    direction,
+                    amount: net.abs(),
+                    counterparts,
+                    block_index: block.index,
+                    confirmations: chain.len() - block.index,
+                });
+            }
         }
+        Ok(history)
+    }
 
-        // create transaction
-        let mut tx = Transaction::new(
-            inputs,
-            outputs,
-            HashValue::new([0u8; 32]),
-            tx_fee,
-            extra_info,
-        );
+    /// get the UTXOs known by the wallet
+    pub fn get_unspent_tx_outputs(&self) -> &[UTXO] {
+        &self.unspent_tx_outputs
+    }
 
-        // update the digest of the transaction
-        tx.update_digest();
+    /// build the transactions for the network with the chain ID `chain_id`,
+    /// e.g. with `Blockchain::get_chain_id`, they are rejected by the other networks
+    pub fn set_chain_id(&mut self, chain_id: u32) {
+        self.chain_id = chain_id;
+    }
 
-        Ok(tx)
+    /// get the ID of the network the transactions are built for, 0 by default
+    pub fn get_chain_id(&self) -> u32 {
+        self.chain_id
+    }
+
+    /// sign one input of a drafted transaction, return the compact signature followed by
+    /// `SigHashType::All`, e.g. one of the signatures needed to spend a multisig output
+    pub fn sign_input(&self, spending_transaction: &Transaction, input_index: usize) -> Vec<u8> {
+        Input::generate_signature(spending_transaction, input_index, self.secret_key)
+    }
+
+    /// same as `sign_input`, the signature commits to the parts of the transaction chosen by
+    /// `sighash_type`, e.g. `SigHashType::None` to let the other signers choose the outputs
+    ///
+    /// return `NoOutputForSigHashSingle` if `sighash_type` is `SigHashType::Single`,
+    /// but the transaction has no output at the index of the input
+    pub fn sign_input_with(
+        &self,
+        spending_transaction: &Transaction,
+        input_index: usize,
+        sighash_type: SigHashType,
+    ) -> Result<Vec<u8>, RustyCoinError> {
+        Input::generate_signature_with(
+            spending_transaction,
+            input_index,
+            self.secret_key,
+            sighash_type,
+        )
+    }
+
+    /// sign an arbitrary message to prove the ownership of the address off-chain,
+    /// verify the signature with `verify_message`
+    pub fn sign_message(&self, msg: &[u8]) -> Signature {
+        let signature = SECP256K1.sign_ecdsa_recoverable(&message_digest(msg), &self.secret_key);
+        let (recovery_id, compact_signature) = signature.serialize_compact();
+
+        let mut bytes = [0u8; 65];
+        bytes[0] = recovery_id.to_i32() as u8;
+        bytes[1..].copy_from_slice(&compact_signature);
+        Signature::new(bytes)
     }
 
     pub fn get_public_key(&self) -> PublicKey {
@@ -140,6 +539,12 @@ impl Wallet {
         self.address
     }
 
+    /// the address encoded in Base58Check, for users to copy and paste,
+    /// decode it with `HashValue::from_address_string`
+    pub fn address_string(&self) -> String {
+        self.address.to_address_string()
+    }
+
     /// read secret key from a file,
     ///
     /// public key can be generated from the secret key,
@@ -148,18 +553,58 @@ impl Wallet {
     pub fn build_from_private_key_file(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
         let content = fs::read(path)?;
 
-        let secret_key = SecretKey::from_slice(content.as_slice())?;
+        let (secret_key, change_key_count) = split_key_file(&content)?;
+
+        let mut wallet = Self::from_secret_key(secret_key);
+        wallet.derive_change_keys(change_key_count);
+        Ok(wallet)
+    }
+
+    /// restore a wallet from a 12/24-word BIP39 mnemonic and an optional passphrase ("" for none),
+    /// the secret key is the first 32 bytes of the BIP39 seed.
+    ///
+    /// return `InvalidMnemonic` if a word is unknown or the checksum does not match
+    pub fn from_mnemonic(phrase: &str, passphrase: &str) -> Result<Self, RustyCoinError> {
+        let mnemonic = Mnemonic::parse(phrase).map_err(|_| RustyCoinError::InvalidMnemonic)?;
+        let seed = mnemonic.to_seed(passphrase);
+        let secret_key =
+            SecretKey::from_slice(&seed[..32]).map_err(|_| RustyCoinError::InvalidMnemonic)?;
+
+        Ok(Self::from_secret_key(secret_key))
+    }
+
+    /// create a new wallet together with its 12-word BIP39 mnemonic,
+    /// the wallet can be restored with `Wallet::from_mnemonic(phrase, "")`
+    pub fn generate_mnemonic() -> (Self, String) {
+        loop {
+            let mut entropy = [0u8; 16];
+            rand::thread_rng().fill_bytes(&mut entropy);
+            let phrase = Mnemonic::from_entropy(&entropy).unwrap().to_string();
+            // the seed is an invalid secret key with negligible probability, try again then
+            if let Ok(wallet) = Self::from_mnemonic(&phrase, "") {
+                return (wallet, phrase);
+            }
+        }
+    }
+
+    /// build a wallet from an existing secret key,
+    /// the public key and the address are derived from it.
+    pub fn from_secret_key(secret_key: SecretKey) -> Self {
         let public_key = PublicKey::from_secret_key(&Secp256k1::new(), &secret_key);
 
-        Ok(Wallet {
+        Wallet {
             public_key,
             secret_key,
             unspent_tx_outputs: Vec::new(),
             address: public_key_to_hash(public_key),
-        })
+            change_keys: Vec::new(),
+            chain_id: 0,
+        }
     }
 
-    /// export the private key to a binary file
+    /// export the private key to a binary file,
+    /// followed by the number of change keys derived so far (8 bytes, big endian),
+    /// so the change addresses are derived again when the wallet is loaded
     ///
     /// later the wallet can be recovered from method
     ///
@@ -167,8 +612,154 @@ impl Wallet {
     pub fn save_private_key_to_file(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
         let mut file = File::create(path)?;
         file.write_all(&self.secret_key[..])?;
+        file.write_all(&(self.change_keys.len() as u64).to_be_bytes())?;
+        Ok(())
+    }
+
+    /// export the private key in Wallet Import Format (WIF):
+    /// version byte + 32 bytes key + compression flag + first 4 bytes of the double SHA256 of them,
+    /// encoded in Base58.
+    ///
+    /// import it with `Wallet::from_wif`
+    pub fn to_wif(&self) -> String {
+        let mut bytes = vec![WIF_VERSION];
+        bytes.extend_from_slice(&self.secret_key[..]);
+        bytes.push(WIF_COMPRESSED_FLAG);
+        bytes.extend_from_slice(&address_checksum(&bytes));
+        bs58::encode(bytes).into_string()
+    }
+
+    /// import a private key in Wallet Import Format, with or without the compression flag.
+    ///
+    /// return `InvalidPrivateKey` if it is not valid Base58, has a wrong length, version or checksum,
+    /// or the key is out of range
+    pub fn from_wif(wif: &str) -> Result<Self, RustyCoinError> {
+        let bytes = bs58::decode(wif)
+            .into_vec()
+            .map_err(|_| RustyCoinError::InvalidPrivateKey)?;
+        let key_end = match bytes.len() {
+            37 => 33,                                           // uncompressed
+            38 if bytes[33] == WIF_COMPRESSED_FLAG => 34,       // compressed
+            _ => return Err(RustyCoinError::InvalidPrivateKey), // wrong length or flag
+        };
+        if bytes[0] != WIF_VERSION {
+            return Err(RustyCoinError::InvalidPrivateKey);
+        }
+
+        let (payload, checksum) = bytes.split_at(key_end);
+        if address_checksum(payload) != checksum {
+            return Err(RustyCoinError::InvalidPrivateKey);
+        }
+
+        let secret_key = SecretKey::from_slice(&payload[1..33])
+            .map_err(|_| RustyCoinError::InvalidPrivateKey)?;
+        Ok(Self::from_secret_key(secret_key))
+    }
+
+    /// export the private key to a binary file, encrypted with a passphrase.
+    ///
+    /// the key is derived from the passphrase with PBKDF2-HMAC-SHA256 and a random salt,
+    /// then the private key and the number of change keys derived so far (8 bytes, big endian)
+    /// are encrypted with ChaCha20-Poly1305,
+    /// the file contains: salt (16 bytes) + nonce (12 bytes) + ciphertext (56 bytes)
+    ///
+    /// later the wallet can be recovered from method
+    ///
+    /// `Wallet::load_encrypted(path: &str, passphrase: &str)`
+    pub fn save_encrypted(
+        &self,
+        path: &str,
+        passphrase: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut salt = [0u8; SALT_SIZE];
+        let mut nonce = [0u8; NONCE_SIZE];
+        rand::thread_rng().fill_bytes(&mut salt);
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        let mut plaintext = self.secret_key[..].to_vec();
+        plaintext.extend_from_slice(&(self.change_keys.len() as u64).to_be_bytes());
+        let cipher = key_file_cipher(passphrase, &salt);
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+            .map_err(|_| "failed to encrypt the private key")?;
+
+        let mut file = File::create(path)?;
+        file.write_all(&salt)?;
+        file.write_all(&nonce)?;
+        file.write_all(&ciphertext)?;
         Ok(())
     }
+
+    /// read an encrypted private key file written by `Wallet::save_encrypted`,
+    /// fail if the passphrase is wrong or the file has been tampered with
+    pub fn load_encrypted(
+        path: &str,
+        passphrase: &str,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = fs::read(path)?;
+        if content.len() < SALT_SIZE + NONCE_SIZE {
+            return Err("the key file is too short".into());
+        }
+        let (salt, rest) = content.split_at(SALT_SIZE);
+        let (nonce, ciphertext) = rest.split_at(NONCE_SIZE);
+
+        let cipher = key_file_cipher(passphrase, salt);
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| "wrong passphrase or corrupted key file")?;
+
+        let (secret_key, change_key_count) = split_key_file(&plaintext)?;
+        let mut wallet = Self::from_secret_key(secret_key);
+        wallet.derive_change_keys(change_key_count);
+        Ok(wallet)
+    }
+}
+
+/// verify a signature created by `Wallet::sign_message`:
+/// recover the public key from the signature, and check if it hashes to the address
+pub fn verify_message(address: &HashValue, msg: &[u8], sig: &Signature) -> bool {
+    let Ok(recovery_id) = RecoveryId::from_i32(sig[0] as i32) else {
+        return false;
+    };
+    let Ok(signature) = RecoverableSignature::from_compact(&sig[1..], recovery_id) else {
+        return false;
+    };
+    match SECP256K1.recover_ecdsa(&message_digest(msg), &signature) {
+        Ok(public_key) => public_key_to_hash(public_key).ct_eq(address),
+        Err(_) => false,
+    }
+}
+
+/// the domain-separated digest of a message signed by `Wallet::sign_message`
+fn message_digest(msg: &[u8]) -> Message {
+    let mut hasher = Sha256::new();
+    hasher.update(MESSAGE_PREFIX);
+    hasher.update((msg.len() as u64).to_be_bytes());
+    hasher.update(msg);
+    Message::from_digest(hasher.finalize().into())
+}
+
+/// split the content of a key file into the secret key and the number of change keys,
+/// a key file holding the secret key only has no change keys
+fn split_key_file(content: &[u8]) -> Result<(SecretKey, usize), Box<dyn std::error::Error>> {
+    let (secret_key, change_key_count) = content.split_at(content.len().min(SECRET_KEY_SIZE));
+    let secret_key = SecretKey::from_slice(secret_key)?;
+    let change_key_count = match change_key_count {
+        [] => 0,
+        count => u64::from_be_bytes(
+            count
+                .try_into()
+                .map_err(|_| "invalid number of change keys")?,
+        ) as usize,
+    };
+    Ok((secret_key, change_key_count))
+}
+
+/// derive the cipher of a key file from the passphrase
+fn key_file_cipher(passphrase: &str, salt: &[u8]) -> ChaCha20Poly1305 {
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, KEY_DERIVATION_ROUNDS, &mut key);
+    ChaCha20Poly1305::new(Key::from_slice(&key))
 }
 fn public_key_to_hash(public_key: PublicKey) -> HashValue {
     let mut hasher = Sha256::new();
@@ -188,7 +779,8 @@ mod test {
     #[test]
     fn test_export_and_import_from_a_file() {
         const FILE_PATH: &str = "./test_key.rsckey";
-        let wallet = Wallet::new();
+        let mut wallet = Wallet::new();
+        wallet.new_change_address();
         if let Err(e) = wallet.save_private_key_to_file(FILE_PATH) {
             println!("{}", e)
         };
@@ -199,6 +791,11 @@ mod test {
         println!("{:?}", wallet);
         println!("{:?}", wallet_copied);
         assert_eq!(wallet, wallet_copied);
+
+        // a key file holding the secret key only has no change keys
+        fs::write(FILE_PATH, &wallet.secret_key[..]).unwrap();
+        let wallet_copied = Wallet::build_from_private_key_file(FILE_PATH).unwrap();
+        assert_eq!(wallet_copied.get_addresses(), vec![wallet.get_address()]);
         // delete the key file after testing
         fs::remove_file(FILE_PATH).expect("Delete Fail: No such file");
     }
@@ -218,7 +815,7 @@ mod test {
         let wallet2 = Wallet::new();
         let prev_tx = Transaction::new(
             vec![Input::new(HashValue::new([0u8; 32]), 0, 0, vec![0u8; 32])],
-            vec![Output::new(dec!(1.0), wallet1.address.to_vec())],
+            vec![Output::new(dec!(1.0), wallet1.address.to_vec()).unwrap()],
             HashValue::new([0u8; 32]),
             dec!(0.0),
             None,
@@ -233,4 +830,542 @@ mod test {
 
         println!("{:?}", tx);
     }
+
+    /// mine a block paying the reward of 50 to `receiver`, packing `txs`
+    fn add_block(blockchain: &mut Blockchain, receiver: HashValue, txs: Vec<Transaction>) {
+        use std::time::{SystemTime, UNIX_EPOCH};
+
+        let block = blockchain
+            .generate_new_block(
+                vec![(receiver, dec!(50.0))],
+                "0.1v test".to_string(),
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs(),
+                0x207FFFFF_u32,
+                Some(txs),
+            )
+            .unwrap();
+        blockchain.add_block(block);
+    }
+
+    #[test]
+    fn test_scan_chain() {
+        use crate::blockchain::COINBASE_MATURITY;
+
+        let wallet = Wallet::new();
+        let mut blockchain = Blockchain::new("hello world");
+        add_block(&mut blockchain, wallet.get_address(), vec![]);
+        add_block(&mut blockchain, wallet.get_address(), vec![]);
+        let other = Wallet::new().get_address();
+        for _ in 0..COINBASE_MATURITY {
+            add_block(&mut blockchain, other, vec![]);
+        }
+
+        // a restored wallet knows nothing until it rescans
+        let mut restored = Wallet::from_secret_key(wallet.secret_key);
+        assert!(restored.get_unspent_tx_outputs().is_empty());
+        restored.scan_chain(&blockchain);
+        assert_eq!(
+            restored.get_unspent_tx_outputs(),
+            blockchain.get_utxos_for(&wallet.get_address(), REGULAR_MATURITY)
+        );
+        assert_eq!(restored.get_unspent_tx_outputs().len(), 2);
+        assert_eq!(
+            sum_amounts(
+                restored
+                    .get_unspent_tx_outputs()
+                    .iter()
+                    .map(UTXO::get_amount)
+            )
+            .unwrap(),
+            dec!(100.0)
+        );
+
+        // the spent outputs are left out
+        let tx = restored
+            .transfer_credits(
+                vec![restored.get_unspent_tx_outputs()[0].clone()],
+                vec![(dec!(40.0), other)],
+                None,
+            )
+            .unwrap();
+        add_block(&mut blockchain, other, vec![tx]);
+        restored.scan_chain(&blockchain);
+        assert_eq!(restored.get_unspent_tx_outputs().len(), 1);
+        assert_eq!(
+            sum_amounts(
+                restored
+                    .get_unspent_tx_outputs()
+                    .iter()
+                    .map(UTXO::get_amount)
+            )
+            .unwrap(),
+            blockchain.get_balance(&wallet.get_address()).unwrap()
+        );
+        assert_eq!(
+            blockchain.get_balance(&wallet.get_address()).unwrap(),
+            dec!(50.0)
+        );
+    }
+
+    #[test]
+    fn test_scan_chain_change_addresses() {
+        use crate::blockchain::COINBASE_MATURITY;
+
+        let mut wallet = Wallet::new();
+        let other = Wallet::new().get_address();
+        let mut blockchain = Blockchain::new("hello world");
+        add_block(&mut blockchain, wallet.get_address(), vec![]);
+        for _ in 0..COINBASE_MATURITY {
+            add_block(&mut blockchain, other, vec![]);
+        }
+
+        // send with change, and leave a few change addresses unused before it
+        wallet.scan_chain(&blockchain);
+        for _ in 0..3 {
+            wallet.new_change_address();
+        }
+        let tx = wallet.send(vec![(dec!(20.0), other)]).unwrap();
+        add_block(&mut blockchain, other, vec![tx]);
+        for _ in 0..REGULAR_MATURITY {
+            add_block(&mut blockchain, other, vec![]);
+        }
+
+        // a wallet restored from the key finds the change again
+        let mut restored = Wallet::from_secret_key(wallet.secret_key);
+        restored.scan_chain(&blockchain);
+        assert_eq!(restored.get_addresses(), wallet.get_addresses());
+        assert_eq!(
+            sum_amounts(
+                restored
+                    .get_unspent_tx_outputs()
+                    .iter()
+                    .map(UTXO::get_amount)
+            )
+            .unwrap(),
+            dec!(30.0) - DEFAULT_TX_FEE
+        );
+
+        // and derives the next change address where the original wallet would
+        assert_eq!(restored.new_change_address(), wallet.new_change_address());
+    }
+
+    #[test]
+    fn test_transaction_history() {
+        use crate::blockchain::COINBASE_MATURITY;
+
+        let mut wallet = Wallet::new();
+        let mut friend = Wallet::new();
+        let miner = Wallet::new().get_address();
+        let mut blockchain = Blockchain::new("hello world");
+        add_block(&mut blockchain, wallet.get_address(), vec![]);
+        add_block(&mut blockchain, friend.get_address(), vec![]);
+        for _ in 0..COINBASE_MATURITY {
+            add_block(&mut blockchain, miner, vec![]);
+        }
+
+        // send 20 out, with the change going back to the wallet
+        wallet.scan_chain(&blockchain);
+        let payment = wallet
+            .send(vec![(dec!(20.0), friend.get_address())])
+            .unwrap();
+        add_block(&mut blockchain, miner, vec![payment.clone()]);
+        // receive 5 back
+        friend.scan_chain(&blockchain);
+        let refund = friend
+            .send(vec![(dec!(5.0), wallet.get_address())])
+            .unwrap();
+        add_block(&mut blockchain, miner, vec![refund.clone()]);
+
+        let history = wallet.transaction_history(&blockchain).unwrap();
+        assert_eq!(history.len(), 3);
+
+        assert_eq!(history[0].tx_id, refund.get_transaction_id());
+        assert_eq!(history[0].direction, TxDirection::Incoming);
+        assert_eq!(history[0].amount, dec!(5.0));
+        assert_eq!(history[0].counterparts, vec![friend.get_address()]);
+        assert_eq!(history[0].block_index, blockchain.len() - 1);
+        assert_eq!(history[0].confirmations, 1);
+
+        assert_eq!(history[1].tx_id, payment.get_transaction_id());
+        assert_eq!(history[1].direction, TxDirection::Outgoing);
+        assert_eq!(history[1].amount, dec!(20.0) + DEFAULT_TX_FEE);
+        assert_eq!(history[1].counterparts, vec![friend.get_address()]);
+        assert_eq!(history[1].confirmations, 2);
+
+        // the block reward has no sender
+        assert_eq!(history[2].direction, TxDirection::Incoming);
+        assert_eq!(history[2].amount, dec!(50.0));
+        assert!(history[2].counterparts.is_empty());
+        assert_eq!(history[2].block_index, 1);
+        assert_eq!(
+            Some(history[2].confirmations),
+            blockchain.confirmations(history[2].tx_id)
+        );
+
+        // the friend sees the same transactions the other way round
+        let friend_history = friend.transaction_history(&blockchain).unwrap();
+        assert_eq!(friend_history[0].direction, TxDirection::Outgoing);
+        assert_eq!(friend_history[1].direction, TxDirection::Incoming);
+        assert_eq!(friend_history[1].amount, dec!(20.0));
+        assert_eq!(friend_history[1].counterparts, vec![wallet.get_address()]);
+    }
+
+    #[test]
+    fn test_utxo_output() {
+        let wallet = Wallet::new();
+        let prev_tx = Transaction::new(
+            vec![],
+            vec![Output::new(dec!(1.0), wallet.address.to_vec()).unwrap()],
+            HashValue::new([0u8; 32]),
+            dec!(0.0),
+            None,
+        );
+
+        let utxo = UTXO::new(prev_tx.clone(), 0, 0);
+        assert_eq!(utxo.amount(), Some(dec!(1.0)));
+        assert_eq!(utxo.locking_script(), Some(&wallet.address.to_vec()));
+
+        // the output index is out of range
+        let utxo = UTXO::new(prev_tx, 0, 1);
+        assert_eq!(utxo.amount(), None);
+        assert_eq!(utxo.locking_script(), None);
+        assert_eq!(utxo.get_amount(), dec!(0.0));
+        assert!(matches!(
+            wallet.transfer_credits(vec![utxo], vec![], None),
+            Err(InvalidOutputIndex)
+        ));
+    }
+
+    #[test]
+    fn test_transfer_to_multisig_script() {
+        let wallet = Wallet::new();
+        let cosigners = [Wallet::new(), Wallet::new(), Wallet::new()];
+        let public_keys = cosigners
+            .iter()
+            .map(|cosigner| cosigner.get_public_key())
+            .collect::<Vec<PublicKey>>();
+        let multisig_script = Output::generate_multisig_script(2, &public_keys);
+
+        let prev_tx = Transaction::new(
+            vec![],
+            vec![Output::new(dec!(1.0), wallet.address.to_vec()).unwrap()],
+            HashValue::new([0u8; 32]),
+            dec!(0.0),
+            None,
+        );
+        let tx = wallet
+            .transfer_to_scripts(
+                vec![UTXO::new(prev_tx, 0, 0)],
+                vec![(dec!(0.9), multisig_script.clone())],
+                None,
+            )
+            .unwrap();
+
+        let output = tx.get_output_by_index(0).unwrap();
+        assert_eq!(tx.get_outputs().len(), 1);
+        assert_eq!(output.get_amount(), dec!(0.9));
+        assert_eq!(output.get_locking_script(), &multisig_script);
+        assert_eq!(tx.get_transaction_fee(), dec!(0.1));
+
+        // two of the cosigners sign the spend of the multisig output
+        let mut spending_tx = Transaction::new(
+            vec![Input::new(tx.get_transaction_id(), 1, 0, vec![])],
+            vec![Output::new(dec!(0.9), wallet.address.to_vec()).unwrap()],
+            HashValue::new([0u8; 32]),
+            dec!(0.0),
+            None,
+        );
+        let signatures = [
+            cosigners[0].sign_input(&spending_tx, 0),
+            cosigners[2].sign_input(&spending_tx, 0),
+        ];
+        spending_tx.set_unlock_script(0, Input::generate_multisig_unlock_script(&signatures));
+        spending_tx.update_digest();
+        assert!(Transaction::verify_scripts(
+            &spending_tx,
+            0,
+            spending_tx.get_inputs()[0].get_unlock_script(),
+            &multisig_script
+        ));
+
+        // one signature is not enough
+        let unlock_script = Input::generate_multisig_unlock_script(&signatures[..1]);
+        assert!(!Transaction::verify_scripts(
+            &spending_tx,
+            0,
+            &unlock_script,
+            &multisig_script
+        ));
+    }
+
+    /// a wallet holding one UTXO for every amount
+    fn wallet_with_utxos(amounts: &[Decimal]) -> Wallet {
+        let mut wallet = Wallet::new();
+        let prev_tx = Transaction::new(
+            vec![],
+            amounts
+                .iter()
+                .map(|amount| Output::new(*amount, wallet.address.to_vec()).unwrap())
+                .collect(),
+            HashValue::new([0u8; 32]),
+            dec!(0.0),
+            None,
+        );
+        let utxos = (0..amounts.len())
+            .map(|output_index| UTXO::new(prev_tx.clone(), 0, output_index))
+            .collect();
+        wallet.update_unspent_tx_outputs(utxos);
+        wallet
+    }
+
+    #[test]
+    fn test_select_utxos() {
+        let wallet = wallet_with_utxos(&[dec!(1.0), dec!(5.0), dec!(10.0), dec!(3.01)]);
+
+        // an exact match beats the largest UTXO
+        let selected = wallet.select_utxos(dec!(3.0)).unwrap();
+        assert_eq!(selected.len(), 1);
+        assert_eq!(selected[0].get_amount(), dec!(3.01));
+
+        // largest first
+        let selected = wallet.select_utxos(dec!(12.0)).unwrap();
+        assert_eq!(
+            selected.iter().map(UTXO::get_amount).collect::<Vec<_>>(),
+            vec![dec!(10.0), dec!(5.0)]
+        );
+
+        // UTXOs summing up beyond `Decimal` are an error, not a panic
+        let half = Decimal::MAX / dec!(2) + dec!(1);
+        let wallet = wallet_with_utxos(&[half, half]);
+        assert!(matches!(
+            wallet.select_utxos(Decimal::MAX - dec!(100)),
+            Err(RustyCoinError::AmountOverflow)
+        ));
+    }
+
+    #[test]
+    fn test_send_with_insufficient_funds() {
+        let mut wallet = wallet_with_utxos(&[dec!(1.0), dec!(2.0)]);
+        let receiver = Wallet::new();
+
+        // the transaction fee can't be covered
+        assert!(matches!(
+            wallet.send(vec![(dec!(3.0), receiver.get_address())]),
+            Err(InsufficientFunds)
+        ));
+        assert!(wallet
+            .send(vec![(dec!(3.0) - DEFAULT_TX_FEE, receiver.get_address())])
+            .is_ok());
+    }
+
+    #[test]
+    fn test_send_with_change() {
+        let mut wallet = wallet_with_utxos(&[dec!(50.0)]);
+        let receiver = Wallet::new();
+
+        let tx = wallet
+            .send(vec![(dec!(20.0), receiver.get_address())])
+            .unwrap();
+        assert_eq!(tx.get_outputs().len(), 2);
+        assert_eq!(tx.get_outputs()[0].get_amount(), dec!(20.0));
+        assert_eq!(
+            tx.get_outputs()[0].get_locking_script(),
+            &receiver.get_address().to_vec()
+        );
+        assert_eq!(
+            tx.get_outputs()[1].get_amount(),
+            dec!(30.0) - DEFAULT_TX_FEE
+        );
+        assert_eq!(tx.get_transaction_fee(), DEFAULT_TX_FEE);
+
+        // the change goes to a fresh address of the wallet
+        let change_address = wallet.get_addresses()[1];
+        assert_ne!(change_address, wallet.get_address());
+        assert!(wallet.is_own_address(&change_address));
+        assert_eq!(
+            tx.get_outputs()[1].get_locking_script(),
+            &change_address.to_vec()
+        );
+
+        // every transaction gets a new change address
+        let tx = wallet
+            .send(vec![(dec!(10.0), receiver.get_address())])
+            .unwrap();
+        assert_eq!(wallet.get_addresses().len(), 3);
+        assert_eq!(
+            tx.get_outputs()[1].get_locking_script(),
+            &wallet.get_addresses()[2].to_vec()
+        );
+
+        // dust change is left as transaction fee
+        let tx = wallet
+            .send(vec![(dec!(49.985), receiver.get_address())])
+            .unwrap();
+        assert_eq!(tx.get_outputs().len(), 1);
+        assert_eq!(tx.get_transaction_fee(), dec!(0.015));
+        assert_eq!(wallet.get_addresses().len(), 3);
+    }
+
+    #[test]
+    fn test_spend_change() {
+        let mut wallet = wallet_with_utxos(&[dec!(50.0)]);
+        let tx = wallet
+            .send(vec![(dec!(20.0), Wallet::new().get_address())])
+            .unwrap();
+
+        // the change output is signed with the key of the change address
+        wallet.update_unspent_tx_outputs(vec![UTXO::new(tx.clone(), 1, 1)]);
+        let spending_tx = wallet
+            .send(vec![(dec!(10.0), Wallet::new().get_address())])
+            .unwrap();
+        assert!(Transaction::verify_scripts(
+            &spending_tx,
+            0,
+            spending_tx.get_inputs()[0].get_unlock_script(),
+            tx.get_outputs()[1].get_locking_script()
+        ));
+
+        // a wallet restored from the same secret key derives the same change addresses
+        let mut restored = Wallet::from_secret_key(wallet.secret_key);
+        restored.new_change_address();
+        restored.new_change_address();
+        assert_eq!(restored.get_addresses(), wallet.get_addresses());
+    }
+
+    #[test]
+    fn test_address_string() {
+        let wallet = Wallet::new();
+        let address = wallet.address_string();
+        assert_eq!(
+            HashValue::from_address_string(&address).unwrap(),
+            wallet.get_address()
+        );
+    }
+
+    #[test]
+    fn test_wif_round_trip() {
+        let wallet = Wallet::new();
+        let wif = wallet.to_wif();
+        assert_eq!(Wallet::from_wif(&wif).unwrap(), wallet);
+
+        // a known test vector, compressed
+        let wallet =
+            Wallet::from_wif("KwdMAjGmerYanjeui5SHS7JkmpZvVipYvB2LJGU1ZxJwYvP98617").unwrap();
+        assert_eq!(
+            hex::encode(&wallet.secret_key[..]),
+            "0c28fca386c7a227600b2fe50b7cae11ec86d3bf1fbe471be89827e19d72aa1d"
+        );
+        // and uncompressed
+        let wallet =
+            Wallet::from_wif("5HueCGU8rMjxEXxiPuD5BDku4MkFqeZyd4dZ1jvhTVqvbTLvyTJ").unwrap();
+        assert_eq!(
+            hex::encode(&wallet.secret_key[..]),
+            "0c28fca386c7a227600b2fe50b7cae11ec86d3bf1fbe471be89827e19d72aa1d"
+        );
+    }
+
+    #[test]
+    fn test_wif_bad_checksum() {
+        let mut bytes = bs58::decode(Wallet::new().to_wif()).into_vec().unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0x01;
+        assert!(matches!(
+            Wallet::from_wif(&bs58::encode(bytes).into_string()),
+            Err(RustyCoinError::InvalidPrivateKey)
+        ));
+
+        assert!(matches!(
+            Wallet::from_wif("not base58 0OIl"),
+            Err(RustyCoinError::InvalidPrivateKey)
+        ));
+        // an address is not a private key
+        assert!(matches!(
+            Wallet::from_wif(&Wallet::new().address_string()),
+            Err(RustyCoinError::InvalidPrivateKey)
+        ));
+    }
+
+    #[test]
+    fn test_export_and_import_encrypted() {
+        const FILE_PATH: &str = "./test_encrypted_key.rsckey";
+        let mut wallet = Wallet::new();
+        wallet.new_change_address();
+        wallet.new_change_address();
+        wallet.save_encrypted(FILE_PATH, "correct horse").unwrap();
+
+        // the secret key is not stored in the clear
+        let content = fs::read(FILE_PATH).unwrap();
+        assert!(!content
+            .windows(32)
+            .any(|window| window == &wallet.secret_key[..]));
+
+        let wallet_copied = Wallet::load_encrypted(FILE_PATH, "correct horse");
+        let wallet_wrong = Wallet::load_encrypted(FILE_PATH, "battery staple");
+        // delete the key file after testing
+        fs::remove_file(FILE_PATH).expect("Delete Fail: No such file");
+
+        // the change keys are derived again
+        let wallet_copied = wallet_copied.unwrap();
+        assert_eq!(wallet_copied.get_addresses().len(), 3);
+        assert_eq!(wallet, wallet_copied);
+        assert!(wallet_wrong.is_err());
+    }
+
+    #[test]
+    fn test_mnemonic() {
+        let (wallet, phrase) = Wallet::generate_mnemonic();
+        assert_eq!(phrase.split_whitespace().count(), 12);
+
+        let wallet_restored = Wallet::from_mnemonic(&phrase, "").unwrap();
+        assert_eq!(wallet.get_address(), wallet_restored.get_address());
+
+        // another passphrase, another wallet
+        let wallet_with_passphrase = Wallet::from_mnemonic(&phrase, "TREZOR").unwrap();
+        assert_ne!(wallet.get_address(), wallet_with_passphrase.get_address());
+
+        // a valid word list, and the same words with a wrong checksum
+        let valid_phrase = format!("{} about", "abandon ".repeat(11).trim_end());
+        let invalid_phrase = "abandon ".repeat(12);
+        assert!(Wallet::from_mnemonic(&valid_phrase, "").is_ok());
+        assert!(matches!(
+            Wallet::from_mnemonic(&invalid_phrase, ""),
+            Err(RustyCoinError::InvalidMnemonic)
+        ));
+        assert!(Wallet::from_mnemonic("not a valid mnemonic", "").is_err());
+    }
+
+    #[test]
+    fn test_sign_message() {
+        let wallet = Wallet::new();
+        let msg = b"login challenge 42";
+        let signature = wallet.sign_message(msg);
+        assert!(verify_message(&wallet.get_address(), msg, &signature));
+
+        // tampered message
+        assert!(!verify_message(
+            &wallet.get_address(),
+            b"login challenge 43",
+            &signature
+        ));
+
+        // wrong address
+        assert!(!verify_message(
+            &Wallet::new().get_address(),
+            msg,
+            &signature
+        ));
+
+        // malformed recovery id
+        let mut bytes = [0u8; 65];
+        bytes.copy_from_slice(&signature[..]);
+        bytes[0] = 4;
+        assert!(!verify_message(
+            &wallet.get_address(),
+            msg,
+            &Signature::new(bytes)
+        ));
+    }
 }
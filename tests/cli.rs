@@ -0,0 +1,101 @@
+use rusty_coin::types::HashValue;
+use rusty_coin::wallet::Wallet;
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+
+#[test]
+fn test_new_wallet() {
+    const FILE_PATH: &str = "./test_cli_key.rsckey";
+    let output = Command::new(env!("CARGO_BIN_EXE_rusty_coin"))
+        .args(["new-wallet", "--out", FILE_PATH])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // the address of the new wallet is printed, the key file restores the same wallet
+    let address = String::from_utf8(output.stdout).unwrap();
+    let wallet = Wallet::build_from_private_key_file(FILE_PATH).unwrap();
+    assert_eq!(
+        HashValue::from_address_string(address.trim()).unwrap(),
+        wallet.get_address()
+    );
+    std::fs::remove_file(FILE_PATH).expect("Delete Fail: No such file");
+
+    // the output path is required
+    let output = Command::new(env!("CARGO_BIN_EXE_rusty_coin"))
+        .arg("new-wallet")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
+
+/// a `run-node` process, killed when dropped so a failing test doesn't leak it
+struct NodeProcess(Child);
+
+impl Drop for NodeProcess {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+/// start `run-node` with the flags, return the process and the base url it listens on
+fn start_node(flags: &[&str]) -> (NodeProcess, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rusty_coin"))
+        .arg("run-node")
+        .args(flags)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+    let node = NodeProcess(child);
+    let base_url = lines
+        .by_ref()
+        .map(Result::unwrap)
+        .find_map(|line| {
+            line.strip_prefix("Listening on localhost ")
+                .map(str::to_string)
+        })
+        .expect("the node exited before listening");
+    // keep draining the output, so the node never blocks on a full pipe
+    std::thread::spawn(move || lines.for_each(drop));
+    (node, base_url)
+}
+
+/// the number of blocks of a node, from its `/height` endpoint
+async fn height(base_url: &str) -> usize {
+    let response = reqwest::get(format!("{base_url}/height")).await.unwrap();
+    response.text().await.unwrap().parse().unwrap()
+}
+
+#[tokio::test]
+async fn test_run_node_sync() {
+    let wallet = Wallet::new();
+    let address = wallet.get_address().to_address_string();
+    let (_root, root_url) = start_node(&["--mine", &address]);
+
+    // let the root node mine a block on top of the genesis block
+    let mut attempts = 0;
+    while height(&root_url).await < 2 {
+        attempts += 1;
+        assert!(attempts < 600, "the root node did not mine a block");
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    // a node joining the network shares the genesis block of the root node, so it catches up with its chain
+    let (_node, node_url) = start_node(&["--peer", &root_url]);
+    assert!(height(&node_url).await >= 2);
+    for index in 0..2 {
+        let root_block = reqwest::get(format!("{root_url}/block/{index}"))
+            .await
+            .unwrap();
+        let node_block = reqwest::get(format!("{node_url}/block/{index}"))
+            .await
+            .unwrap();
+        assert_eq!(
+            root_block.text().await.unwrap(),
+            node_block.text().await.unwrap()
+        );
+    }
+}
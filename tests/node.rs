@@ -0,0 +1,477 @@
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use rusty_coin::block::{Block, BlockHeader};
+use rusty_coin::blockchain::{Blockchain, COINBASE_MATURITY};
+use rusty_coin::node::{
+    broadcast_block, join_network, ping_peers, router, NodeState, MAX_BLOCKS_PER_REQUEST,
+    MAX_PEER_FAILURES,
+};
+use rusty_coin::types::HashValue;
+use rusty_coin::wallet::Wallet;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// serve the router of a node on a random local port, return the base url
+async fn spawn_node(blockchain: Blockchain) -> String {
+    spawn_node_with_state(NodeState::new(blockchain)).await
+}
+
+/// serve the router of a node with a given state, e.g. shared with the test
+async fn spawn_node_with_state(state: NodeState) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = router(state);
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{addr}")
+}
+
+/// poll `/height` of a node until it reaches `height`, panic after about 5 seconds
+async fn wait_for_height(base_url: &str, height: usize) {
+    for _ in 0..50 {
+        let response = reqwest::get(format!("{base_url}/height")).await.unwrap();
+        if response.text().await.unwrap() == height.to_string() {
+            return;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    panic!("{base_url} did not reach height {height}");
+}
+
+/// mine a block at a trivial difficulty, paying the reward to the receivers
+fn mine_block(blockchain: &mut Blockchain, receivers: Vec<(HashValue, Decimal)>) {
+    blockchain.add_block(
+        blockchain
+            .generate_new_block(
+                receivers,
+                "0.1v test".to_string(),
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs(),
+                0x207FFFFF_u32,
+                Some(vec![]),
+            )
+            .unwrap(),
+    );
+}
+
+/// the genesis block and one mined block
+fn two_block_chain() -> Blockchain {
+    let mut blockchain = Blockchain::new("hello world");
+    mine_block(
+        &mut blockchain,
+        vec![(Wallet::new().get_address(), dec!(50.0))],
+    );
+    blockchain
+}
+
+#[tokio::test]
+async fn test_query_blocks() {
+    let blockchain = two_block_chain();
+    let base_url = spawn_node(blockchain.clone()).await;
+
+    let response = reqwest::get(format!("{base_url}/height")).await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await.unwrap(), "2");
+
+    for index in 0..2 {
+        let response = reqwest::get(format!("{base_url}/block/{index}"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let block: Block = serde_json::from_str(&response.text().await.unwrap()).unwrap();
+        assert_eq!(&block, blockchain.get_block(index).unwrap());
+    }
+
+    let response = reqwest::get(format!("{base_url}/block/2")).await.unwrap();
+    assert_eq!(response.status(), 404);
+    let response = reqwest::get(format!("{base_url}/block/abc")).await.unwrap();
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+async fn test_estimate_fee() {
+    let blockchain = two_block_chain();
+    let base_url = spawn_node(blockchain.clone()).await;
+
+    let response = reqwest::get(format!("{base_url}/estimatefee/3"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let fee_rate: Decimal = serde_json::from_str(&response.text().await.unwrap()).unwrap();
+    assert_eq!(fee_rate, blockchain.estimate_fee_rate(3));
+
+    let response = reqwest::get(format!("{base_url}/estimatefee/abc"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+async fn test_submit_transactions() {
+    let mut blockchain = Blockchain::new("hello world");
+    let mut wallet = Wallet::new();
+    let receiver = Wallet::new();
+    mine_block(&mut blockchain, vec![(wallet.get_address(), dec!(50.0))]);
+    // wait for the coinbase output to mature
+    for _ in 0..COINBASE_MATURITY {
+        mine_block(&mut blockchain, vec![]);
+    }
+    wallet.update_unspent_tx_outputs(blockchain.get_utxos_for(&wallet.get_address(), 0));
+
+    let tx = wallet
+        .send(vec![(dec!(20.0), receiver.get_address())])
+        .unwrap();
+    let double_spend_tx = wallet
+        .send(vec![(dec!(30.0), receiver.get_address())])
+        .unwrap();
+
+    let base_url = spawn_node(blockchain).await;
+    let client = reqwest::Client::new();
+
+    let response = client
+        .post(format!("{base_url}/tx"))
+        .header("content-type", "application/json")
+        .body(serde_json::to_string(&tx).unwrap())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 202);
+    let tx_id: HashValue = serde_json::from_str(&response.text().await.unwrap()).unwrap();
+    assert_eq!(tx_id, tx.get_transaction_id());
+
+    let response = client
+        .post(format!("{base_url}/tx"))
+        .header("content-type", "application/json")
+        .body(serde_json::to_string(&double_spend_tx).unwrap())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    assert_eq!(
+        response.text().await.unwrap(),
+        "the transaction spends an output already spent in the transaction pool"
+    );
+}
+
+#[tokio::test]
+async fn test_query_headers() {
+    let blockchain = two_block_chain();
+    let base_url = spawn_node(blockchain.clone()).await;
+
+    let response = reqwest::get(format!("{base_url}/headers")).await.unwrap();
+    assert_eq!(response.status(), 200);
+    let headers: Vec<BlockHeader> = serde_json::from_str(&response.text().await.unwrap()).unwrap();
+    assert_eq!(
+        headers,
+        blockchain.iter().map(Block::header).collect::<Vec<_>>()
+    );
+
+    let response = reqwest::get(format!("{base_url}/headers?from=1"))
+        .await
+        .unwrap();
+    let headers: Vec<BlockHeader> = serde_json::from_str(&response.text().await.unwrap()).unwrap();
+    assert_eq!(headers.len(), 1);
+    assert_eq!(headers[0], blockchain.get_block(1).unwrap().header());
+}
+
+#[tokio::test]
+async fn test_announce_blocks() {
+    let blockchain = two_block_chain();
+    let state_a = NodeState::new(blockchain.clone());
+    let state_b = NodeState::new(blockchain.clone());
+    let base_url_a = spawn_node_with_state(state_a.clone()).await;
+    let base_url_b = spawn_node_with_state(state_b.clone()).await;
+    // the nodes know each other, an announcement must not bounce between them forever
+    state_a.add_peer(base_url_b.clone()).await;
+    state_b.add_peer(base_url_a.clone()).await;
+
+    let mut miner = blockchain;
+    mine_block(&mut miner, vec![(Wallet::new().get_address(), dec!(50.0))]);
+    let block = miner.get_last_block().unwrap().clone();
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!("{base_url_a}/block"))
+        .header("content-type", "application/json")
+        .body(serde_json::to_string(&block).unwrap())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 202);
+
+    wait_for_height(&base_url_b, 3).await;
+    assert_eq!(
+        state_b.blockchain.read().await.get_last_block(),
+        Some(&block)
+    );
+    assert_eq!(state_a.blockchain.read().await.len(), 3);
+
+    // the block is already the tip now
+    let response = client
+        .post(format!("{base_url_a}/block"))
+        .header("content-type", "application/json")
+        .body(serde_json::to_string(&block).unwrap())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    // a valid block that doesn't extend the tip is rejected
+    let response = client
+        .post(format!("{base_url_a}/block"))
+        .header("content-type", "application/json")
+        .body(serde_json::to_string(miner.get_block(1).unwrap()).unwrap())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+
+    // a block mined at another difficulty than the chain requires is rejected
+    let block = miner
+        .generate_new_block(
+            vec![(Wallet::new().get_address(), dec!(50.0))],
+            "0.1v test".to_string(),
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            0x2000FFFF_u32,
+            Some(vec![]),
+        )
+        .unwrap();
+    let response = client
+        .post(format!("{base_url_a}/block"))
+        .header("content-type", "application/json")
+        .body(serde_json::to_string(&block).unwrap())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    assert_eq!(state_a.blockchain.read().await.len(), 3);
+}
+
+#[tokio::test]
+async fn test_peer_discovery() {
+    let blockchain = two_block_chain();
+    let state_a = NodeState::new(blockchain.clone());
+    let state_b = NodeState::new(blockchain.clone());
+    let state_c = NodeState::new(blockchain);
+    let base_url_a = spawn_node_with_state(state_a.clone()).await;
+    let base_url_b = spawn_node_with_state(state_b.clone()).await;
+    let base_url_c = spawn_node_with_state(state_c.clone()).await;
+
+    // node B boots pointing at the root node A
+    join_network(&state_b, &base_url_a, &base_url_b)
+        .await
+        .unwrap();
+    assert_eq!(
+        state_a.peers.read().await.iter().collect::<Vec<_>>(),
+        vec![&base_url_b]
+    );
+    assert_eq!(
+        state_b.peers.read().await.iter().collect::<Vec<_>>(),
+        vec![&base_url_a]
+    );
+
+    // node C learns about node B from the root node, joining twice changes nothing
+    join_network(&state_c, &base_url_a, &base_url_c)
+        .await
+        .unwrap();
+    join_network(&state_c, &base_url_a, &base_url_c)
+        .await
+        .unwrap();
+    assert_eq!(state_c.peers.read().await.len(), 2);
+    assert!(state_c.peers.read().await.contains(&base_url_a));
+    assert!(state_c.peers.read().await.contains(&base_url_b));
+
+    let response = reqwest::get(format!("{base_url_a}/peers")).await.unwrap();
+    assert_eq!(response.status(), 200);
+    let peers: Vec<String> = serde_json::from_str(&response.text().await.unwrap()).unwrap();
+    assert_eq!(peers.len(), 2);
+    assert!(peers.contains(&base_url_b) && peers.contains(&base_url_c));
+}
+
+#[tokio::test]
+async fn test_sync_chain() {
+    let mut blockchain_a = two_block_chain();
+    mine_block(&mut blockchain_a, vec![]);
+    let mut blockchain_b = Blockchain::from_vec(&[blockchain_a.get_block(0).unwrap().clone()]);
+    let base_url_a = spawn_node(blockchain_a.clone()).await;
+    let base_url_b = spawn_node(blockchain_b.clone()).await;
+
+    let response = reqwest::get(format!("{base_url_a}/chain")).await.unwrap();
+    assert_eq!(response.status(), 200);
+    let chain: Vec<Block> = serde_json::from_str(&response.text().await.unwrap()).unwrap();
+    assert_eq!(chain, blockchain_a.iter().cloned().collect::<Vec<_>>());
+
+    // node B catches up with the longer chain of node A
+    assert!(blockchain_b.sync_from_peer(&base_url_a).await.unwrap());
+    assert_eq!(blockchain_b, blockchain_a);
+
+    // the shorter chain of node B is ignored
+    assert!(!blockchain_a.sync_from_peer(&base_url_b).await.unwrap());
+    assert_eq!(blockchain_a.len(), 3);
+
+    // an unreachable peer is an error
+    assert!(blockchain_a
+        .sync_from_peer("http://127.0.0.1:1")
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_sync_range() {
+    let mut blockchain_a = Blockchain::new("hello world");
+    for _ in 1..20 {
+        mine_block(
+            &mut blockchain_a,
+            vec![(Wallet::new().get_address(), dec!(50.0))],
+        );
+    }
+    assert_eq!(blockchain_a.len(), 20);
+    let base_url_a = spawn_node(blockchain_a.clone()).await;
+
+    let response = reqwest::get(format!("{base_url_a}/blocks?from=18&count=5"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let blocks: Vec<Block> = serde_json::from_str(&response.text().await.unwrap()).unwrap();
+    assert_eq!(
+        blocks,
+        blockchain_a.iter().skip(18).cloned().collect::<Vec<_>>()
+    );
+    // the count is capped
+    let response = reqwest::get(format!(
+        "{base_url_a}/blocks?count={}",
+        MAX_BLOCKS_PER_REQUEST + 1
+    ))
+    .await
+    .unwrap();
+    let blocks: Vec<Block> = serde_json::from_str(&response.text().await.unwrap()).unwrap();
+    assert_eq!(blocks.len(), 20);
+
+    // node B catches up in batches of 5 blocks
+    let genesis_block = std::slice::from_ref(blockchain_a.get_block(0).unwrap());
+    let mut blockchain_b = Blockchain::from_vec(genesis_block);
+    assert!(blockchain_b.sync_range(&base_url_a, 5).await.unwrap());
+    assert_eq!(blockchain_b, blockchain_a);
+    // nothing left to sync
+    assert!(!blockchain_b.sync_range(&base_url_a, 5).await.unwrap());
+
+    // node C has forked at block 1, the longer chain of node A replaces it
+    let mut blockchain_c = Blockchain::from_vec(genesis_block);
+    mine_block(
+        &mut blockchain_c,
+        vec![(Wallet::new().get_address(), dec!(50.0))],
+    );
+    assert!(blockchain_c.sync_range(&base_url_a, 5).await.unwrap());
+    assert_eq!(blockchain_c, blockchain_a);
+
+    // a block mined at another difficulty than the chain requires is not synced
+    let mut blockchain_d = Blockchain::from_vec(genesis_block);
+    let block = blockchain_d
+        .generate_new_block(
+            vec![(Wallet::new().get_address(), dec!(50.0))],
+            "0.1v test".to_string(),
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            0x2000FFFF_u32,
+            Some(vec![]),
+        )
+        .unwrap();
+    blockchain_d.add_block(block);
+    let base_url_d = spawn_node(blockchain_d).await;
+    let mut blockchain_e = Blockchain::from_vec(genesis_block);
+    assert!(!blockchain_e.sync_range(&base_url_d, 5).await.unwrap());
+    assert_eq!(blockchain_e.len(), 1);
+
+    // an unreachable peer is an error
+    assert!(blockchain_c
+        .sync_range("http://127.0.0.1:1", 5)
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_broadcast_to_unreachable_peer() {
+    let blockchain = two_block_chain();
+    let state = NodeState::new(blockchain.clone());
+    let live_url = spawn_node_with_state(state.clone()).await;
+    let dead_url = "http://127.0.0.1:1".to_string();
+    let peers = vec![dead_url.clone(), live_url.clone()];
+
+    let mut miner = blockchain;
+    mine_block(&mut miner, vec![(Wallet::new().get_address(), dec!(50.0))]);
+    let block = miner.get_last_block().unwrap().clone();
+
+    // the dead peer doesn't stop the live one from receiving the block
+    let results = broadcast_block(peers.clone(), block.clone()).await;
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].0, dead_url);
+    assert!(results[0].1.is_err());
+    assert_eq!(results[1].0, live_url);
+    assert!(results[1].1.is_ok());
+    wait_for_height(&live_url, 3).await;
+    assert_eq!(state.blockchain.read().await.get_last_block(), Some(&block));
+
+    let results = ping_peers(peers.clone()).await;
+    assert!(results[0].1.is_err());
+    assert_eq!(results[1].1.as_deref().unwrap(), "pong");
+
+    // the dead peer is pruned once it keeps failing, the live one is kept
+    let observer = NodeState::new(Blockchain::new("hello world"));
+    for peer in &peers {
+        observer.add_peer(peer.clone()).await;
+    }
+    for failures in 1..=MAX_PEER_FAILURES {
+        let results = ping_peers(peers.clone()).await;
+        observer.record_peer_results(&results).await;
+        let known_peers = observer.peers.read().await;
+        assert_eq!(
+            known_peers.contains(&dead_url),
+            failures < MAX_PEER_FAILURES
+        );
+        assert!(known_peers.contains(&live_url));
+    }
+    assert!(observer.peer_failures.read().await.is_empty());
+}
+
+#[tokio::test]
+async fn test_pruned_node() {
+    let mut blockchain = Blockchain::new("hello world");
+    for _ in 1..20 {
+        mine_block(
+            &mut blockchain,
+            vec![(Wallet::new().get_address(), dec!(50.0))],
+        );
+    }
+    blockchain.prune(5);
+    assert_eq!(blockchain.get_pruned_height(), 15);
+    let base_url = spawn_node(blockchain.clone()).await;
+
+    // the pruned blocks are refused, rather than served without their transactions
+    for path in ["chain", "blocks", "blocks?from=14", "block/1"] {
+        let response = reqwest::get(format!("{base_url}/{path}")).await.unwrap();
+        assert_eq!(response.status(), 410, "{path}");
+    }
+
+    // the blocks kept in full are still served
+    let response = reqwest::get(format!("{base_url}/blocks?from=15"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let blocks: Vec<Block> = serde_json::from_str(&response.text().await.unwrap()).unwrap();
+    assert_eq!(
+        blocks,
+        blockchain.iter().skip(15).cloned().collect::<Vec<_>>()
+    );
+    let response = reqwest::get(format!("{base_url}/block/15")).await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    // and so are the headers of every block
+    let response = reqwest::get(format!("{base_url}/headers")).await.unwrap();
+    let headers: Vec<BlockHeader> = serde_json::from_str(&response.text().await.unwrap()).unwrap();
+    assert_eq!(headers.len(), 20);
+}